@@ -0,0 +1,86 @@
+use crate::{Expr, ExprId, ExprNode};
+use std::collections::HashMap;
+
+fn as_constant(expr: &Expr) -> Option<f64> {
+    match &**expr {
+        ExprNode::Constant { value } => Some(*value),
+        _ => None,
+    }
+}
+
+/// Adds two expressions, dropping zero terms so that derivatives stay readable.
+fn add(lhs: Expr, rhs: Expr) -> Expr {
+    match (as_constant(&lhs), as_constant(&rhs)) {
+        (Some(0.0), _) => rhs,
+        (_, Some(0.0)) => lhs,
+        _ => Expr::add(lhs, rhs),
+    }
+}
+
+/// Multiplies two expressions, folding multiplications by zero and one.
+fn mul(lhs: Expr, rhs: Expr) -> Expr {
+    match (as_constant(&lhs), as_constant(&rhs)) {
+        (Some(0.0), _) | (_, Some(0.0)) => Expr::constant(0.0),
+        (Some(1.0), _) => rhs,
+        (_, Some(1.0)) => lhs,
+        _ => Expr::mul(lhs, rhs),
+    }
+}
+
+impl Expr {
+    /// The symbolic derivative of this expression with respect to the parameter with the given
+    /// index.
+    ///
+    /// Shared sub-expressions are differentiated once, so the result shares nodes in the same
+    /// way. Higher-order derivatives are obtained by calling `grad` on the result.
+    pub fn grad(&self, index: u32) -> Expr {
+        self.grad_memo(index, &mut HashMap::new())
+    }
+
+    fn grad_memo(&self, index: u32, memo: &mut HashMap<ExprId, Expr>) -> Expr {
+        if let Some(grad) = memo.get(&self.id()) {
+            return grad.clone();
+        }
+        let grad = match &**self {
+            ExprNode::Constant { .. } => Expr::constant(0.0),
+            ExprNode::Parameter { index: i, .. } => {
+                Expr::constant(if *i == index { 1.0 } else { 0.0 })
+            }
+            ExprNode::Add { lhs, rhs } => {
+                add(lhs.grad_memo(index, memo), rhs.grad_memo(index, memo))
+            }
+            ExprNode::Mul { lhs, rhs } => {
+                let dlhs = lhs.grad_memo(index, memo);
+                let drhs = rhs.grad_memo(index, memo);
+                add(mul(dlhs, rhs.clone()), mul(lhs.clone(), drhs))
+            }
+        };
+        memo.insert(self.id(), grad.clone());
+        grad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Expr, Tracer};
+
+    #[test]
+    fn grad_of_polynomial() {
+        // f(x, y) = x * x * y + 3 * x
+        let x = Expr::parameter(0, "x".to_string());
+        let y = Expr::parameter(1, "y".to_string());
+        let xx = Expr::mul(x.clone(), x.clone());
+        let f = Expr::add(
+            Expr::mul(xx, y.clone()),
+            Expr::mul(Expr::constant(3.0), x.clone()),
+        );
+        let mut tracer = Tracer::new();
+        tracer.set_parameter(0, 2.0);
+        tracer.set_parameter(1, 5.0);
+        assert_eq!(tracer.visit(&f.grad(0)), 23.0);
+        assert_eq!(tracer.visit(&f.grad(1)), 4.0);
+        assert_eq!(tracer.visit(&f.grad(0).grad(0)), 10.0);
+        assert_eq!(tracer.visit(&f.grad(0).grad(1)), 4.0);
+        assert_eq!(f.grad(1).grad(1).to_string(), "0");
+    }
+}
@@ -1 +1,12 @@
+mod grad;
+#[cfg(feature = "xla")]
+mod lower;
 mod node;
+mod testing;
+#[cfg(feature = "xla")]
+mod vmap;
+
+pub use node::*;
+pub use testing::*;
+#[cfg(feature = "xla")]
+pub use vmap::*;
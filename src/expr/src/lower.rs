@@ -0,0 +1,61 @@
+//! Lowering of expressions to XLA, enabled by the `xla` feature.
+use crate::{Expr, ExprId, ExprNode};
+use std::collections::HashMap;
+use xla::{ElementType, Shape, XlaBuilder, XlaComputation, XlaOp};
+
+impl Expr {
+    /// Lowers this expression to ops of `builder`, the parameter with index `i` being
+    /// `params[i]`. Constants are converted to `ty`, and since all the ops are elementwise the
+    /// parameters may be arrays of a common shape.
+    ///
+    /// Shared sub-expressions are only lowered once.
+    pub fn lower(&self, builder: &XlaBuilder, params: &[XlaOp], ty: ElementType) -> XlaOp {
+        self.lower_memo(builder, params, ty, &mut HashMap::new())
+    }
+
+    fn lower_memo(
+        &self,
+        builder: &XlaBuilder,
+        params: &[XlaOp],
+        ty: ElementType,
+        memo: &mut HashMap<ExprId, XlaOp>,
+    ) -> XlaOp {
+        if let Some(op) = memo.get(&self.id()) {
+            return op.clone();
+        }
+        let op = match &**self {
+            ExprNode::Constant { value } => builder
+                .constant(*value)
+                .convert_element_type(ty.primitive_type()),
+            ExprNode::Parameter { index, name } => params
+                .get(*index as usize)
+                .cloned()
+                .unwrap_or_else(|| panic!("Parameter {} ('{}') not set", index, name)),
+            ExprNode::Add { lhs, rhs } => lhs
+                .lower_memo(builder, params, ty, memo)
+                .add(&rhs.lower_memo(builder, params, ty, memo)),
+            ExprNode::Mul { lhs, rhs } => lhs
+                .lower_memo(builder, params, ty, memo)
+                .mul(&rhs.lower_memo(builder, params, ty, memo)),
+        };
+        memo.insert(self.id(), op.clone());
+        op
+    }
+
+    /// Builds a computation evaluating this expression with `num_params` scalar parameters of
+    /// type `ty`.
+    pub fn build_computation(
+        &self,
+        num_params: u32,
+        ty: ElementType,
+    ) -> xla::Result<XlaComputation> {
+        let builder = XlaBuilder::new("expr");
+        let params = (0..num_params)
+            .map(|i| {
+                let shape = Shape::array_with_type(ty, vec![]);
+                builder.parameter(i as i64, shape, &format!("p{i}"))
+            })
+            .collect::<xla::Result<Vec<_>>>()?;
+        self.lower(&builder, &params, ty).build()
+    }
+}
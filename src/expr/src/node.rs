@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
 use std::hash::Hash;
 use std::ops::Deref;
 use std::sync::Arc;
@@ -8,7 +9,7 @@ use std::{
 };
 
 #[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
-struct ExprId(usize);
+pub struct ExprId(usize);
 
 impl Default for ExprId {
     /// Provides default generation of unique identifiers for expressions.
@@ -34,7 +35,7 @@ pub enum ExprNode {
 ///
 /// Multiple expressions can share the same sub-expression allowing computations
 /// to reuse intermediate results.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Expr {
     node: Arc<ExprNode>,
     id: ExprId,
@@ -47,19 +48,20 @@ impl Expr {
             id: ExprId::default(),
         }
     }
-    fn parameter(index: u32, name: String) -> Self {
+
+    pub fn parameter(index: u32, name: String) -> Self {
         Self::new(ExprNode::Parameter { index, name })
     }
 
-    fn constant(value: f64) -> Self {
+    pub fn constant(value: f64) -> Self {
         Self::new(ExprNode::Constant { value })
     }
 
-    fn add(lhs: Expr, rhs: Expr) -> Self {
+    pub(crate) fn add(lhs: Expr, rhs: Expr) -> Self {
         Self::new(ExprNode::Add { lhs, rhs })
     }
 
-    fn mul(lhs: Expr, rhs: Expr) -> Self {
+    pub(crate) fn mul(lhs: Expr, rhs: Expr) -> Self {
         Self::new(ExprNode::Mul { lhs, rhs })
     }
 }
@@ -81,6 +83,14 @@ impl Add for Expr {
     }
 }
 
+impl Mul for Expr {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Expr::mul(self, rhs)
+    }
+}
+
 impl From<f64> for Expr {
     fn from(value: f64) -> Self {
         Expr::constant(value)
@@ -122,6 +132,242 @@ impl Expr {
     }
 }
 
+impl Expr {
+    /// Renders the expression DAG as Graphviz DOT text.
+    ///
+    /// Shared sub-expressions are drawn once, and each node is labelled with its op, its shape
+    /// and its `ExprId`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph expr {\n    node [shape=box];\n");
+        let mut visited = HashSet::new();
+        self.write_dot(&mut out, &mut visited);
+        out.push_str("}\n");
+        out
+    }
+
+    fn write_dot(&self, out: &mut String, visited: &mut HashSet<ExprId>) {
+        if !visited.insert(self.id) {
+            return;
+        }
+        let (op, operands) = match &*self.node {
+            ExprNode::Constant { value } => (format!("constant {value}"), vec![]),
+            ExprNode::Parameter { index, name } => (format!("parameter {index} {name}"), vec![]),
+            ExprNode::Add { lhs, rhs } => ("add".to_string(), vec![("lhs", lhs), ("rhs", rhs)]),
+            ExprNode::Mul { lhs, rhs } => ("mul".to_string(), vec![("lhs", lhs), ("rhs", rhs)]),
+        };
+        let op = op.replace('\\', "\\\\").replace('"', "\\\"");
+        // Expressions only hold scalars for now.
+        let _ = writeln!(
+            out,
+            "    n{} [label=\"{op}\\nf64[]\\n{:?}\"];",
+            self.id.0, self.id
+        );
+        for (label, operand) in operands {
+            operand.write_dot(out, visited);
+            let _ = writeln!(
+                out,
+                "    n{} -> n{} [label=\"{label}\"];",
+                operand.id.0, self.id.0
+            );
+        }
+    }
+}
+
+impl Expr {
+    /// Binding strength of the infix form, atoms bind the tightest.
+    fn precedence(&self) -> u8 {
+        match &*self.node {
+            ExprNode::Add { .. } => 1,
+            ExprNode::Mul { .. } => 2,
+            ExprNode::Constant { .. } | ExprNode::Parameter { .. } => 3,
+        }
+    }
+
+    fn fmt_operand(&self, f: &mut std::fmt::Formatter<'_>, parens: bool) -> std::fmt::Result {
+        if parens {
+            write!(f, "({self})")
+        } else {
+            write!(f, "{self}")
+        }
+    }
+}
+
+/// Infix notation with the parentheses needed to preserve the tree structure, e.g.
+/// `x * (y + 2)`. Both operators are treated as left-associative so `a + (b + c)` keeps its
+/// parentheses.
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (op, lhs, rhs) = match &*self.node {
+            ExprNode::Constant { value } => return write!(f, "{value}"),
+            ExprNode::Parameter { name, .. } => return write!(f, "{name}"),
+            ExprNode::Add { lhs, rhs } => ("+", lhs, rhs),
+            ExprNode::Mul { lhs, rhs } => ("*", lhs, rhs),
+        };
+        let precedence = self.precedence();
+        lhs.fmt_operand(f, lhs.precedence() < precedence)?;
+        write!(f, " {op} ")?;
+        rhs.fmt_operand(f, rhs.precedence() <= precedence)
+    }
+}
+
+/// Error returned when parsing the SSA form of an expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// One-based line number.
+    pub line: usize,
+    pub msg: String,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Expr {
+    /// Prints the expression in a let-bound SSA form, one node per line, e.g.
+    ///
+    /// ```text
+    /// %0 = parameter 0 "x"
+    /// %1 = constant 2.0
+    /// %2 = mul %0 %1
+    /// %3 = add %2 %2
+    /// ```
+    ///
+    /// Shared nodes are named once and operands always come before their users, the last line
+    /// being the root. [`Expr::parse_ssa`] reads this form back.
+    pub fn to_ssa(&self) -> String {
+        let mut out = String::new();
+        let mut names = HashMap::new();
+        self.write_ssa(&mut out, &mut names);
+        out
+    }
+
+    fn write_ssa(&self, out: &mut String, names: &mut HashMap<ExprId, usize>) -> usize {
+        if let Some(name) = names.get(&self.id) {
+            return *name;
+        }
+        let rhs = match &*self.node {
+            ExprNode::Constant { value } => format!("constant {value:?}"),
+            ExprNode::Parameter { index, name } => format!("parameter {index} {name:?}"),
+            ExprNode::Add { lhs, rhs } => {
+                let (lhs, rhs) = (lhs.write_ssa(out, names), rhs.write_ssa(out, names));
+                format!("add %{lhs} %{rhs}")
+            }
+            ExprNode::Mul { lhs, rhs } => {
+                let (lhs, rhs) = (lhs.write_ssa(out, names), rhs.write_ssa(out, names));
+                format!("mul %{lhs} %{rhs}")
+            }
+        };
+        let name = names.len();
+        names.insert(self.id, name);
+        let _ = writeln!(out, "%{name} = {rhs}");
+        name
+    }
+
+    /// Parses the SSA form printed by [`Expr::to_ssa`], returning the node defined on the last
+    /// line. Names can be any `%`-prefixed word, empty lines and lines starting
+    /// with `#` are ignored.
+    pub fn parse_ssa(text: &str) -> Result<Expr, ParseError> {
+        let mut nodes: HashMap<&str, Expr> = HashMap::new();
+        let mut root = None;
+        for (line, content) in text.lines().enumerate() {
+            let line = line + 1;
+            let err = |msg: String| ParseError { line, msg };
+            let content = content.trim();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            let Some((name, rhs)) = content.split_once('=') else {
+                return Err(err(format!("expected `%name = ...`, got {content:?}")));
+            };
+            let name = name.trim();
+            if !name.starts_with('%') || name.len() == 1 || name.contains(char::is_whitespace) {
+                return Err(err(format!("invalid name {name:?}")));
+            }
+            if nodes.contains_key(name) {
+                return Err(err(format!("{name} is defined more than once")));
+            }
+            let rhs = rhs.trim();
+            let (op, args) = rhs.split_once(char::is_whitespace).unwrap_or((rhs, ""));
+            let args = args.trim();
+            let operand = |arg: &str| {
+                nodes
+                    .get(arg)
+                    .cloned()
+                    .ok_or_else(|| err(format!("undefined operand {arg:?}")))
+            };
+            let binary_operands = || match args.split_whitespace().collect::<Vec<_>>()[..] {
+                [lhs, rhs] => Ok((operand(lhs)?, operand(rhs)?)),
+                _ => Err(err(format!("{op} expects two operands, got {args:?}"))),
+            };
+            let expr = match op {
+                "constant" => {
+                    let value = args
+                        .parse::<f64>()
+                        .map_err(|_| err(format!("invalid constant {args:?}")))?;
+                    Expr::constant(value)
+                }
+                "parameter" => {
+                    let (index, name) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                    let index = index
+                        .parse::<u32>()
+                        .map_err(|_| err(format!("invalid parameter index {index:?}")))?;
+                    let name = parse_quoted(name.trim())
+                        .ok_or_else(|| err(format!("invalid parameter name {name:?}")))?;
+                    Expr::parameter(index, name)
+                }
+                "add" => {
+                    let (lhs, rhs) = binary_operands()?;
+                    Expr::add(lhs, rhs)
+                }
+                "mul" => {
+                    let (lhs, rhs) = binary_operands()?;
+                    Expr::mul(lhs, rhs)
+                }
+                _ => return Err(err(format!("unknown op {op:?}"))),
+            };
+            root = Some(expr.clone());
+            nodes.insert(name, expr);
+        }
+        root.ok_or_else(|| ParseError {
+            line: 0,
+            msg: "no expression defined".to_string(),
+        })
+    }
+}
+
+/// Parses a double-quoted string as printed by the `Debug` impl of `str`, undoing the escapes
+/// produced by `char::escape_debug`.
+fn parse_quoted(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                c @ ('"' | '\'' | '\\') => out.push(c),
+                'n' => out.push('\n'),
+                'r' => out.push('\r'),
+                't' => out.push('\t'),
+                '0' => out.push('\0'),
+                'u' => {
+                    let rest = chars.as_str().strip_prefix('{')?;
+                    let (hex, rest) = rest.split_once('}')?;
+                    out.push(char::from_u32(u32::from_str_radix(hex, 16).ok()?)?);
+                    chars = rest.chars();
+                }
+                _ => return None,
+            },
+            '"' => return None,
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
 pub struct Tracer {
     context: HashMap<u32, f64>,
 }
@@ -154,6 +400,11 @@ impl Tracer {
         }
     }
 
+    /// Sets the value used for the parameter with the given index.
+    pub fn set_parameter(&mut self, index: u32, value: f64) {
+        self.context.insert(index, value);
+    }
+
     pub fn visit(&mut self, expr: &Expr) -> f64 {
         expr.walk(self)
     }
@@ -192,6 +443,68 @@ mod tests {
         println!("{}", result);
     }
 
+    #[test]
+    fn expr_to_dot() {
+        let x = Expr::parameter(0, "x".to_string());
+        let y = Expr::mul(x.clone(), Expr::constant(2.0));
+        let expr = Expr::add(y.clone(), y.clone());
+        let dot = expr.to_dot();
+        assert!(dot.starts_with("digraph expr {"));
+        // The shared `y` and `x` nodes are only drawn once.
+        assert_eq!(dot.matches("[label=\"mul\\nf64[]").count(), 1);
+        assert_eq!(dot.matches("[label=\"parameter 0 x").count(), 1);
+        let y_edges = format!("n{} -> n{}", y.id.0, expr.id.0);
+        assert_eq!(dot.matches(&y_edges).count(), 2);
+        assert!(dot.contains(&format!("{:?}", x.id())));
+    }
+
+    #[test]
+    fn expr_display() {
+        let x = Expr::parameter(0, "x".to_string());
+        let y = Expr::parameter(1, "y".to_string());
+        let expr = Expr::mul(x.clone(), Expr::add(y.clone(), Expr::constant(2.0)));
+        assert_eq!(expr.to_string(), "x * (y + 2)");
+        let expr = Expr::add(Expr::mul(x.clone(), y.clone()), Expr::constant(1.5));
+        assert_eq!(expr.to_string(), "x * y + 1.5");
+        let expr = Expr::add(x.clone(), Expr::add(y, x));
+        assert_eq!(expr.to_string(), "x + (y + x)");
+    }
+
+    #[test]
+    fn expr_ssa_round_trip() {
+        let x = Expr::parameter(0, "x \"quoted\"".to_string());
+        let y = Expr::mul(x.clone(), Expr::constant(2.0));
+        let expr = Expr::add(y.clone(), y);
+        let ssa = expr.to_ssa();
+        assert_eq!(
+            ssa,
+            "%0 = parameter 0 \"x \\\"quoted\\\"\"\n%1 = constant 2.0\n%2 = mul %0 %1\n%3 = add %2 %2\n"
+        );
+        let parsed = Expr::parse_ssa(&ssa).unwrap();
+        assert_eq!(parsed.to_ssa(), ssa);
+        // The shared node is parsed back as a single node.
+        match &*parsed {
+            ExprNode::Add { lhs, rhs } => assert_eq!(lhs.id(), rhs.id()),
+            node => panic!("unexpected node {node:?}"),
+        }
+        // Names keep every character escaped by `Debug`.
+        let name = "tab\t line\n\r nul\0 quote' zwj\u{200d} bell\u{7} é";
+        let ssa = Expr::parameter(1, name.to_string()).to_ssa();
+        assert!(ssa.contains("\\u{200d}"), "{ssa}");
+        match &*Expr::parse_ssa(&ssa).unwrap() {
+            ExprNode::Parameter { name: parsed, .. } => assert_eq!(parsed, name),
+            node => panic!("unexpected node {node:?}"),
+        }
+
+        let parsed = Expr::parse_ssa("# golden\n%a = constant -1.0\n\n%b = mul %a %a\n").unwrap();
+        assert_eq!(parsed.to_string(), "-1 * -1");
+        let err = Expr::parse_ssa("%a = constant 1.0\n%b = add %a %c").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(Expr::parse_ssa("%a = constant 1.0\n%a = constant 2.0").is_err());
+        assert!(Expr::parse_ssa("%a = sub %a %a").is_err());
+        assert!(Expr::parse_ssa("").is_err());
+    }
+
     #[test]
     fn eval_mul() {
         let expr = Expr::mul(Expr::constant(2.0), Expr::constant(3.0));
@@ -0,0 +1,211 @@
+//! Utilities to test derivative rules.
+use crate::{Expr, ExprNode, Tracer};
+use std::collections::HashSet;
+use std::fmt::Display;
+
+/// The way expressions are evaluated by [`check_grads`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evaluator {
+    /// Interpreted with [`Tracer`].
+    Tracer,
+    /// Compiled and run on the XLA CPU client.
+    #[cfg(feature = "xla")]
+    Xla,
+}
+
+/// Error returned by [`check_grads`].
+#[derive(Debug)]
+pub enum GradCheckError {
+    /// An analytic derivative differs from its finite-difference estimate.
+    Mismatch {
+        evaluator: Evaluator,
+        /// The parameter indices the derivative is taken with respect to, in order, so
+        /// `[0, 1]` stands for the second derivative with respect to parameters 0 then 1.
+        wrt: Vec<u32>,
+        analytic: f64,
+        numeric: f64,
+    },
+    /// The expression uses a parameter with no corresponding input.
+    MissingInput { index: u32 },
+    #[cfg(feature = "xla")]
+    Xla(xla::Error),
+}
+
+impl Display for GradCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mismatch {
+                evaluator,
+                wrt,
+                analytic,
+                numeric,
+            } => write!(
+                f,
+                "derivative with respect to parameters {wrt:?} evaluated with {evaluator:?}: \
+                 analytic {analytic}, finite differences {numeric}"
+            ),
+            Self::MissingInput { index } => write!(f, "no input for parameter {index}"),
+            #[cfg(feature = "xla")]
+            Self::Xla(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for GradCheckError {}
+
+#[cfg(feature = "xla")]
+impl From<xla::Error> for GradCheckError {
+    fn from(err: xla::Error) -> Self {
+        Self::Xla(err)
+    }
+}
+
+fn parameter_indices(expr: &Expr, visited: &mut HashSet<crate::ExprId>, out: &mut Vec<u32>) {
+    if !visited.insert(expr.id()) {
+        return;
+    }
+    match &**expr {
+        ExprNode::Constant { .. } => {}
+        ExprNode::Parameter { index, .. } => out.push(*index),
+        ExprNode::Add { lhs, rhs } | ExprNode::Mul { lhs, rhs } => {
+            parameter_indices(lhs, visited, out);
+            parameter_indices(rhs, visited, out);
+        }
+    }
+}
+
+fn eval_tracer(expr: &Expr, inputs: &[f64]) -> f64 {
+    let mut tracer = Tracer::new();
+    for (index, value) in inputs.iter().enumerate() {
+        tracer.set_parameter(index as u32, *value);
+    }
+    tracer.visit(expr)
+}
+
+#[cfg(feature = "xla")]
+fn eval_xla(client: &xla::PjRtClient, expr: &Expr, inputs: &[f64]) -> xla::Result<f64> {
+    let comp = expr.build_computation(inputs.len() as u32, xla::ElementType::F64)?;
+    let exec = client.compile_with_default_options(&comp)?;
+    let args = inputs
+        .iter()
+        .map(|v| client.copy_host_buffer(&[*v], &[]))
+        .collect::<xla::Result<xla::BufferArgsOwned>>()?;
+    let out = exec.execute_buffers(args)?;
+    Ok(out[0].to_literal_sync()?.typed_buf::<f64>()?[0])
+}
+
+/// Checks the derivatives of `f` up to the given `order` against central finite differences,
+/// with `inputs[i]` being the value of the parameter with index `i`.
+///
+/// Like `jax.test_util.check_grads`, derivatives of order `k` are compared to the finite
+/// differences of the analytic derivatives of order `k - 1`, using a step of `eps`. Values
+/// match when they differ by at most `rtol` times the largest of their magnitudes and 1, so
+/// `rtol` acts as an absolute tolerance for small derivatives. Every input is a scalar so a
+/// disagreement is identified by the parameters the derivative is taken with respect to.
+///
+/// Expressions are evaluated with [`Tracer`] and, with the `xla` feature, through the compiled
+/// XLA path as well.
+pub fn check_grads(
+    f: &Expr,
+    inputs: &[f64],
+    order: usize,
+    eps: f64,
+    rtol: f64,
+) -> Result<(), GradCheckError> {
+    let mut indices = vec![];
+    parameter_indices(f, &mut HashSet::new(), &mut indices);
+    if let Some(index) = indices.into_iter().find(|i| *i as usize >= inputs.len()) {
+        return Err(GradCheckError::MissingInput { index });
+    }
+    #[cfg(feature = "xla")]
+    let client = xla::PjRtClient::cpu()?;
+    let eval = |evaluator: Evaluator, expr: &Expr, inputs: &[f64]| -> Result<f64, GradCheckError> {
+        match evaluator {
+            Evaluator::Tracer => Ok(eval_tracer(expr, inputs)),
+            #[cfg(feature = "xla")]
+            Evaluator::Xla => Ok(eval_xla(&client, expr, inputs)?),
+        }
+    };
+    let evaluators = [
+        Evaluator::Tracer,
+        #[cfg(feature = "xla")]
+        Evaluator::Xla,
+    ];
+
+    // The analytic derivatives of the previous order, along with the parameters they are taken
+    // with respect to.
+    let mut derivatives = vec![(vec![], f.clone())];
+    for _ in 0..order {
+        let mut next = vec![];
+        for (wrt, g) in derivatives.iter() {
+            for index in 0..inputs.len() {
+                let dg = g.grad(index as u32);
+                let mut wrt = wrt.clone();
+                wrt.push(index as u32);
+                for evaluator in evaluators {
+                    let analytic = eval(evaluator, &dg, inputs)?;
+                    let mut shifted = inputs.to_vec();
+                    shifted[index] = inputs[index] + eps;
+                    let plus = eval(evaluator, g, &shifted)?;
+                    shifted[index] = inputs[index] - eps;
+                    let minus = eval(evaluator, g, &shifted)?;
+                    let numeric = (plus - minus) / (2. * eps);
+                    let scale = analytic.abs().max(numeric.abs()).max(1.);
+                    let diff = (analytic - numeric).abs();
+                    if diff.is_nan() || diff > rtol * scale {
+                        return Err(GradCheckError::Mismatch {
+                            evaluator,
+                            wrt,
+                            analytic,
+                            numeric,
+                        });
+                    }
+                }
+                next.push((wrt, dg));
+            }
+        }
+        derivatives = next;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_polynomial_grads() {
+        let x = Expr::parameter(0, "x".to_string());
+        let y = Expr::parameter(1, "y".to_string());
+        let xy = Expr::mul(x.clone(), y.clone());
+        let f = Expr::add(Expr::mul(xy.clone(), xy), Expr::mul(Expr::constant(3.0), x));
+        check_grads(&f, &[1.5, -2.0], 2, 1e-3, 1e-5).unwrap();
+        assert!(matches!(
+            check_grads(&f, &[1.5], 1, 1e-3, 1e-5),
+            Err(GradCheckError::MissingInput { index: 1 })
+        ));
+    }
+
+    #[test]
+    fn report_mismatch() {
+        // The central difference of y * x^3 is off by y * eps^2, which a coarse step exposes.
+        let x = Expr::parameter(0, "x".to_string());
+        let y = Expr::parameter(1, "y".to_string());
+        let f = Expr::mul(Expr::mul(Expr::mul(x.clone(), x.clone()), x), y);
+        check_grads(&f, &[2.0, 1.0], 1, 1e-4, 1e-5).unwrap();
+        match check_grads(&f, &[2.0, 1.0], 1, 0.1, 1e-5) {
+            Err(GradCheckError::Mismatch {
+                evaluator,
+                wrt,
+                analytic,
+                numeric,
+            }) => {
+                assert_eq!(evaluator, Evaluator::Tracer);
+                assert_eq!(wrt, [0]);
+                assert_eq!(analytic, 12.0);
+                assert!((numeric - 12.01).abs() < 1e-9);
+            }
+            res => panic!("unexpected result {res:?}"),
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Automatic batching of expressions lowered to XLA, enabled by the `xla` feature.
+use crate::{Expr, ExprId, ExprNode};
+use std::collections::HashMap;
+use std::fmt::Display;
+use xla::{ElementType, XlaBuilder, XlaOp};
+
+/// Error returned by functions transformed with [`vmap`].
+#[derive(Debug)]
+pub enum VmapError {
+    /// The number of inputs differs from the length of `in_axes`.
+    WrongNumberOfInputs {
+        expected: usize,
+        got: usize,
+    },
+    /// Every entry of `in_axes` is `None`, so there is no batch size to map over.
+    NoBatchedInput,
+    /// A batch axis is out of bounds for the rank of its input or of the output.
+    AxisOutOfBounds {
+        axis: i64,
+        rank: usize,
+    },
+    /// Two inputs have different sizes along their batch axes.
+    BatchSizeMismatch {
+        input: usize,
+        size: i64,
+        expected: i64,
+    },
+    Xla(xla::Error),
+}
+
+impl Display for VmapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::WrongNumberOfInputs { expected, got } => {
+                write!(f, "expected {expected} inputs, got {got}")
+            }
+            Self::NoBatchedInput => write!(f, "vmap requires at least one batched input"),
+            Self::AxisOutOfBounds { axis, rank } => {
+                write!(f, "batch axis {axis} out of bounds for rank {rank}")
+            }
+            Self::BatchSizeMismatch {
+                input,
+                size,
+                expected,
+            } => write!(
+                f,
+                "input {input} has batch size {size}, expected {expected}"
+            ),
+            Self::Xla(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for VmapError {}
+
+impl From<xla::Error> for VmapError {
+    fn from(err: xla::Error) -> Self {
+        Self::Xla(err)
+    }
+}
+
+fn normalize_axis(axis: i64, rank: usize) -> Result<usize, VmapError> {
+    let normalized = if axis < 0 { axis + rank as i64 } else { axis };
+    if normalized < 0 || normalized >= rank as i64 {
+        return Err(VmapError::AxisOutOfBounds { axis, rank });
+    }
+    Ok(normalized as usize)
+}
+
+/// Moves dimension `from` of an array of the given rank to position `to`.
+fn move_axis(op: &XlaOp, rank: usize, from: usize, to: usize) -> XlaOp {
+    if from == to {
+        return op.clone();
+    }
+    let mut perm: Vec<i64> = (0..rank as i64).filter(|d| *d != from as i64).collect();
+    perm.insert(to, from as i64);
+    op.transpose(&perm)
+}
+
+/// A lowered value together with the position of its batch dimension, `None` standing for a
+/// value that is the same for every element of the batch.
+#[derive(Clone)]
+struct Batched {
+    op: XlaOp,
+    bdim: Option<usize>,
+}
+
+struct Batcher<'a> {
+    builder: &'a XlaBuilder,
+    inputs: &'a [Batched],
+    ty: ElementType,
+    batch_size: i64,
+    memo: HashMap<ExprId, Batched>,
+}
+
+impl Batcher<'_> {
+    /// Moves the batch dimension of `value` to the front, broadcasting unbatched values unless
+    /// they are scalars, which XLA broadcasts implicitly.
+    fn to_front(&self, value: &Batched) -> xla::Result<XlaOp> {
+        let rank = value.op.array_shape()?.dims().len();
+        Ok(match value.bdim {
+            Some(bdim) => move_axis(&value.op, rank, bdim, 0),
+            None if rank == 0 => value.op.clone(),
+            None => value.op.broadcast(&[self.batch_size]),
+        })
+    }
+
+    /// The batching rule shared by the elementwise binary ops: operands batched along the same
+    /// dimension are combined as is, otherwise batch dimensions are moved to the front. An
+    /// operand that is a scalar for each element of the batch is broadcast against the other.
+    fn binary(
+        &self,
+        lhs: &Batched,
+        rhs: &Batched,
+        f: impl Fn(&XlaOp, &XlaOp) -> XlaOp,
+    ) -> xla::Result<Batched> {
+        match (lhs.bdim, rhs.bdim) {
+            (None, None) => {
+                return Ok(Batched {
+                    op: f(&lhs.op, &rhs.op),
+                    bdim: None,
+                });
+            }
+            (Some(l), Some(r)) if l == r => {
+                let lhs_rank = lhs.op.array_shape()?.dims().len();
+                let rhs_rank = rhs.op.array_shape()?.dims().len();
+                if lhs_rank == rhs_rank {
+                    return Ok(Batched {
+                        op: f(&lhs.op, &rhs.op),
+                        bdim: Some(l),
+                    });
+                }
+            }
+            _ => {}
+        }
+        let mut lhs = self.to_front(lhs)?;
+        let mut rhs = self.to_front(rhs)?;
+        let lhs_dims = lhs.array_shape()?.dims().to_vec();
+        let rhs_dims = rhs.array_shape()?.dims().to_vec();
+        if lhs_dims.len() == 1 && rhs_dims.len() > 1 {
+            lhs = lhs.broadcast_in_dim(&rhs_dims, &[0]);
+        } else if rhs_dims.len() == 1 && lhs_dims.len() > 1 {
+            rhs = rhs.broadcast_in_dim(&lhs_dims, &[0]);
+        }
+        Ok(Batched {
+            op: f(&lhs, &rhs),
+            bdim: Some(0),
+        })
+    }
+
+    fn visit(&mut self, expr: &Expr) -> Result<Batched, VmapError> {
+        if let Some(value) = self.memo.get(&expr.id()) {
+            return Ok(value.clone());
+        }
+        let value = match &**expr {
+            ExprNode::Constant { value } => Batched {
+                op: self
+                    .builder
+                    .constant(*value)
+                    .convert_element_type(self.ty.primitive_type()),
+                bdim: None,
+            },
+            ExprNode::Parameter { index, name } => self
+                .inputs
+                .get(*index as usize)
+                .cloned()
+                .unwrap_or_else(|| panic!("Parameter {} ('{}') not set", index, name)),
+            ExprNode::Add { lhs, rhs } => {
+                let lhs = self.visit(lhs)?;
+                let rhs = self.visit(rhs)?;
+                self.binary(&lhs, &rhs, |l, r| l.add(r))?
+            }
+            ExprNode::Mul { lhs, rhs } => {
+                let lhs = self.visit(lhs)?;
+                let rhs = self.visit(rhs)?;
+                self.binary(&lhs, &rhs, |l, r| l.mul(r))?
+            }
+        };
+        self.memo.insert(expr.id(), value.clone());
+        Ok(value)
+    }
+}
+
+/// Vectorizes `f` over a batch dimension, in the manner of `jax.vmap`.
+///
+/// `f` is traced once on one parameter per entry of `in_axes`, the parameter with index `i`
+/// standing for a single element of the batch of input `i`. The returned function takes the
+/// batched inputs as ops of a common builder: `in_axes[i]` is the batch axis of input `i`, or
+/// `None` for an input shared by the whole batch, and negative axes count from the last one.
+/// The result has its batch dimension at `out_axes`.
+///
+/// Batching rules move batch dimensions only when operands disagree on them, so inputs
+/// batched along the same axis are combined without any transpose. Constants have the element
+/// type of the first batched input.
+pub fn vmap<F>(
+    f: F,
+    in_axes: &[Option<i64>],
+    out_axes: i64,
+) -> impl Fn(&[XlaOp]) -> Result<XlaOp, VmapError> + use<F>
+where
+    F: Fn(&[Expr]) -> Expr,
+{
+    let params: Vec<Expr> = (0..in_axes.len())
+        .map(|i| Expr::parameter(i as u32, format!("p{i}")))
+        .collect();
+    let expr = f(&params);
+    let in_axes = in_axes.to_vec();
+    move |inputs: &[XlaOp]| {
+        if inputs.len() != in_axes.len() {
+            return Err(VmapError::WrongNumberOfInputs {
+                expected: in_axes.len(),
+                got: inputs.len(),
+            });
+        }
+        let mut batch = None;
+        let mut batched = Vec::with_capacity(inputs.len());
+        for (input, (op, axis)) in inputs.iter().zip(in_axes.iter()).enumerate() {
+            let bdim = match axis {
+                None => None,
+                Some(axis) => {
+                    let shape = op.array_shape()?;
+                    let bdim = normalize_axis(*axis, shape.dims().len())?;
+                    let size = shape.dims()[bdim];
+                    match batch {
+                        None => batch = Some((size, shape.ty())),
+                        Some((expected, _)) if expected != size => {
+                            return Err(VmapError::BatchSizeMismatch {
+                                input,
+                                size,
+                                expected,
+                            });
+                        }
+                        Some(_) => {}
+                    }
+                    Some(bdim)
+                }
+            };
+            batched.push(Batched {
+                op: op.clone(),
+                bdim,
+            });
+        }
+        let (batch_size, ty) = batch.ok_or(VmapError::NoBatchedInput)?;
+        let mut batcher = Batcher {
+            builder: inputs[0].builder(),
+            inputs: &batched,
+            ty,
+            batch_size,
+            memo: HashMap::new(),
+        };
+        let out = batcher.visit(&expr)?;
+        let op = match out.bdim {
+            None => out.op.broadcast(&[batch_size]),
+            Some(_) => batcher.to_front(&out)?,
+        };
+        let rank = op.array_shape()?.dims().len();
+        let out_axis = normalize_axis(out_axes, rank)?;
+        Ok(move_axis(&op, rank, 0, out_axis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xla::{BufferArgsRef, PjRtClient};
+
+    fn run(op: &XlaOp) -> Result<Vec<f64>, VmapError> {
+        let client = PjRtClient::cpu()?;
+        let exec = client.compile_with_default_options(&op.build()?)?;
+        let out = exec.execute_buffers(BufferArgsRef::default())?;
+        Ok(out[0].to_literal_sync()?.typed_buf::<f64>()?.to_vec())
+    }
+
+    #[test]
+    fn vmap_elementwise() -> Result<(), VmapError> {
+        let builder = XlaBuilder::new("test");
+        let xs = builder.constant_vector(&[1f64, 2., 3.]);
+        let y = builder.constant(10f64);
+        // f(x, y) = x * y + 2 * x
+        let f = vmap(
+            |p| {
+                Expr::add(
+                    Expr::mul(p[0].clone(), p[1].clone()),
+                    Expr::mul(Expr::constant(2.0), p[0].clone()),
+                )
+            },
+            &[Some(0), None],
+            0,
+        );
+        assert_eq!(run(&f(&[xs.clone(), y.clone()])?)?, [12., 24., 36.]);
+        assert!(matches!(
+            f(&[xs.clone()]),
+            Err(VmapError::WrongNumberOfInputs {
+                expected: 2,
+                got: 1
+            })
+        ));
+        let f = vmap(|p| p[0].clone(), &[None], 0);
+        assert!(matches!(f(&[y]), Err(VmapError::NoBatchedInput)));
+        Ok(())
+    }
+
+    #[test]
+    fn vmap_per_example_grads() -> Result<(), VmapError> {
+        // Two examples of size 4, stored as the columns of a 4x2 array. Building a computation
+        // clears its builder, so each case gets its own.
+        let inputs = || {
+            let builder = XlaBuilder::new("test");
+            let xs = builder
+                .constant_vector(&[1f64, 2., 3., 4., 5., 6., 7., 8.])
+                .reshape(&[4, 2]);
+            [xs, builder.constant(3f64)]
+        };
+        let loss = |p: &[Expr]| Expr::mul(Expr::mul(p[0].clone(), p[0].clone()), p[1].clone());
+        let f = vmap(|p| loss(p).grad(0), &[Some(1), None], 0);
+        let grads = f(&inputs())?;
+        assert_eq!(grads.array_shape()?.dims(), [2, 4]);
+        assert_eq!(run(&grads)?, [6., 18., 30., 42., 12., 24., 36., 48.]);
+        // The gradient with respect to `w` only depends on the batched input through x * x.
+        let f = vmap(|p| loss(p).grad(1), &[Some(-1), None], -1);
+        assert_eq!(run(&f(&inputs())?)?, [1., 4., 9., 16., 25., 36., 49., 64.]);
+        Ok(())
+    }
+}
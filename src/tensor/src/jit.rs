@@ -0,0 +1,227 @@
+//! Tracing of Rust closures into compiled XLA executables.
+use crate::{Tensor, TreeDef, TreeFlatten, tree_flatten, tree_unflatten};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use xla::{
+    BufferArgsRef, DeviceArray, PjRtBuffer, PjRtClient, PjRtLoadedExecutable, Result, Shape,
+    XlaBuilder,
+};
+
+struct Compiled {
+    exec: PjRtLoadedExecutable,
+    out_def: TreeDef,
+}
+
+/// The signature executables are cached by: the structure of the traced arguments, the shapes
+/// of their leaves and the static arguments.
+type CacheKey<S> = (TreeDef, Vec<Shape>, S);
+
+/// The result of a function given to [`jit`]: a tree of tensors, or a [`Result`] holding one
+/// so that errors raised while tracing, such as shape mismatches, are returned by the call.
+pub trait TraceOutput {
+    type Tree: TreeFlatten<Leaf = Tensor>;
+
+    fn into_result(self) -> Result<Self::Tree>;
+}
+
+impl<O: TreeFlatten<Leaf = Tensor>> TraceOutput for O {
+    type Tree = O;
+
+    fn into_result(self) -> Result<O> {
+        Ok(self)
+    }
+}
+
+impl<O: TreeFlatten<Leaf = Tensor>> TraceOutput for Result<O> {
+    type Tree = O;
+
+    fn into_result(self) -> Result<O> {
+        self
+    }
+}
+
+/// A function compiled on demand by [`jit`].
+pub struct Jit<F, S = ()> {
+    f: F,
+    client: PjRtClient,
+    cache: Mutex<HashMap<CacheKey<S>, Arc<Compiled>>>,
+}
+
+/// Wraps `f` so that it is traced and compiled for `client` on its first call with a given
+/// signature, and the compiled executable is run on later calls with the same signature.
+///
+/// The traced arguments are a tree of [`DeviceArray`] or [`PjRtBuffer`] leaves, see
+/// [`TreeFlatten`], and `f` receives the same tree with a [`Tensor`] in place of each leaf. The
+/// signature of a call is made of the structure of this tree, the shapes and element types of
+/// its leaves, and the static arguments given to [`Jit::call_static`]. Static arguments are
+/// passed to `f` as is, so they can drive its control flow, and any change in their value
+/// triggers a new trace.
+///
+/// The result of `f` is a tree of tensors, possibly wrapped in a [`Result`], and calls return
+/// the matching tree of device arrays, which stay on the device until explicitly copied back
+/// and can be passed to later calls.
+pub fn jit<F, S>(client: &PjRtClient, f: F) -> Jit<F, S> {
+    Jit {
+        f,
+        client: client.clone(),
+        cache: Mutex::new(HashMap::new()),
+    }
+}
+
+impl<F, S: Hash + Eq + Clone> Jit<F, S> {
+    /// The number of signatures `f` has been compiled for.
+    pub fn cache_size(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    fn trace<I, O>(
+        &self,
+        def: &TreeDef,
+        shapes: &[Shape],
+        f: impl FnOnce(I::Map<Tensor>) -> O,
+    ) -> Result<Compiled>
+    where
+        I: TreeFlatten<Leaf: AsRef<PjRtBuffer>>,
+        O: TraceOutput,
+    {
+        let builder = XlaBuilder::new("jit");
+        let params = shapes
+            .iter()
+            .enumerate()
+            .map(|(i, shape)| builder.parameter(i as i64, shape.clone(), &format!("arg{i}")))
+            .collect::<Result<Vec<_>>>()?;
+        let args = tree_unflatten::<I, _>(def, params).expect("tree structure of the arguments");
+        let out = f(args).into_result()?;
+        let (leaves, out_def) = tree_flatten(&out);
+        let leaves = leaves.iter().map(|op| op.as_ref()).collect::<Vec<_>>();
+        let comp = builder.tuple(&leaves).build()?;
+        let exec = self.client.compile_with_default_options(&comp)?;
+        Ok(Compiled { exec, out_def })
+    }
+
+    fn run<I, O>(
+        &self,
+        args: &I,
+        static_args: &S,
+        f: impl FnOnce(I::Map<Tensor>) -> O,
+    ) -> Result<<O::Tree as TreeFlatten>::Map<DeviceArray>>
+    where
+        I: TreeFlatten<Leaf: AsRef<PjRtBuffer>>,
+        O: TraceOutput,
+    {
+        let (leaves, def) = tree_flatten(args);
+        let shapes = leaves
+            .iter()
+            .map(|leaf| leaf.as_ref().shape().shape())
+            .collect::<Result<Vec<_>>>()?;
+        let key = (def, shapes, static_args.clone());
+        // The lock is not held while tracing so that `f` may itself call jitted functions.
+        let compiled = self.cache.lock().unwrap().get(&key).cloned();
+        let compiled = match compiled {
+            Some(compiled) => compiled,
+            None => {
+                let compiled = Arc::new(self.trace::<I, O>(&key.0, &key.1, f)?);
+                self.cache.lock().unwrap().insert(key, compiled.clone());
+                compiled
+            }
+        };
+        let buffers = leaves
+            .into_iter()
+            .map(|leaf| leaf.as_ref())
+            .collect::<BufferArgsRef>();
+        let out = compiled
+            .exec
+            .execute_buffers(buffers.untuple_result(true))?
+            .into_iter()
+            .map(|buf| DeviceArray::new(&self.client, buf))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(tree_unflatten::<O::Tree, _>(&compiled.out_def, out)
+            .expect("tree structure of the outputs"))
+    }
+
+    /// Runs the function on `args` and `static_args`, tracing and compiling it first if it has
+    /// not been called with this signature before.
+    pub fn call_static<I, O>(
+        &self,
+        args: &I,
+        static_args: S,
+    ) -> Result<<O::Tree as TreeFlatten>::Map<DeviceArray>>
+    where
+        F: Fn(I::Map<Tensor>, &S) -> O,
+        I: TreeFlatten<Leaf: AsRef<PjRtBuffer>>,
+        O: TraceOutput,
+    {
+        self.run(args, &static_args, |args| (self.f)(args, &static_args))
+    }
+}
+
+impl<F> Jit<F, ()> {
+    /// Runs the function on `args`, tracing and compiling it first if it has not been called
+    /// with this signature before.
+    pub fn call<I, O>(&self, args: &I) -> Result<<O::Tree as TreeFlatten>::Map<DeviceArray>>
+    where
+        F: Fn(I::Map<Tensor>) -> O,
+        I: TreeFlatten<Leaf: AsRef<PjRtBuffer>>,
+        O: TraceOutput,
+    {
+        self.run(args, &(), |args| (self.f)(args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn jit_traces_once_per_signature() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let traces = Cell::new(0);
+        let f = jit(&client, |(x, y): (Tensor, Tensor)| {
+            traces.set(traces.get() + 1);
+            (x.clone() * y.clone() + x, y)
+        });
+        let x = DeviceArray::from_host(&client, &[1f32, 2., 3.], &[3])?;
+        let y = DeviceArray::from_host(&client, &[4f32, 5., 6.], &[3])?;
+        let (z, y2) = f.call(&(x, y))?;
+        assert_eq!(z.to_vec::<f32>()?, [5., 12., 21.]);
+        assert_eq!(y2.to_vec::<f32>()?, [4., 5., 6.]);
+
+        let x = DeviceArray::from_host(&client, &[2f32, 2., 2.], &[3])?;
+        let (z, _) = f.call(&(x, y2))?;
+        assert_eq!(z.to_vec::<f32>()?, [10., 12., 14.]);
+        assert_eq!(traces.get(), 1);
+
+        let x = DeviceArray::from_host(&client, &[1f32, 2.], &[2])?;
+        let y = DeviceArray::from_host(&client, &[3f32], &[])?;
+        let (z, _) = f.call(&(x, y))?;
+        assert_eq!(z.to_vec::<f32>()?, [4., 8.]);
+        assert_eq!(traces.get(), 2);
+        assert_eq!(f.cache_size(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn jit_static_args_and_trees() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        // Sums the first `n` elements of a list of tensors.
+        let f = jit(&client, |xs: Vec<Tensor>, n: &usize| {
+            let mut sum = xs[0].clone();
+            for x in xs[1..*n].iter() {
+                sum = sum + x.clone();
+            }
+            sum
+        });
+        let xs = (1..4)
+            .map(|i| client.copy_host_buffer(&[i as f32; 2], &[2]))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(f.call_static(&xs, 2)?.to_vec::<f32>()?, [3., 3.]);
+        assert_eq!(f.call_static(&xs, 3)?.to_vec::<f32>()?, [6., 6.]);
+        let mut xs = xs;
+        xs.pop();
+        assert_eq!(f.call_static(&xs, 2)?.to_vec::<f32>()?, [3., 3.]);
+        assert_eq!(f.cache_size(), 3);
+        Ok(())
+    }
+}
@@ -1,3 +1,13 @@
+mod jit;
+pub mod nn;
+mod tree;
+
+pub use jit::*;
+pub use tree::*;
+
+/// The traced value of an array, as seen by the functions given to [`jit`].
+pub type Tensor = xla::XlaOp;
+
 pub fn add(left: u64, right: u64) -> u64 {
     left + right
 }
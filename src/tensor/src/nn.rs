@@ -0,0 +1,188 @@
+//! Neural network layers, activations and losses operating on traced [`Tensor`]s.
+//!
+//! Each layer is split between a configuration holding its hyperparameters, such as
+//! [`Linear`], and a struct of parameters defined with [`tree_struct!`](crate::tree_struct),
+//! such as [`LinearParams`]. Both `init` and `forward` are meant to be called from functions
+//! given to [`jit`](crate::jit): `init` samples the parameters from a [`PrngKey`](xla::PrngKey)
+//! so that the jitted function returns them as device arrays, and later calls take these
+//! arrays back as arguments which `forward` applies to its input.
+//!
+//! Inputs follow the usual layouts: features are the last dimension for dense layers and
+//! `[batch, channels, spatial...]` is used for convolutions and batch normalisation.
+mod activation;
+mod init;
+mod layers;
+mod loss;
+mod rnn;
+
+pub use activation::*;
+pub use init::*;
+pub use layers::*;
+pub use loss::*;
+pub use rnn::*;
+
+use crate::Tensor;
+use xla::{
+    DotDimensionNumbers, ElementType, Error, Result, Shape, XlaBuilder, XlaComputation, XlaOp,
+};
+
+fn rank(x: &Tensor) -> Result<usize> {
+    Ok(x.array_shape()?.dims().len())
+}
+
+/// Resolves `axis`, negative values counting from the last dimension.
+fn normalize_axis(axis: i64, rank: usize) -> Result<i64> {
+    let normalized = if axis < 0 { axis + rank as i64 } else { axis };
+    if normalized < 0 || normalized >= rank as i64 {
+        Err(Error::IndexOutOfBounds { index: axis, rank })?
+    }
+    Ok(normalized)
+}
+
+/// An array of the given type and dimensions filled with `value`.
+fn full(builder: &XlaBuilder, ty: ElementType, dims: &[i64], value: f64) -> Tensor {
+    builder
+        .constant(value)
+        .convert_element_type(ty.primitive_type())
+        .broadcast(dims)
+}
+
+/// The scalar computation combining two elements of `x` with `f`, used by reductions.
+fn reducer(x: &Tensor, name: &str, f: impl Fn(&XlaOp, &XlaOp) -> XlaOp) -> Result<XlaComputation> {
+    let ty = x.array_shape()?.ty();
+    let builder = XlaBuilder::new(name);
+    let lhs = builder.parameter(0, Shape::array_with_type(ty, vec![]), "lhs")?;
+    let rhs = builder.parameter(1, Shape::array_with_type(ty, vec![]), "rhs")?;
+    f(&lhs, &rhs).build()
+}
+
+fn reduce_sum(x: &Tensor, dims: &[i64]) -> Result<Tensor> {
+    let sum = reducer(x, "nn_sum", |lhs, rhs| lhs.add(rhs))?;
+    Ok(x.reduce(&x.zero_like(), &sum, dims))
+}
+
+fn reduce_max(x: &Tensor, dims: &[i64]) -> Result<Tensor> {
+    let max = reducer(x, "nn_max", |lhs, rhs| lhs.max(rhs))?;
+    Ok(x.reduce(&x.scalar_like(f64::NEG_INFINITY), &max, dims))
+}
+
+fn reduce_mean(x: &Tensor, dims: &[i64]) -> Result<Tensor> {
+    let shape = x.array_shape()?;
+    let count = dims
+        .iter()
+        .map(|d| shape.dims()[*d as usize])
+        .product::<i64>();
+    Ok(reduce_sum(x, dims)?.div(&x.scalar_like(count as f64)))
+}
+
+/// The mean of all the elements of `x`.
+fn mean_all(x: &Tensor) -> Result<Tensor> {
+    let dims = (0..rank(x)? as i64).collect::<Vec<_>>();
+    reduce_mean(x, &dims)
+}
+
+/// Broadcasts `reduced`, the result of reducing an array with dimensions `dims` over
+/// `reduced_dims`, back to `dims`.
+fn keep_dims(reduced: &Tensor, dims: &[i64], reduced_dims: &[i64]) -> Tensor {
+    let kept = (0..dims.len() as i64)
+        .filter(|d| !reduced_dims.contains(d))
+        .collect::<Vec<_>>();
+    reduced.broadcast_in_dim(dims, &kept)
+}
+
+/// The mean and biased variance of `x` over `dims`.
+fn moments(x: &Tensor, dims: &[i64]) -> Result<(Tensor, Tensor)> {
+    let shape = x.array_shape()?;
+    let mean = reduce_mean(x, dims)?;
+    let centered = x.sub(&keep_dims(&mean, shape.dims(), dims));
+    let var = reduce_mean(&centered.mul(&centered), dims)?;
+    Ok((mean, var))
+}
+
+/// Multiplies the last dimension of `x` with `weight`, of shape `[in, out]`, then adds `bias`
+/// of shape `[out]` if given.
+fn dense(x: &Tensor, weight: &Tensor, bias: Option<&Tensor>) -> Result<Tensor> {
+    let last = normalize_axis(-1, rank(x)?)?;
+    let mut dims = DotDimensionNumbers::new();
+    dims.add_lhs_contracting_dimensions(last);
+    dims.add_rhs_contracting_dimensions(0);
+    let y = x.dot_general(weight, dims);
+    let Some(bias) = bias else {
+        return Ok(y);
+    };
+    let dims = y.array_shape()?.dims().to_vec();
+    Ok(y.add(&bias.broadcast_in_dim(&dims, &[last])))
+}
+
+#[cfg(test)]
+fn assert_close(got: &[f32], expected: &[f32]) {
+    assert_eq!(got.len(), expected.len());
+    for (g, e) in got.iter().zip(expected) {
+        assert!((g - e).abs() < 1e-4, "got {got:?}, expected {expected:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jit, tree_struct};
+    use xla::{DeviceArray, PjRtClient, PrngKey, RngAlgorithm};
+
+    tree_struct! {
+        struct Mlp<T> {
+            layers: Vec<LinearParams<T>>,
+        }
+    }
+
+    #[test]
+    fn mlp_init_and_forward() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let layers = [Linear::new(4, 8), Linear::new(8, 3)];
+        let init = jit(&client, |key: Tensor| {
+            let key = PrngKey::from_state(key, RngAlgorithm::ThreeFry);
+            let keys = key.split(layers.len());
+            let layers = layers
+                .iter()
+                .zip(keys.iter())
+                .map(|(layer, key)| layer.init(key, ElementType::F32))
+                .collect();
+            Mlp { layers }
+        });
+        let key = DeviceArray::from_host(&client, &[0u64, 42], &[2])?;
+        let params = init.call(&key)?;
+        assert_eq!(params.layers[0].weight.dims(), [4, 8]);
+        assert_eq!(params.layers[1].bias.to_vec::<f32>()?, [0.; 3]);
+        // The same key gives the same parameters.
+        let again = init.call(&key)?;
+        assert_eq!(
+            params.layers[1].weight.to_vec::<f32>()?,
+            again.layers[1].weight.to_vec::<f32>()?
+        );
+
+        let forward = jit(&client, |(params, x): (Mlp<Tensor>, Tensor)| {
+            let mut x = x;
+            for (i, (layer, params)) in layers.iter().zip(params.layers.iter()).enumerate() {
+                x = layer.forward(params, &x)?;
+                if i + 1 < layers.len() {
+                    x = relu(&x);
+                }
+            }
+            log_softmax(&x, -1)
+        });
+        let x = DeviceArray::from_host(&client, &[0.5f32; 8], &[2, 4])?;
+        let logp = forward.call(&(params, x))?;
+        assert_eq!(logp.dims(), [2, 3]);
+        let probs = logp.to_vec::<f32>()?;
+        let total = probs[..3].iter().map(|p| p.exp()).sum::<f32>();
+        assert!((total - 1.).abs() < 1e-5);
+
+        // Shape errors raised while tracing are returned by the call.
+        let x = DeviceArray::from_host(&client, &[0.5f32; 2], &[2])?;
+        let f = jit(&client, |x: Tensor| log_softmax(&x, 1));
+        assert!(matches!(
+            f.call(&x),
+            Err(Error::IndexOutOfBounds { index: 1, rank: 1 })
+        ));
+        Ok(())
+    }
+}
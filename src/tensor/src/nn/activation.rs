@@ -0,0 +1,85 @@
+//! Elementwise activations and normalised exponentials.
+use super::{keep_dims, normalize_axis, reduce_max, reduce_sum};
+use crate::Tensor;
+use xla::Result;
+
+pub fn relu(x: &Tensor) -> Tensor {
+    x.max(&x.zero_like())
+}
+
+/// The Gaussian error linear unit, using the tanh approximation
+/// `x / 2 * (1 + tanh(sqrt(2 / pi) * (x + 0.044715 * x^3)))`.
+pub fn gelu(x: &Tensor) -> Tensor {
+    let cube = x.mul(x).mul(x);
+    let inner = x
+        .add(&cube.mul(&x.scalar_like(0.044715)))
+        .mul(&x.scalar_like((2. / std::f64::consts::PI).sqrt()));
+    let half = x.mul(&x.scalar_like(0.5));
+    half.mul(&inner.tanh().add(&x.scalar_like(1.)))
+}
+
+/// The sigmoid linear unit `x * sigmoid(x)`, also known as swish.
+pub fn silu(x: &Tensor) -> Tensor {
+    x.mul(&x.logistic())
+}
+
+/// Normalises the exponentials of `x` along `axis` so that they sum to one, negative axes
+/// counting from the last dimension.
+pub fn softmax(x: &Tensor, axis: i64) -> Result<Tensor> {
+    let shape = x.array_shape()?;
+    let axis = normalize_axis(axis, shape.dims().len())?;
+    let dims = shape.dims();
+    let max = keep_dims(&reduce_max(x, &[axis])?, dims, &[axis]);
+    let exp = x.sub(&max).exp();
+    let sum = keep_dims(&reduce_sum(&exp, &[axis])?, dims, &[axis]);
+    Ok(exp.div(&sum))
+}
+
+/// The logarithm of [`softmax`], computed without overflowing for large inputs.
+pub fn log_softmax(x: &Tensor, axis: i64) -> Result<Tensor> {
+    let shape = x.array_shape()?;
+    let axis = normalize_axis(axis, shape.dims().len())?;
+    let dims = shape.dims();
+    let shifted = x.sub(&keep_dims(&reduce_max(x, &[axis])?, dims, &[axis]));
+    let log_sum = reduce_sum(&shifted.exp(), &[axis])?.log();
+    Ok(shifted.sub(&keep_dims(&log_sum, dims, &[axis])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jit;
+    use crate::nn::assert_close;
+    use xla::{DeviceArray, PjRtClient};
+
+    #[test]
+    fn activations() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let x = DeviceArray::from_host(&client, &[-2f32, 0., 1., 1000., 1000., 1001.], &[2, 3])?;
+        let f = jit(&client, |x: Tensor| -> Result<_> {
+            Ok((
+                relu(&x),
+                gelu(&x),
+                silu(&x),
+                softmax(&x, -1)?,
+                log_softmax(&x, 0)?,
+            ))
+        });
+        let (relu, gelu, silu, softmax, log_softmax) = f.call(&x)?;
+        assert_close(&relu.to_vec()?, &[0., 0., 1., 1000., 1000., 1001.]);
+        assert_close(&gelu.to_vec::<f32>()?[..3], &[-0.0454023, 0., 0.841192]);
+        assert_close(&silu.to_vec::<f32>()?[..3], &[-0.23840584, 0., 0.7310586]);
+        // Large inputs do not overflow.
+        assert_close(
+            &softmax.to_vec()?,
+            &[
+                0.0351190, 0.2594965, 0.7053845, 0.2119416, 0.2119416, 0.5761169,
+            ],
+        );
+        assert_close(
+            &log_softmax.to_vec::<f32>()?[..3],
+            &[-1002., -1000., -1000.],
+        );
+        Ok(())
+    }
+}
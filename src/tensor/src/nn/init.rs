@@ -0,0 +1,99 @@
+//! Initialisation schemes for the weights of layers.
+use super::full;
+use crate::Tensor;
+use xla::{ArrayShape, PrngKey};
+
+/// The way the weights of a layer are sampled, given the number of inputs and outputs each
+/// weight contributes to, its fan in and fan out.
+///
+/// The normal variants of the Glorot and He schemes sample from a normal distribution
+/// truncated to two standard deviations, rescaled to have the variance of the scheme, like
+/// `jax.nn.initializers`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Init {
+    Zeros,
+    Ones,
+    /// Normal with mean zero and the given standard deviation.
+    Normal {
+        stddev: f64,
+    },
+    /// Uniform in `[-limit, limit)` with `limit = sqrt(6 / (fan_in + fan_out))`, also known as
+    /// Xavier initialisation.
+    GlorotUniform,
+    /// Truncated normal with variance `2 / (fan_in + fan_out)`.
+    GlorotNormal,
+    /// Uniform in `[-limit, limit)` with `limit = sqrt(6 / fan_in)`, also known as Kaiming
+    /// initialisation.
+    HeUniform,
+    /// Truncated normal with variance `2 / fan_in`.
+    HeNormal,
+}
+
+/// The standard deviation of a standard normal distribution truncated to `[-2, 2]`.
+const TRUNCATED_NORMAL_STDDEV: f64 = 0.879_625_661_034_239_8;
+
+impl Init {
+    /// Samples an array with the given shape.
+    pub fn sample(&self, key: &PrngKey, shape: &ArrayShape, fan_in: i64, fan_out: i64) -> Tensor {
+        let variance_scaling = |variance: f64, uniform: bool| {
+            if uniform {
+                let limit = (3. * variance).sqrt();
+                key.uniform(shape, -limit, limit)
+            } else {
+                let z = key.truncated_normal(shape, -2., 2.);
+                z.mul(&z.scalar_like(variance.sqrt() / TRUNCATED_NORMAL_STDDEV))
+            }
+        };
+        let builder = key.state().builder();
+        match *self {
+            Self::Zeros => full(builder, shape.ty(), shape.dims(), 0.),
+            Self::Ones => full(builder, shape.ty(), shape.dims(), 1.),
+            Self::Normal { stddev } => {
+                let z = key.normal(shape);
+                z.mul(&z.scalar_like(stddev))
+            }
+            Self::GlorotUniform => variance_scaling(2. / (fan_in + fan_out) as f64, true),
+            Self::GlorotNormal => variance_scaling(2. / (fan_in + fan_out) as f64, false),
+            Self::HeUniform => variance_scaling(2. / fan_in as f64, true),
+            Self::HeNormal => variance_scaling(2. / fan_in as f64, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jit;
+    use xla::{DeviceArray, ElementType, PjRtClient, Result, RngAlgorithm};
+
+    #[test]
+    fn init_schemes() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let shape = ArrayShape::new_with_type(ElementType::F32, vec![200, 100]);
+        let f = jit(&client, |key: Tensor| {
+            let key = PrngKey::from_state(key, RngAlgorithm::ThreeFry);
+            let sample = |init: Init| init.sample(&key, &shape, 200, 100);
+            (
+                sample(Init::Ones),
+                sample(Init::GlorotUniform),
+                sample(Init::HeNormal),
+            )
+        });
+        let key = DeviceArray::from_host(&client, &[0u64, 0], &[2])?;
+        let (ones, glorot, he) = f.call(&key)?;
+        assert!(ones.to_vec::<f32>()?.iter().all(|w| *w == 1.));
+
+        let limit = (6f32 / 300.).sqrt();
+        let glorot = glorot.to_vec::<f32>()?;
+        assert!(glorot.iter().all(|w| w.abs() <= limit));
+        let mean = glorot.iter().sum::<f32>() / glorot.len() as f32;
+        assert!(mean.abs() < 0.01);
+
+        let he = he.to_vec::<f32>()?;
+        let variance = he.iter().map(|w| w * w).sum::<f32>() / he.len() as f32;
+        assert!((variance - 0.01).abs() < 1e-3, "variance {variance}");
+        let bound = 2. * 0.01f32.sqrt() / TRUNCATED_NORMAL_STDDEV as f32;
+        assert!(he.iter().all(|w| w.abs() <= bound * 1.0001));
+        Ok(())
+    }
+}
@@ -0,0 +1,659 @@
+//! Feed-forward layers.
+use super::{Init, dense, full, keep_dims, moments, normalize_axis, rank, softmax};
+use crate::{Tensor, tree_struct};
+use xla::{ArrayShape, DotDimensionNumbers, ElementType, Error, PrngKey, Result, XlaBuilder};
+
+tree_struct! {
+    /// The parameters of a [`Linear`] layer.
+    #[derive(Debug, Clone)]
+    pub struct LinearParams<T> {
+        /// `[in_features, out_features]`
+        pub weight: T,
+        /// `[out_features]`
+        pub bias: T,
+    }
+}
+
+/// A fully connected layer `x W + b`, applied to the last dimension of its input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Linear {
+    pub in_features: i64,
+    pub out_features: i64,
+    /// The initialisation of the weight, the bias starting at zero.
+    pub init: Init,
+}
+
+impl Linear {
+    /// A layer with Glorot uniform initialisation.
+    pub fn new(in_features: i64, out_features: i64) -> Self {
+        Self {
+            in_features,
+            out_features,
+            init: Init::GlorotUniform,
+        }
+    }
+
+    pub fn init(&self, key: &PrngKey, ty: ElementType) -> LinearParams<Tensor> {
+        let (fan_in, fan_out) = (self.in_features, self.out_features);
+        let shape = |dims| ArrayShape::new_with_type(ty, dims);
+        LinearParams {
+            weight: self
+                .init
+                .sample(key, &shape(vec![fan_in, fan_out]), fan_in, fan_out),
+            bias: full(key.state().builder(), ty, &[fan_out], 0.),
+        }
+    }
+
+    pub fn forward(&self, params: &LinearParams<Tensor>, x: &Tensor) -> Result<Tensor> {
+        dense(x, &params.weight, Some(&params.bias))
+    }
+}
+
+/// The padding of the spatial dimensions of a [`Conv`] input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Padding {
+    /// No padding, only the windows that fit in the input are kept.
+    Valid,
+    /// Pads with zeros so that the output has `ceil(size / stride)` elements along each spatial
+    /// dimension, the odd element of padding going after the input.
+    Same,
+    /// The `(low, high)` padding of each spatial dimension.
+    Explicit(Vec<(i64, i64)>),
+}
+
+tree_struct! {
+    /// The parameters of a [`Conv`] layer.
+    #[derive(Debug, Clone)]
+    pub struct ConvParams<T> {
+        /// `[out_channels, in_channels / groups, kernel_size...]`
+        pub kernel: T,
+        /// `[out_channels]`
+        pub bias: T,
+    }
+}
+
+/// A convolution of `[batch, in_channels, spatial...]` inputs into `[batch, out_channels,
+/// spatial...]` outputs, with one spatial dimension per entry of `kernel_size`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conv {
+    pub in_channels: i64,
+    pub out_channels: i64,
+    pub kernel_size: Vec<i64>,
+    pub stride: Vec<i64>,
+    pub padding: Padding,
+    /// The number of groups channels are split into, each group of outputs only depending on
+    /// the matching group of inputs. Both channel counts must be multiples of it.
+    pub groups: i64,
+    /// The initialisation of the kernel, the bias starting at zero.
+    pub init: Init,
+}
+
+impl Conv {
+    /// A convolution with unit strides, no padding and He normal initialisation.
+    pub fn new(in_channels: i64, out_channels: i64, kernel_size: &[i64]) -> Self {
+        Self {
+            in_channels,
+            out_channels,
+            kernel_size: kernel_size.to_vec(),
+            stride: vec![1; kernel_size.len()],
+            padding: Padding::Valid,
+            groups: 1,
+            init: Init::HeNormal,
+        }
+    }
+
+    /// A convolution over `[batch, channels, length]` inputs.
+    pub fn conv1d(in_channels: i64, out_channels: i64, kernel_size: i64) -> Self {
+        Self::new(in_channels, out_channels, &[kernel_size])
+    }
+
+    /// A convolution over `[batch, channels, height, width]` inputs.
+    pub fn conv2d(in_channels: i64, out_channels: i64, kernel_size: [i64; 2]) -> Self {
+        Self::new(in_channels, out_channels, &kernel_size)
+    }
+
+    pub fn init(&self, key: &PrngKey, ty: ElementType) -> ConvParams<Tensor> {
+        let receptive_field = self.kernel_size.iter().product::<i64>();
+        let in_channels = self.in_channels / self.groups;
+        let mut dims = vec![self.out_channels, in_channels];
+        dims.extend_from_slice(&self.kernel_size);
+        let shape = ArrayShape::new_with_type(ty, dims);
+        let fan_in = in_channels * receptive_field;
+        let fan_out = self.out_channels * receptive_field;
+        ConvParams {
+            kernel: self.init.sample(key, &shape, fan_in, fan_out),
+            bias: full(key.state().builder(), ty, &[self.out_channels], 0.),
+        }
+    }
+
+    fn padding(&self, dims: &[i64]) -> Vec<(i64, i64)> {
+        match &self.padding {
+            Padding::Valid => vec![(0, 0); self.kernel_size.len()],
+            Padding::Same => (0..self.kernel_size.len())
+                .map(|i| {
+                    let (size, stride) = (dims[i + 2], self.stride[i]);
+                    let out = (size + stride - 1) / stride;
+                    let total = ((out - 1) * stride + self.kernel_size[i] - size).max(0);
+                    (total / 2, total - total / 2)
+                })
+                .collect(),
+            Padding::Explicit(padding) => padding.clone(),
+        }
+    }
+
+    pub fn forward(&self, params: &ConvParams<Tensor>, x: &Tensor) -> Result<Tensor> {
+        let dims = x.array_shape()?.dims().to_vec();
+        if dims.len() != self.kernel_size.len() + 2 {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: self.kernel_size.len() + 2,
+                got: dims.len(),
+                dims: dims.clone(),
+            })?
+        }
+        let y = x.conv_with_general_padding(
+            &params.kernel,
+            &self.stride,
+            &self.padding(&dims),
+            self.groups,
+            1,
+        );
+        let out_dims = y.array_shape()?.dims().to_vec();
+        Ok(y.add(&params.bias.broadcast_in_dim(&out_dims, &[1])))
+    }
+}
+
+tree_struct! {
+    /// The parameters of a [`LayerNorm`] or [`BatchNorm`] layer.
+    #[derive(Debug, Clone)]
+    pub struct NormParams<T> {
+        /// `[features]`
+        pub scale: T,
+        /// `[features]`
+        pub bias: T,
+    }
+}
+
+fn norm_params(builder: &XlaBuilder, ty: ElementType, features: i64) -> NormParams<Tensor> {
+    NormParams {
+        scale: full(builder, ty, &[features], 1.),
+        bias: full(builder, ty, &[features], 0.),
+    }
+}
+
+/// Normalises `x` with the given mean and variance, then scales and shifts the features along
+/// dimension `axis`.
+fn normalize(
+    x: &Tensor,
+    mean: &Tensor,
+    var: &Tensor,
+    eps: f64,
+    params: &NormParams<Tensor>,
+    axis: i64,
+) -> Result<Tensor> {
+    let dims = x.array_shape()?.dims().to_vec();
+    let inv_std = var.add(&var.scalar_like(eps)).rsqrt();
+    let scale = params.scale.broadcast_in_dim(&dims, &[axis]);
+    let bias = params.bias.broadcast_in_dim(&dims, &[axis]);
+    Ok(x.sub(mean).mul(&inv_std).mul(&scale).add(&bias))
+}
+
+/// Normalises the last dimension of its input to zero mean and unit variance, then applies a
+/// learned scale and bias per feature.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerNorm {
+    pub features: i64,
+    /// Added to the variance for numerical stability.
+    pub eps: f64,
+}
+
+impl LayerNorm {
+    pub fn new(features: i64) -> Self {
+        Self {
+            features,
+            eps: 1e-5,
+        }
+    }
+
+    /// Parameters with unit scale and zero bias.
+    pub fn init(&self, builder: &XlaBuilder, ty: ElementType) -> NormParams<Tensor> {
+        norm_params(builder, ty, self.features)
+    }
+
+    pub fn forward(&self, params: &NormParams<Tensor>, x: &Tensor) -> Result<Tensor> {
+        let dims = x.array_shape()?.dims().to_vec();
+        let last = normalize_axis(-1, dims.len())?;
+        let (mean, var) = moments(x, &[last])?;
+        let mean = keep_dims(&mean, &dims, &[last]);
+        let var = keep_dims(&var, &dims, &[last]);
+        normalize(x, &mean, &var, self.eps, params, last)
+    }
+}
+
+tree_struct! {
+    /// The running statistics of a [`BatchNorm`] layer, used in place of the statistics of the
+    /// batch at inference.
+    #[derive(Debug, Clone)]
+    pub struct BatchNormStats<T> {
+        /// `[features]`
+        pub mean: T,
+        /// `[features]`
+        pub var: T,
+    }
+}
+
+/// Normalises each feature of `[batch, features, spatial...]` inputs over the batch and
+/// spatial dimensions, then applies a learned scale and bias per feature.
+///
+/// In training the statistics of the batch are used and the running statistics are updated
+/// with them, otherwise the running statistics are used as is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchNorm {
+    pub features: i64,
+    /// The weight of the previous running statistics in their update.
+    pub momentum: f64,
+    /// Added to the variance for numerical stability.
+    pub eps: f64,
+}
+
+impl BatchNorm {
+    pub fn new(features: i64) -> Self {
+        Self {
+            features,
+            momentum: 0.9,
+            eps: 1e-5,
+        }
+    }
+
+    /// Parameters with unit scale and zero bias, and running statistics of zero mean and unit
+    /// variance.
+    pub fn init(
+        &self,
+        builder: &XlaBuilder,
+        ty: ElementType,
+    ) -> (NormParams<Tensor>, BatchNormStats<Tensor>) {
+        let stats = BatchNormStats {
+            mean: full(builder, ty, &[self.features], 0.),
+            var: full(builder, ty, &[self.features], 1.),
+        };
+        (norm_params(builder, ty, self.features), stats)
+    }
+
+    /// Returns the normalised input along with the updated running statistics.
+    pub fn forward(
+        &self,
+        params: &NormParams<Tensor>,
+        stats: &BatchNormStats<Tensor>,
+        x: &Tensor,
+        training: bool,
+    ) -> Result<(Tensor, BatchNormStats<Tensor>)> {
+        let dims = x.array_shape()?.dims().to_vec();
+        if dims.len() < 2 {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 2,
+                got: dims.len(),
+                dims: dims.clone(),
+            })?
+        }
+        let (mean, var, stats) = if training {
+            let reduced = (0..dims.len() as i64)
+                .filter(|d| *d != 1)
+                .collect::<Vec<_>>();
+            let (mean, var) = moments(x, &reduced)?;
+            let update = |running: &Tensor, batch: &Tensor| {
+                let momentum = running.scalar_like(self.momentum);
+                let rest = running.scalar_like(1. - self.momentum);
+                running.mul(&momentum).add(&batch.mul(&rest))
+            };
+            let stats = BatchNormStats {
+                mean: update(&stats.mean, &mean),
+                var: update(&stats.var, &var),
+            };
+            (mean, var, stats)
+        } else {
+            (stats.mean.clone(), stats.var.clone(), stats.clone())
+        };
+        let mean = mean.broadcast_in_dim(&dims, &[1]);
+        let var = var.broadcast_in_dim(&dims, &[1]);
+        Ok((normalize(x, &mean, &var, self.eps, params, 1)?, stats))
+    }
+}
+
+tree_struct! {
+    /// The parameters of an [`Embedding`] layer.
+    #[derive(Debug, Clone)]
+    pub struct EmbeddingParams<T> {
+        /// `[num_embeddings, features]`
+        pub table: T,
+    }
+}
+
+/// A lookup table mapping integer ids to learned vectors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Embedding {
+    pub num_embeddings: i64,
+    pub features: i64,
+    pub init: Init,
+}
+
+impl Embedding {
+    /// A table initialised from the standard normal distribution.
+    pub fn new(num_embeddings: i64, features: i64) -> Self {
+        Self {
+            num_embeddings,
+            features,
+            init: Init::Normal { stddev: 1. },
+        }
+    }
+
+    pub fn init(&self, key: &PrngKey, ty: ElementType) -> EmbeddingParams<Tensor> {
+        let shape = ArrayShape::new_with_type(ty, vec![self.num_embeddings, self.features]);
+        EmbeddingParams {
+            table: self
+                .init
+                .sample(key, &shape, self.num_embeddings, self.features),
+        }
+    }
+
+    /// Looks up integer `ids` of any shape, returning an array with an extra trailing dimension
+    /// of size `features`. Out of range ids are clamped to the bounds of the table.
+    pub fn forward(&self, params: &EmbeddingParams<Tensor>, ids: &Tensor) -> Result<Tensor> {
+        let rank = rank(ids)? as i64;
+        Ok(params
+            .table
+            .gather(ids, &[rank], &[0], &[0], &[1, self.features], rank))
+    }
+}
+
+/// Zeroes each element of its input with probability `rate` in training, scaling the others
+/// by `1 / (1 - rate)` so that the expected value is unchanged. The input is returned as is
+/// otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dropout {
+    pub rate: f64,
+}
+
+impl Dropout {
+    pub fn new(rate: f64) -> Self {
+        Self { rate }
+    }
+
+    /// Drops elements of `x` according to `key`, which should differ for every step of
+    /// training, e.g. by folding in the step with [`PrngKey::fold_in`].
+    pub fn forward(&self, key: &PrngKey, x: &Tensor, training: bool) -> Result<Tensor> {
+        if !training || self.rate == 0. {
+            return Ok(x.clone());
+        }
+        let keep = 1. - self.rate;
+        let mask = key.bernoulli(keep, &x.array_shape()?);
+        Ok(mask.select(&x.div(&x.scalar_like(keep)), &x.zeros_like()))
+    }
+}
+
+tree_struct! {
+    /// The parameters of a [`MultiHeadAttention`] layer.
+    #[derive(Debug, Clone)]
+    pub struct MultiHeadAttentionParams<T> {
+        pub query: LinearParams<T>,
+        pub key: LinearParams<T>,
+        pub value: LinearParams<T>,
+        pub output: LinearParams<T>,
+    }
+}
+
+/// Scaled dot-product attention over `num_heads` heads, each attending with its own slice of
+/// the projected features. Inputs have the layout `[batch, length, embed_dim]` and `embed_dim`
+/// must be a multiple of `num_heads`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiHeadAttention {
+    pub embed_dim: i64,
+    pub num_heads: i64,
+    /// The initialisation of the projection weights.
+    pub init: Init,
+}
+
+impl MultiHeadAttention {
+    pub fn new(embed_dim: i64, num_heads: i64) -> Self {
+        Self {
+            embed_dim,
+            num_heads,
+            init: Init::GlorotUniform,
+        }
+    }
+
+    fn projection(&self) -> Linear {
+        Linear {
+            init: self.init,
+            ..Linear::new(self.embed_dim, self.embed_dim)
+        }
+    }
+
+    pub fn init(&self, key: &PrngKey, ty: ElementType) -> MultiHeadAttentionParams<Tensor> {
+        let keys = key.split(4);
+        let projection = self.projection();
+        MultiHeadAttentionParams {
+            query: projection.init(&keys[0], ty),
+            key: projection.init(&keys[1], ty),
+            value: projection.init(&keys[2], ty),
+            output: projection.init(&keys[3], ty),
+        }
+    }
+
+    /// Projects `x` and splits the result into `[batch, num_heads, length, head_dim]`.
+    fn heads(&self, params: &LinearParams<Tensor>, x: &Tensor) -> Result<Tensor> {
+        let dims = x.array_shape()?.dims().to_vec();
+        if dims.len() != 3 {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 3,
+                got: dims.len(),
+                dims: dims.clone(),
+            })?
+        }
+        let head_dim = self.embed_dim / self.num_heads;
+        Ok(self
+            .projection()
+            .forward(params, x)?
+            .reshape(&[dims[0], dims[1], self.num_heads, head_dim])
+            .transpose(&[0, 2, 1, 3]))
+    }
+
+    /// Attends from each position of `query` to the positions of `key` and `value`, which
+    /// share their length. `mask` is an optional predicate of shape `[query_len, key_len]`,
+    /// such as a causal mask, that is false for the pairs of positions that cannot attend to
+    /// each other. Every query must be able to attend to at least one key.
+    pub fn forward(
+        &self,
+        params: &MultiHeadAttentionParams<Tensor>,
+        query: &Tensor,
+        key: &Tensor,
+        value: &Tensor,
+        mask: Option<&Tensor>,
+    ) -> Result<Tensor> {
+        let q = self.heads(&params.query, query)?;
+        let k = self.heads(&params.key, key)?;
+        let v = self.heads(&params.value, value)?;
+        let batch_dims = || {
+            let mut dims = DotDimensionNumbers::new();
+            dims.add_lhs_batch_dimensions(0);
+            dims.add_lhs_batch_dimensions(1);
+            dims.add_rhs_batch_dimensions(0);
+            dims.add_rhs_batch_dimensions(1);
+            dims
+        };
+        let mut dims = batch_dims();
+        dims.add_lhs_contracting_dimensions(3);
+        dims.add_rhs_contracting_dimensions(3);
+        let head_dim = self.embed_dim / self.num_heads;
+        let scores = q.dot_general(&k, dims);
+        let mut scores = scores.mul(&scores.scalar_like(1. / (head_dim as f64).sqrt()));
+        if let Some(mask) = mask {
+            let dims = scores.array_shape()?.dims().to_vec();
+            let masked = scores.scalar_like(f64::NEG_INFINITY).broadcast(&dims);
+            scores = mask
+                .broadcast_in_dim(&dims, &[2, 3])
+                .select(&scores, &masked);
+        }
+        let weights = softmax(&scores, -1)?;
+        let mut dims = batch_dims();
+        dims.add_lhs_contracting_dimensions(3);
+        dims.add_rhs_contracting_dimensions(2);
+        let out = weights.dot_general(&v, dims).transpose(&[0, 2, 1, 3]);
+        let out_dims = out.array_shape()?.dims().to_vec();
+        let out = out.reshape(&[out_dims[0], out_dims[1], self.embed_dim]);
+        self.projection().forward(&params.output, &out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jit;
+    use crate::nn::assert_close;
+    use xla::{DeviceArray, PjRtClient};
+
+    #[test]
+    fn linear_conv_embedding() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let array = |data: &[f32], dims: &[i64]| DeviceArray::from_host(&client, data, dims);
+        let linear = Linear::new(2, 3);
+        let params = LinearParams {
+            weight: array(&[1., 2., 3., 4., 5., 6.], &[2, 3])?,
+            bias: array(&[1., 0., -1.], &[3])?,
+        };
+        let f = jit(&client, |(params, x): (LinearParams<Tensor>, Tensor)| {
+            linear.forward(&params, &x)
+        });
+        let y = f.call(&(params, array(&[1., 1., 0., 1.], &[2, 2])?))?;
+        assert_eq!(y.dims(), [2, 3]);
+        assert_eq!(y.to_vec::<f32>()?, [6., 7., 8., 5., 5., 5.]);
+
+        let conv = Conv {
+            stride: vec![2],
+            padding: Padding::Same,
+            ..Conv::conv1d(1, 1, 3)
+        };
+        let params = ConvParams {
+            kernel: array(&[1.; 3], &[1, 1, 3])?,
+            bias: array(&[0.5], &[1])?,
+        };
+        let f = jit(&client, |(params, x): (ConvParams<Tensor>, Tensor)| {
+            conv.forward(&params, &x)
+        });
+        let x = array(&[0., 1., 2., 3., 4.], &[1, 1, 5])?;
+        assert_eq!(f.call(&(params, x))?.to_vec::<f32>()?, [1.5, 6.5, 7.5]);
+
+        // A grouped 2d convolution initialised in the same trace, keeping the spatial size.
+        let conv = Conv {
+            padding: Padding::Same,
+            groups: 2,
+            ..Conv::conv2d(2, 4, [3, 3])
+        };
+        let f = jit(&client, |x: Tensor| {
+            let params = conv.init(&PrngKey::new(x.builder(), 0), ElementType::F32);
+            conv.forward(&params, &x)
+        });
+        assert_eq!(
+            f.call(&array(&[1.; 50], &[1, 2, 5, 5])?)?.dims(),
+            [1, 4, 5, 5]
+        );
+        let x = array(&[1.; 10], &[2, 5])?;
+        assert!(matches!(
+            f.call(&x),
+            Err(Error::UnexpectedNumberOfDims {
+                expected: 4,
+                got: 2,
+                ..
+            })
+        ));
+
+        let embedding = Embedding::new(3, 2);
+        let table = array(&[0., 1., 10., 11., 20., 21.], &[3, 2])?;
+        let ids = DeviceArray::from_host(&client, &[2i32, 0, 1, 2], &[2, 2])?;
+        let f = jit(&client, |(table, ids): (Tensor, Tensor)| {
+            embedding.forward(&EmbeddingParams { table }, &ids)
+        });
+        let y = f.call(&(table, ids))?;
+        assert_eq!(y.dims(), [2, 2, 2]);
+        assert_eq!(y.to_vec::<f32>()?, [20., 21., 0., 1., 10., 11., 20., 21.]);
+        Ok(())
+    }
+
+    #[test]
+    fn normalization_and_dropout() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let layer_norm = LayerNorm::new(2);
+        let batch_norm = BatchNorm::new(1);
+        let f = jit(
+            &client,
+            |(x, y): (Tensor, Tensor), training: &bool| -> Result<_> {
+                let params = layer_norm.init(x.builder(), ElementType::F32);
+                let x = layer_norm.forward(&params, &x)?;
+                let (params, stats) = batch_norm.init(y.builder(), ElementType::F32);
+                let (y, stats) = batch_norm.forward(&params, &stats, &y, *training)?;
+                Ok((x, y, stats))
+            },
+        );
+        let x = DeviceArray::from_host(&client, &[1f32, 3., 2., 2.], &[2, 2])?;
+        let y = DeviceArray::from_host(&client, &[1f32, 2., 3., 4.], &[4, 1])?;
+        let inputs = (x, y);
+        let (x, y, stats) = f.call_static(&inputs, true)?;
+        assert_close(&x.to_vec()?, &[-1., 1., 0., 0.]);
+        let std = 1.25f32.sqrt();
+        assert_close(
+            &y.to_vec()?,
+            &[-1.5 / std, -0.5 / std, 0.5 / std, 1.5 / std],
+        );
+        assert_close(&stats.mean.to_vec()?, &[0.25]);
+        assert_close(&stats.var.to_vec()?, &[1.025]);
+        let (_, y, stats) = f.call_static(&inputs, false)?;
+        assert_close(&y.to_vec()?, &[1., 2., 3., 4.]);
+        assert_close(&stats.var.to_vec()?, &[1.]);
+
+        let dropout = Dropout::new(0.5);
+        let f = jit(&client, |x: Tensor, training: &bool| {
+            dropout.forward(&PrngKey::new(x.builder(), 0), &x, *training)
+        });
+        let x = DeviceArray::from_host(&client, &[1f32; 1000], &[10, 100])?;
+        let y = f.call_static(&x, true)?.to_vec::<f32>()?;
+        assert!(y.iter().all(|v| *v == 0. || *v == 2.));
+        let kept = y.iter().filter(|v| **v == 2.).count();
+        assert!((400..600).contains(&kept), "kept {kept}");
+        assert!(
+            f.call_static(&x, false)?
+                .to_vec::<f32>()?
+                .iter()
+                .all(|v| *v == 1.)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn multi_head_attention() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let attention = MultiHeadAttention::new(4, 2);
+        let f = jit(&client, |(q, kv): (Tensor, Tensor)| -> Result<_> {
+            let builder = q.builder();
+            let params = attention.init(&PrngKey::new(builder, 0), ElementType::F32);
+            let out = attention.forward(&params, &q, &kv, &kv, None)?;
+            // Each query only attends to the key at the same position, so the attention
+            // reduces to the projections of the values.
+            let diagonal = builder.iota(&[3, 3], ElementType::S32, 0).eq(&builder.iota(
+                &[3, 3],
+                ElementType::S32,
+                1,
+            ));
+            let masked = attention.forward(&params, &q, &kv, &kv, Some(&diagonal))?;
+            let projection = attention.projection();
+            let values = projection.forward(&params.value, &kv)?;
+            let expected = projection.forward(&params.output, &values)?;
+            Ok((out, masked, expected))
+        });
+        let q = (0..24).map(|i| i as f32 / 10.).collect::<Vec<_>>();
+        let kv = (0..24).map(|i| (i as f32).sin()).collect::<Vec<_>>();
+        let q = DeviceArray::from_host(&client, &q, &[2, 3, 4])?;
+        let kv = DeviceArray::from_host(&client, &kv, &[2, 3, 4])?;
+        let (out, masked, expected) = f.call(&(q, kv))?;
+        assert_eq!(out.dims(), [2, 3, 4]);
+        assert_close(&masked.to_vec()?, &expected.to_vec()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! Losses, averaged over all the examples of a batch.
+use super::{keep_dims, log_softmax, mean_all, normalize_axis, reduce_sum};
+use crate::Tensor;
+use xla::Result;
+
+/// The mean squared error between `predictions` and `targets`.
+pub fn mse(predictions: &Tensor, targets: &Tensor) -> Result<Tensor> {
+    let diff = predictions.sub(targets);
+    mean_all(&diff.mul(&diff))
+}
+
+/// The mean Huber loss between `predictions` and `targets`, quadratic for differences up to
+/// `delta` and linear beyond: `d^2 / 2` if `|d| <= delta`, `delta * (|d| - delta / 2)`
+/// otherwise.
+pub fn huber(predictions: &Tensor, targets: &Tensor, delta: f64) -> Result<Tensor> {
+    let diff = predictions.sub(targets);
+    let abs = diff.abs();
+    let quadratic = diff.mul(&diff).mul(&diff.scalar_like(0.5));
+    let linear = abs
+        .sub(&diff.scalar_like(delta / 2.))
+        .mul(&diff.scalar_like(delta));
+    let loss = abs.le(&diff.scalar_like(delta)).select(&quadratic, &linear);
+    mean_all(&loss)
+}
+
+/// The mean cross-entropy between the distributions given by `logits`, normalised along their
+/// last dimension, and integer class `labels` with the shape of `logits` without its last
+/// dimension.
+pub fn cross_entropy(logits: &Tensor, labels: &Tensor) -> Result<Tensor> {
+    let shape = logits.array_shape()?;
+    let dims = shape.dims();
+    let last = normalize_axis(-1, dims.len())?;
+    let log_probs = log_softmax(logits, last)?;
+    let classes = logits
+        .builder()
+        .iota(dims, labels.array_shape()?.ty(), last);
+    let one_hot = keep_dims(labels, dims, &[last]).eq(&classes);
+    let picked = one_hot.select(&log_probs, &log_probs.zeros_like());
+    mean_all(&reduce_sum(&picked, &[last])?.neg())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jit;
+    use crate::nn::assert_close;
+    use xla::{DeviceArray, PjRtClient};
+
+    #[test]
+    fn losses() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let f = jit(&client, |(x, y): (Tensor, Tensor)| -> Result<_> {
+            Ok((mse(&x, &y)?, huber(&x, &y, 1.)?))
+        });
+        let x = DeviceArray::from_host(&client, &[0f32, 1., 2., 3.], &[2, 2])?;
+        let y = DeviceArray::from_host(&client, &[0f32, 0.5, 4., 0.], &[2, 2])?;
+        let (mse, huber) = f.call(&(x, y))?;
+        assert_close(&mse.to_vec()?, &[(0.25 + 4. + 9.) / 4.]);
+        assert_close(&huber.to_vec()?, &[(0.125 + 1.5 + 2.5) / 4.]);
+
+        let f = jit(&client, |(logits, labels): (Tensor, Tensor)| {
+            cross_entropy(&logits, &labels)
+        });
+        let logits = DeviceArray::from_host(&client, &[0f32, 0., 0., 5., 0., -5.], &[2, 3])?;
+        let labels = DeviceArray::from_host(&client, &[1i32, 0], &[2])?;
+        let loss = f.call(&(logits, labels))?;
+        let second = (1. + (-5f32).exp() + (-10f32).exp()).ln();
+        assert_close(&loss.to_vec()?, &[(3f32.ln() + second) / 2.]);
+        Ok(())
+    }
+}
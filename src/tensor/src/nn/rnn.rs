@@ -0,0 +1,269 @@
+//! Recurrent cells, applied to one step of a sequence at a time.
+use super::{Init, dense, full, normalize_axis, rank};
+use crate::{Tensor, tree_struct};
+use xla::{ArrayShape, ElementType, PrngKey, Result, XlaBuilder};
+
+/// Splits the last dimension of `x` into `n` consecutive chunks of the given size.
+fn split_last(x: &Tensor, n: i64, size: i64) -> Result<Vec<Tensor>> {
+    let last = normalize_axis(-1, rank(x)?)?;
+    Ok((0..n)
+        .map(|i| x.slice_in_dim(i * size, (i + 1) * size, 1, last))
+        .collect())
+}
+
+fn weight(init: Init, key: &PrngKey, ty: ElementType, rows: i64, cols: i64) -> Tensor {
+    init.sample(
+        key,
+        &ArrayShape::new_with_type(ty, vec![rows, cols]),
+        rows,
+        cols,
+    )
+}
+
+tree_struct! {
+    /// The parameters of a [`GruCell`], the gates being stacked along the last dimension in the
+    /// order reset, update, new.
+    #[derive(Debug, Clone)]
+    pub struct GruCellParams<T> {
+        /// `[input_size, 3 * hidden_size]`
+        pub weight_ih: T,
+        /// `[hidden_size, 3 * hidden_size]`
+        pub weight_hh: T,
+        /// `[3 * hidden_size]`
+        pub bias_ih: T,
+        /// `[3 * hidden_size]`
+        pub bias_hh: T,
+    }
+}
+
+/// A gated recurrent unit cell, mapping an input of shape `[batch, input_size]` and a hidden
+/// state of shape `[batch, hidden_size]` to the next hidden state, with the same equations as
+/// PyTorch:
+///
+/// ```text
+/// r = sigmoid(x W_ir + b_ir + h W_hr + b_hr)
+/// z = sigmoid(x W_iz + b_iz + h W_hz + b_hz)
+/// n = tanh(x W_in + b_in + r * (h W_hn + b_hn))
+/// h' = (1 - z) * n + z * h
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GruCell {
+    pub input_size: i64,
+    pub hidden_size: i64,
+    /// The initialisation of the weights, the biases starting at zero.
+    pub init: Init,
+}
+
+impl GruCell {
+    /// A cell with Glorot uniform initialisation.
+    pub fn new(input_size: i64, hidden_size: i64) -> Self {
+        Self {
+            input_size,
+            hidden_size,
+            init: Init::GlorotUniform,
+        }
+    }
+
+    pub fn init(&self, key: &PrngKey, ty: ElementType) -> GruCellParams<Tensor> {
+        let keys = key.split(2);
+        let gates = 3 * self.hidden_size;
+        let builder = key.state().builder();
+        GruCellParams {
+            weight_ih: weight(self.init, &keys[0], ty, self.input_size, gates),
+            weight_hh: weight(self.init, &keys[1], ty, self.hidden_size, gates),
+            bias_ih: full(builder, ty, &[gates], 0.),
+            bias_hh: full(builder, ty, &[gates], 0.),
+        }
+    }
+
+    /// A hidden state of zeros.
+    pub fn initial_state(&self, builder: &XlaBuilder, ty: ElementType, batch: i64) -> Tensor {
+        full(builder, ty, &[batch, self.hidden_size], 0.)
+    }
+
+    /// Returns the hidden state following `h` after reading `x`.
+    pub fn forward(
+        &self,
+        params: &GruCellParams<Tensor>,
+        h: &Tensor,
+        x: &Tensor,
+    ) -> Result<Tensor> {
+        let size = self.hidden_size;
+        let xs = split_last(
+            &dense(x, &params.weight_ih, Some(&params.bias_ih))?,
+            3,
+            size,
+        )?;
+        let hs = split_last(
+            &dense(h, &params.weight_hh, Some(&params.bias_hh))?,
+            3,
+            size,
+        )?;
+        let r = xs[0].add(&hs[0]).logistic();
+        let z = xs[1].add(&hs[1]).logistic();
+        let n = xs[2].add(&r.mul(&hs[2])).tanh();
+        Ok(n.add(&z.mul(&h.sub(&n))))
+    }
+}
+
+tree_struct! {
+    /// The parameters of an [`LstmCell`], the gates being stacked along the last dimension in
+    /// the order input, forget, cell, output.
+    #[derive(Debug, Clone)]
+    pub struct LstmCellParams<T> {
+        /// `[input_size, 4 * hidden_size]`
+        pub weight_ih: T,
+        /// `[hidden_size, 4 * hidden_size]`
+        pub weight_hh: T,
+        /// `[4 * hidden_size]`
+        pub bias: T,
+    }
+}
+
+tree_struct! {
+    /// The state carried by an [`LstmCell`] from one step to the next.
+    #[derive(Debug, Clone)]
+    pub struct LstmState<T> {
+        /// The hidden state `[batch, hidden_size]`, which is also the output of the step.
+        pub h: T,
+        /// The cell state `[batch, hidden_size]`.
+        pub c: T,
+    }
+}
+
+/// A long short-term memory cell, mapping an input of shape `[batch, input_size]` and an
+/// [`LstmState`] to the next state:
+///
+/// ```text
+/// i = sigmoid(x W_ii + h W_hi + b_i)
+/// f = sigmoid(x W_if + h W_hf + b_f)
+/// g = tanh(x W_ig + h W_hg + b_g)
+/// o = sigmoid(x W_io + h W_ho + b_o)
+/// c' = f * c + i * g
+/// h' = o * tanh(c')
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct LstmCell {
+    pub input_size: i64,
+    pub hidden_size: i64,
+    /// The initialisation of the weights, the bias starting at zero.
+    pub init: Init,
+}
+
+impl LstmCell {
+    /// A cell with Glorot uniform initialisation.
+    pub fn new(input_size: i64, hidden_size: i64) -> Self {
+        Self {
+            input_size,
+            hidden_size,
+            init: Init::GlorotUniform,
+        }
+    }
+
+    pub fn init(&self, key: &PrngKey, ty: ElementType) -> LstmCellParams<Tensor> {
+        let keys = key.split(2);
+        let gates = 4 * self.hidden_size;
+        LstmCellParams {
+            weight_ih: weight(self.init, &keys[0], ty, self.input_size, gates),
+            weight_hh: weight(self.init, &keys[1], ty, self.hidden_size, gates),
+            bias: full(key.state().builder(), ty, &[gates], 0.),
+        }
+    }
+
+    /// A state of zeros.
+    pub fn initial_state(
+        &self,
+        builder: &XlaBuilder,
+        ty: ElementType,
+        batch: i64,
+    ) -> LstmState<Tensor> {
+        let zeros = full(builder, ty, &[batch, self.hidden_size], 0.);
+        LstmState {
+            h: zeros.clone(),
+            c: zeros,
+        }
+    }
+
+    /// Returns the state following `state` after reading `x`.
+    pub fn forward(
+        &self,
+        params: &LstmCellParams<Tensor>,
+        state: &LstmState<Tensor>,
+        x: &Tensor,
+    ) -> Result<LstmState<Tensor>> {
+        let gates = dense(x, &params.weight_ih, Some(&params.bias))?.add(&dense(
+            &state.h,
+            &params.weight_hh,
+            None,
+        )?);
+        let gates = split_last(&gates, 4, self.hidden_size)?;
+        let i = gates[0].logistic();
+        let f = gates[1].logistic();
+        let g = gates[2].tanh();
+        let o = gates[3].logistic();
+        let c = f.mul(&state.c).add(&i.mul(&g));
+        let h = o.mul(&c.tanh());
+        Ok(LstmState { h, c })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jit;
+    use crate::nn::assert_close;
+    use xla::{DeviceArray, PjRtClient};
+
+    #[test]
+    fn recurrent_cells() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        // With zero weights the gates are all 1/2 and the candidate values zero, whatever the
+        // input, so the states are halved.
+        let gru = GruCell {
+            init: Init::Zeros,
+            ..GruCell::new(3, 2)
+        };
+        let lstm = LstmCell {
+            init: Init::Zeros,
+            ..LstmCell::new(3, 2)
+        };
+        let f = jit(
+            &client,
+            |(x, h, c): (Tensor, Tensor, Tensor)| -> Result<_> {
+                let key = PrngKey::new(x.builder(), 0);
+                let h = gru.forward(&gru.init(&key, ElementType::F32), &h, &x)?;
+                let state = LstmState { h: c.clone(), c };
+                let state = lstm.forward(&lstm.init(&key, ElementType::F32), &state, &x)?;
+                Ok((h, state))
+            },
+        );
+        let x = DeviceArray::from_host(&client, &[1f32, 2., 3.], &[1, 3])?;
+        let h = DeviceArray::from_host(&client, &[2f32, -4.], &[1, 2])?;
+        let c = DeviceArray::from_host(&client, &[2f32, -4.], &[1, 2])?;
+        let (h, state) = f.call(&(x, h, c))?;
+        assert_close(&h.to_vec()?, &[1., -2.]);
+        assert_close(&state.c.to_vec()?, &[1., -2.]);
+        let tanh = |v: f32| 0.5 * v.tanh();
+        assert_close(&state.h.to_vec()?, &[tanh(1.), tanh(-2.)]);
+
+        // Randomly initialised cells keep the shape of their state.
+        let gru = GruCell::new(3, 2);
+        let lstm = LstmCell::new(3, 5);
+        let f = jit(&client, |x: Tensor| -> Result<_> {
+            let builder = x.builder();
+            let keys = PrngKey::new(builder, 0).split(2);
+            let params = gru.init(&keys[0], ElementType::F32);
+            let h = gru.initial_state(builder, ElementType::F32, 1);
+            let h = gru.forward(&params, &h, &x)?;
+            let params = lstm.init(&keys[1], ElementType::F32);
+            let state = lstm.initial_state(builder, ElementType::F32, 1);
+            let state = lstm.forward(&params, &state, &x)?;
+            Ok((h, state.c))
+        });
+        let x = DeviceArray::from_host(&client, &[1f32, 2., 3.], &[1, 3])?;
+        let (h, c) = f.call(&x)?;
+        assert_eq!(h.dims(), [1, 2]);
+        assert_eq!(c.dims(), [1, 5]);
+        Ok(())
+    }
+}
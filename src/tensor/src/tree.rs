@@ -0,0 +1,441 @@
+//! Flattening of nested inputs and outputs into lists of leaves.
+use std::collections::HashMap;
+use xla::{DeviceArray, Error, Literal, PjRtBuffer, Result, Shape, XlaBuilder, XlaOp};
+
+/// The structure of a flattened tree, leaves excluded.
+///
+/// Trees map onto XLA tuples: a leaf stands for itself and every other node for the tuple of
+/// its children, in the order leaves are flattened.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TreeDef {
+    Leaf,
+    Tuple(Vec<TreeDef>),
+    List(Vec<TreeDef>),
+    /// A map with string keys, its entries being sorted by key.
+    Dict(Vec<(String, TreeDef)>),
+    /// A struct defined with [`tree_struct!`](crate::tree_struct), with its name and fields.
+    Struct(&'static str, Vec<(&'static str, TreeDef)>),
+}
+
+impl TreeDef {
+    /// The number of leaves in trees with this structure.
+    pub fn num_leaves(&self) -> usize {
+        self.children().map_or(1, |c| c.map(Self::num_leaves).sum())
+    }
+
+    fn children(&self) -> Option<Box<dyn Iterator<Item = &TreeDef> + '_>> {
+        match self {
+            Self::Leaf => None,
+            Self::Tuple(defs) | Self::List(defs) => Some(Box::new(defs.iter())),
+            Self::Dict(defs) => Some(Box::new(defs.iter().map(|(_, def)| def))),
+            Self::Struct(_, defs) => Some(Box::new(defs.iter().map(|(_, def)| def))),
+        }
+    }
+
+    fn check_num_leaves(&self, got: usize) -> Result<()> {
+        let expected = self.num_leaves();
+        if expected != got {
+            Err(Error::UnexpectedNumberOfElemsInTuple { expected, got })?
+        }
+        Ok(())
+    }
+
+    fn nest<T>(&self, leaves: &mut impl Iterator<Item = T>, tuple: &impl Fn(Vec<T>) -> T) -> T {
+        match self.children() {
+            None => leaves.next().unwrap(),
+            Some(children) => tuple(children.map(|def| def.nest(leaves, tuple)).collect()),
+        }
+    }
+
+    /// The tuple shape of trees with this structure, given the shapes of their leaves.
+    pub fn shape(&self, leaves: &[Shape]) -> Result<Shape> {
+        self.check_num_leaves(leaves.len())?;
+        Ok(self.nest(&mut leaves.iter().cloned(), &Shape::tuple))
+    }
+
+    /// Builds the tuple holding `leaves` with this structure.
+    pub fn tuple_op(&self, builder: &XlaBuilder, leaves: &[XlaOp]) -> Result<XlaOp> {
+        self.check_num_leaves(leaves.len())?;
+        let tuple = |elems: Vec<XlaOp>| {
+            let elems = elems.iter().map(|op| op.as_ref()).collect::<Vec<_>>();
+            builder.tuple(&elems)
+        };
+        Ok(self.nest(&mut leaves.iter().cloned(), &tuple))
+    }
+
+    /// Extracts the leaves of `op`, a tuple with this structure such as a parameter with the
+    /// shape returned by [`TreeDef::shape`].
+    pub fn untuple_op(&self, op: &XlaOp) -> Vec<XlaOp> {
+        let mut leaves = vec![];
+        self.untuple_op_into(op, &mut leaves);
+        leaves
+    }
+
+    fn untuple_op_into(&self, op: &XlaOp, leaves: &mut Vec<XlaOp>) {
+        match self.children() {
+            None => leaves.push(op.clone()),
+            Some(children) => {
+                for (index, def) in children.enumerate() {
+                    def.untuple_op_into(&op.get_tuple_element(index as i64), leaves)
+                }
+            }
+        }
+    }
+
+    /// Builds the tuple literal holding `leaves` with this structure.
+    pub fn tuple_literal(&self, leaves: Vec<Literal>) -> Result<Literal> {
+        self.check_num_leaves(leaves.len())?;
+        Ok(self.nest(&mut leaves.into_iter(), &Literal::tuple))
+    }
+
+    /// Extracts the leaves of `literal`, a tuple with this structure such as the output of an
+    /// executable that returns a tree.
+    pub fn untuple_literal(&self, literal: Literal) -> Result<Vec<Literal>> {
+        let mut leaves = vec![];
+        self.untuple_literal_into(literal, &mut leaves)?;
+        Ok(leaves)
+    }
+
+    fn untuple_literal_into(&self, mut literal: Literal, leaves: &mut Vec<Literal>) -> Result<()> {
+        let Some(children) = self.children() else {
+            leaves.push(literal);
+            return Ok(());
+        };
+        let children = children.collect::<Vec<_>>();
+        let shape = literal.shape()?;
+        let Some(got) = shape.tuple_size() else {
+            return Err(Error::NotATuple { got: shape });
+        };
+        if got != children.len() {
+            Err(Error::UnexpectedNumberOfElemsInTuple {
+                expected: children.len(),
+                got,
+            })?
+        }
+        for (def, elem) in children.into_iter().zip(literal.decompose_tuple()?) {
+            def.untuple_literal_into(elem, leaves)?
+        }
+        Ok(())
+    }
+}
+
+/// A nested structure of leaves, such as tuples, `Vec`s, maps and structs of tensors, in the
+/// manner of JAX pytrees.
+///
+/// `Map<U>` is the same structure with leaves of type `U`, so that for instance a tree of
+/// device buffers can be traced as the corresponding tree of [`XlaOp`]. User structs implement
+/// this trait through [`tree_struct!`](crate::tree_struct).
+pub trait TreeFlatten {
+    type Leaf;
+    type Map<U>;
+
+    /// Appends the leaves of this tree to `leaves` in depth-first order and returns its
+    /// structure.
+    fn flatten<'a>(&'a self, leaves: &mut Vec<&'a Self::Leaf>) -> TreeDef;
+
+    /// Rebuilds a tree with structure `def` from the leaves produced by `leaves`, returning
+    /// `None` if `def` does not describe a `Self` or if there are not enough leaves.
+    fn unflatten<U>(def: &TreeDef, leaves: &mut impl Iterator<Item = U>) -> Option<Self::Map<U>>;
+}
+
+/// The types making up the leaves of trees.
+pub trait Leaf {}
+
+impl Leaf for XlaOp {}
+impl Leaf for PjRtBuffer {}
+impl Leaf for Literal {}
+impl Leaf for DeviceArray {}
+
+impl<T: Leaf> TreeFlatten for T {
+    type Leaf = T;
+    type Map<U> = U;
+
+    fn flatten<'a>(&'a self, leaves: &mut Vec<&'a Self::Leaf>) -> TreeDef {
+        leaves.push(self);
+        TreeDef::Leaf
+    }
+
+    fn unflatten<U>(def: &TreeDef, leaves: &mut impl Iterator<Item = U>) -> Option<Self::Map<U>> {
+        match def {
+            TreeDef::Leaf => leaves.next(),
+            _ => None,
+        }
+    }
+}
+
+/// Flattens `tree` into its leaves and structure.
+pub fn tree_flatten<T: TreeFlatten>(tree: &T) -> (Vec<&T::Leaf>, TreeDef) {
+    let mut leaves = vec![];
+    let def = tree.flatten(&mut leaves);
+    (leaves, def)
+}
+
+/// Rebuilds a tree of type `T::Map<U>` from its structure and leaves, returning `None` unless
+/// `def` describes a `T` with exactly `leaves.len()` leaves.
+pub fn tree_unflatten<T: TreeFlatten, U>(def: &TreeDef, leaves: Vec<U>) -> Option<T::Map<U>> {
+    let mut leaves = leaves.into_iter();
+    let tree = T::unflatten(def, &mut leaves)?;
+    leaves.next().is_none().then_some(tree)
+}
+
+macro_rules! impl_tuple {
+    ($(($($t:ident $i:tt),+))*) => {
+        $(
+            impl<L, $($t: TreeFlatten<Leaf = L>),+> TreeFlatten for ($($t,)+) {
+                type Leaf = L;
+                type Map<U> = ($($t::Map<U>,)+);
+
+                fn flatten<'a>(&'a self, leaves: &mut Vec<&'a L>) -> TreeDef {
+                    TreeDef::Tuple(vec![$(self.$i.flatten(leaves)),+])
+                }
+
+                fn unflatten<U>(
+                    def: &TreeDef,
+                    leaves: &mut impl Iterator<Item = U>,
+                ) -> Option<Self::Map<U>> {
+                    let TreeDef::Tuple(defs) = def else {
+                        return None;
+                    };
+                    let mut defs = defs.iter();
+                    let tree = ($($t::unflatten(defs.next()?, leaves)?,)+);
+                    defs.next().is_none().then_some(tree)
+                }
+            }
+        )*
+    };
+}
+
+impl_tuple! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+}
+
+impl<T: TreeFlatten> TreeFlatten for Vec<T> {
+    type Leaf = T::Leaf;
+    type Map<U> = Vec<T::Map<U>>;
+
+    fn flatten<'a>(&'a self, leaves: &mut Vec<&'a Self::Leaf>) -> TreeDef {
+        TreeDef::List(self.iter().map(|t| t.flatten(leaves)).collect())
+    }
+
+    fn unflatten<U>(def: &TreeDef, leaves: &mut impl Iterator<Item = U>) -> Option<Self::Map<U>> {
+        let TreeDef::List(defs) = def else {
+            return None;
+        };
+        defs.iter().map(|def| T::unflatten(def, leaves)).collect()
+    }
+}
+
+impl<T: TreeFlatten> TreeFlatten for HashMap<String, T> {
+    type Leaf = T::Leaf;
+    type Map<U> = HashMap<String, T::Map<U>>;
+
+    fn flatten<'a>(&'a self, leaves: &mut Vec<&'a Self::Leaf>) -> TreeDef {
+        let mut entries = self.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|(key, _)| *key);
+        let defs = entries
+            .into_iter()
+            .map(|(key, value)| (key.clone(), value.flatten(leaves)))
+            .collect();
+        TreeDef::Dict(defs)
+    }
+
+    fn unflatten<U>(def: &TreeDef, leaves: &mut impl Iterator<Item = U>) -> Option<Self::Map<U>> {
+        let TreeDef::Dict(defs) = def else {
+            return None;
+        };
+        defs.iter()
+            .map(|(key, def)| Some((key.clone(), T::unflatten(def, leaves)?)))
+            .collect()
+    }
+}
+
+/// Defines a struct generic over its leaf type and implements [`TreeFlatten`] for it, each
+/// field being a tree of such leaves.
+///
+/// ```ignore
+/// tree_struct! {
+///     #[derive(Debug)]
+///     pub struct Linear<T> {
+///         pub weight: T,
+///         pub bias: T,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! tree_struct {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident<$t:ident> {
+            $($(#[$field_attr:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name<$t> {
+            $($(#[$field_attr])* $field_vis $field: $ty),*
+        }
+
+        impl<$t: $crate::Leaf> $crate::TreeFlatten for $name<$t> {
+            type Leaf = $t;
+            type Map<U> = $name<U>;
+
+            fn flatten<'a>(&'a self, leaves: &mut Vec<&'a $t>) -> $crate::TreeDef {
+                $crate::TreeDef::Struct(
+                    stringify!($name),
+                    vec![$((
+                        stringify!($field),
+                        $crate::TreeFlatten::flatten(&self.$field, leaves),
+                    )),*],
+                )
+            }
+
+            fn unflatten<U>(
+                def: &$crate::TreeDef,
+                leaves: &mut impl Iterator<Item = U>,
+            ) -> Option<$name<U>> {
+                let $crate::TreeDef::Struct(stringify!($name), defs) = def else {
+                    return None;
+                };
+                let mut defs = defs.iter();
+                let tree = $name {
+                    $($field: {
+                        let (name, def) = defs.next()?;
+                        if *name != stringify!($field) {
+                            return None;
+                        }
+                        <$ty as $crate::TreeFlatten>::unflatten(def, leaves)?
+                    }),*
+                };
+                defs.next().is_none().then_some(tree)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Tensor, jit};
+    use xla::{BufferArgsRef, PjRtClient};
+
+    tree_struct! {
+        struct Linear<T> {
+            weight: T,
+            bias: T,
+        }
+    }
+
+    tree_struct! {
+        struct Params<T> {
+            layers: Vec<Linear<T>>,
+            extra: HashMap<String, T>,
+        }
+    }
+
+    fn to_vec(literal: &Literal) -> Result<Vec<f32>> {
+        Ok(literal.typed_buf::<f32>()?.to_vec())
+    }
+
+    #[test]
+    fn flatten_nested_struct() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let buf = |v: f32| client.copy_host_buffer(&[v], &[]);
+        let params = Params {
+            layers: vec![
+                Linear {
+                    weight: buf(1.)?,
+                    bias: buf(2.)?,
+                },
+                Linear {
+                    weight: buf(3.)?,
+                    bias: buf(4.)?,
+                },
+            ],
+            extra: HashMap::from([("b".to_string(), buf(6.)?), ("a".to_string(), buf(5.)?)]),
+        };
+        let (leaves, def) = tree_flatten(&params);
+        assert_eq!(def.num_leaves(), 6);
+        let values = leaves
+            .iter()
+            .map(|buf| Ok(to_vec(&buf.to_literal_sync()?)?[0]))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(values, [1., 2., 3., 4., 5., 6.]);
+
+        // Unflattening with the structure of another type fails.
+        assert!(tree_unflatten::<Vec<PjRtBuffer>, _>(&def, vec![0; 6]).is_none());
+        assert!(tree_unflatten::<Params<PjRtBuffer>, _>(&def, vec![0; 5]).is_none());
+        let ids = tree_unflatten::<Params<PjRtBuffer>, _>(&def, (0..6).collect()).unwrap();
+        assert_eq!(ids.layers[1].bias, 3);
+        assert_eq!(ids.extra["a"], 4);
+
+        // Structs are traced by jit like any other tree.
+        let sgd = jit(&client, |params: Params<Tensor>| Params {
+            layers: params
+                .layers
+                .into_iter()
+                .map(|l| Linear {
+                    weight: l.weight.clone() + l.weight,
+                    bias: l.bias,
+                })
+                .collect(),
+            extra: params.extra,
+        });
+        let params = sgd.call(&params)?;
+        assert_eq!(params.layers[1].weight.to_vec::<f32>()?, [6.]);
+        assert_eq!(params.extra["b"].to_vec::<f32>()?, [6.]);
+        Ok(())
+    }
+
+    #[test]
+    fn tree_tuples() -> Result<()> {
+        let client = PjRtClient::cpu()?;
+        let def = tree_flatten(&(
+            Literal::scalar(1f32),
+            vec![Literal::scalar(2f32), Literal::scalar(3f32)],
+        ))
+        .1;
+        let scalar = Shape::array::<f32>(vec![]);
+        let shape = def.shape(&[scalar.clone(), scalar.clone(), scalar.clone()])?;
+        assert_eq!(
+            shape,
+            Shape::tuple(vec![
+                scalar.clone(),
+                Shape::tuple(vec![scalar.clone(), scalar.clone()])
+            ])
+        );
+
+        // Takes the tree as a single tuple parameter and returns it with its leaves doubled.
+        let builder = XlaBuilder::new("test");
+        let param = builder.parameter(0, shape, "params")?;
+        let leaves = def
+            .untuple_op(&param)
+            .into_iter()
+            .map(|op| op.clone() + op)
+            .collect::<Vec<_>>();
+        let comp = def.tuple_op(&builder, &leaves)?.build()?;
+        let exec = client.compile_with_default_options(&comp)?;
+        let input = def.tuple_literal(vec![
+            Literal::scalar(1f32),
+            Literal::scalar(2f32),
+            Literal::scalar(3f32),
+        ])?;
+        let input = client.copy_literal(&input)?;
+        let out = exec.execute_buffers(BufferArgsRef::from([&input]))?;
+        let leaves = def.untuple_literal(out[0].to_literal_sync()?)?;
+        let (x, ys) = tree_unflatten::<(Literal, Vec<Literal>), _>(&def, leaves).unwrap();
+        assert_eq!(to_vec(&x)?, [2.]);
+        assert_eq!(to_vec(&ys[1])?, [6.]);
+        assert!(matches!(
+            def.tuple_literal(vec![Literal::scalar(1f32)]),
+            Err(Error::UnexpectedNumberOfElemsInTuple {
+                expected: 3,
+                got: 1
+            })
+        ));
+        Ok(())
+    }
+}
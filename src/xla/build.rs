@@ -103,6 +103,11 @@ fn main() -> anyhow::Result<()> {
     println!("cargo:rerun-if-changed=src/buffer.rs");
     println!("cargo:rerun-if-changed=src/computation.rs");
     println!("cargo:rerun-if-changed=src/hlo_module.rs");
+    println!("cargo:rerun-if-changed=src/future.rs");
+    println!("cargo:rerun-if-changed=src/device.rs");
+    println!("cargo:rerun-if-changed=src/sharding.rs");
+    println!("cargo:rerun-if-changed=src/custom_call.rs");
+    println!("cargo:rerun-if-changed=src/prng.rs");
 
     let jax_metal_dir =
         env_var_rerun("JAX_METAL_DIR").map_or_else(|| out_dir.join("jax_metal"), PathBuf::from);
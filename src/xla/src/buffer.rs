@@ -1,4 +1,6 @@
-use crate::{Literal, RawShape, Result, Status};
+use crate::{
+    Error, Literal, PjRtDevice, PjRtFuture, RawPjRtFuture, RawShape, Result, Status, StatusCode,
+};
 
 use cpp::{cpp, cpp_class};
 
@@ -34,6 +36,16 @@ impl PjRtBuffer {
         }
     }
 
+    /// Returns true if the buffer has been deleted, typically because it was donated to an
+    /// execution whose output aliases it.
+    pub fn is_deleted(&self) -> bool {
+        unsafe {
+            cpp!([self as "const std::unique_ptr<PjRtBuffer>*"] -> bool as "bool" {
+                return (*self)->IsDeleted();
+            })
+        }
+    }
+
     pub fn shape(&self) -> RawShape {
         unsafe {
             cpp!([self as "std::unique_ptr<PjRtBuffer>*"] -> RawShape as "xla::Shape" {
@@ -45,6 +57,9 @@ impl PjRtBuffer {
     }
 
     pub(crate) fn copy_into(&self, dst: &mut Vec<u8>) -> Result<()> {
+        if self.is_deleted() {
+            Err(Error::DonatedBuffer)?
+        }
         let shape = self.shape();
         let len = shape.size();
         dst.clear();
@@ -64,6 +79,9 @@ impl PjRtBuffer {
     }
 
     pub fn to_literal_sync(&self) -> Result<Literal> {
+        if self.is_deleted() {
+            Err(Error::DonatedBuffer)?
+        }
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let lit = unsafe {
             cpp!([self as "std::unique_ptr<PjRtBuffer>*", out_status as "Status*"] -> Literal as "std::shared_ptr<Literal>" {
@@ -79,12 +97,91 @@ impl PjRtBuffer {
         out_status.to_result()?;
         Ok(lit)
     }
+
+    /// Copies the buffer to a host literal without blocking the calling thread.
+    pub fn to_literal_async(&self) -> PjRtFuture<Literal> {
+        let lit = unsafe {
+            cpp!([self as "std::unique_ptr<PjRtBuffer>*"] -> Literal as "std::shared_ptr<Literal>" {
+                auto shape = ShapeUtil::DeviceShapeToHostShape((*self)->on_device_shape());
+                return std::make_shared<Literal>(shape);
+            })
+        };
+        let lit_ref = &lit;
+        let raw = unsafe {
+            cpp!([self as "std::unique_ptr<PjRtBuffer>*", lit_ref as "std::shared_ptr<Literal>*"] -> RawPjRtFuture as "PjRtFuture<>" {
+                auto lit = *lit_ref;
+                auto future = (*self)->ToLiteral(lit.get());
+                // Keep the destination alive until the transfer completes, even if the
+                // Rust future is dropped early.
+                future.OnReady([lit](absl::Status) {});
+                return future;
+            })
+        };
+        PjRtFuture::new(raw, lit)
+    }
+
+    /// Returns a future that resolves once the buffer's contents have been computed or
+    /// transferred.
+    pub fn ready(&self) -> PjRtFuture<()> {
+        let raw = unsafe {
+            cpp!([self as "std::unique_ptr<PjRtBuffer>*"] -> RawPjRtFuture as "PjRtFuture<>" {
+                return (*self)->GetReadyFuture();
+            })
+        };
+        PjRtFuture::new(raw, ())
+    }
+
+    /// Blocks until the buffer's contents are available.
+    pub fn block_until_ready(&self) -> Result<()> {
+        self.ready().wait()
+    }
+
+    /// Copies the buffer to the given device without going through the host.
+    pub fn copy_to_device(&self, device: &PjRtDevice) -> Result<PjRtBuffer> {
+        if self.is_deleted() {
+            Err(Error::DonatedBuffer)?
+        }
+        let device = &device.raw;
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let buffer = unsafe {
+            cpp!([self as "std::unique_ptr<PjRtBuffer>*", device as "PjRtDevice**", out_status as "Status*"] -> PjRtBuffer as "std::unique_ptr<PjRtBuffer>" {
+                auto status = (*self)->CopyToDevice(*device);
+                if (status.ok()) {
+                    return std::move(status.value());
+                }else{
+                    *out_status = Status(status.status());
+                    return std::unique_ptr<PjRtBuffer>();
+                }
+            })
+        };
+        out_status.to_result()?;
+        if buffer.is_null() {
+            let backtrace = std::backtrace::Backtrace::capture().to_string();
+            return Err(Error::XlaError {
+                code: StatusCode::Internal,
+                msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
+                backtrace,
+            });
+        }
+        Ok(buffer)
+    }
+}
+
+impl AsRef<PjRtBuffer> for PjRtBuffer {
+    fn as_ref(&self) -> &PjRtBuffer {
+        self
+    }
 }
 
+/// Arguments borrowed for an execution. They are never donated, since the caller keeps
+/// ownership of the buffers.
 pub struct BufferArgsRef<'a> {
     phantom_data: PhantomData<&'a ()>,
     pub(crate) untuple_result: bool,
     pub(crate) buffers: BufferArgsInner,
+    /// The indices of all the arguments pushed so far.
+    non_donatable: Vec<i64>,
 }
 
 impl Default for BufferArgsRef<'_> {
@@ -98,12 +195,14 @@ impl Default for BufferArgsRef<'_> {
                 })
             },
             untuple_result: false,
+            non_donatable: vec![],
         }
     }
 }
 
 impl<'a> BufferArgsRef<'a> {
     pub fn push(&mut self, buf: &'a PjRtBuffer) {
+        self.non_donatable.push(self.non_donatable.len() as i64);
         let inner = &mut self.buffers;
         let buf = buf as *const PjRtBuffer;
         unsafe {
@@ -139,6 +238,12 @@ impl<'a, const N: usize> From<[&'a PjRtBuffer; N]> for BufferArgsRef<'a> {
 pub trait BufferArgs {
     fn get(&self) -> &'_ BufferArgsInnerRaw;
     fn untuple_result(&self) -> bool;
+
+    /// Indices of arguments that must not be donated, even if the executable aliases them with
+    /// an output.
+    fn non_donatable_input_indices(&self) -> &[i64] {
+        &[]
+    }
 }
 
 impl BufferArgs for BufferArgsRef<'_> {
@@ -155,15 +260,34 @@ impl BufferArgs for BufferArgsRef<'_> {
     fn untuple_result(&self) -> bool {
         self.untuple_result
     }
+
+    fn non_donatable_input_indices(&self) -> &[i64] {
+        &self.non_donatable
+    }
 }
 
 pub struct BufferArgsOwned {
     pub(crate) untuple_result: bool,
     pub(crate) buffers: BufferArgsInner,
+    pub(crate) non_donatable: Vec<i64>,
+    len: usize,
 }
 
 impl BufferArgsOwned {
+    /// Push an argument that may be donated to the execution.
+    ///
+    /// The buffer is consumed either way, so donating it lets an aliased output reuse its memory
+    /// instead of copying.
     pub fn push(&mut self, buf: PjRtBuffer) {
+        self.push_with_donation(buf, true)
+    }
+
+    /// Push an argument, choosing whether it may be donated to the execution.
+    pub fn push_with_donation(&mut self, buf: PjRtBuffer, donate: bool) {
+        if !donate {
+            self.non_donatable.push(self.len as i64);
+        }
+        self.len += 1;
         let inner = &mut self.buffers;
         let mut buf = ManuallyDrop::new(buf);
         unsafe {
@@ -172,6 +296,11 @@ impl BufferArgsOwned {
             })
         };
     }
+
+    pub fn untuple_result(mut self, untuple_result: bool) -> Self {
+        self.untuple_result = untuple_result;
+        self
+    }
 }
 
 impl BufferArgs for BufferArgsOwned {
@@ -188,6 +317,10 @@ impl BufferArgs for BufferArgsOwned {
     fn untuple_result(&self) -> bool {
         self.untuple_result
     }
+
+    fn non_donatable_input_indices(&self) -> &[i64] {
+        &self.non_donatable
+    }
 }
 
 impl Default for BufferArgsOwned {
@@ -200,6 +333,8 @@ impl Default for BufferArgsOwned {
                 })
             },
             untuple_result: false,
+            non_donatable: vec![],
+            len: 0,
         }
     }
 }
@@ -235,4 +370,30 @@ impl<A: BufferArgs> BufferArgs for &'_ A {
     fn untuple_result(&self) -> bool {
         A::untuple_result(*self)
     }
+
+    fn non_donatable_input_indices(&self) -> &[i64] {
+        A::non_donatable_input_indices(*self)
+    }
+}
+
+impl BufferArgsInnerRaw {
+    /// Returns an error if any of the arguments has already been donated.
+    pub(crate) fn check_not_donated(&self) -> Result<()> {
+        let index = unsafe {
+            cpp!([self as "const std::vector<PjRtBuffer*>*"] -> i64 as "int64_t" {
+                for (size_t i = 0; i < self->size(); ++i) {
+                    if ((*self)[i]->IsDeleted()) {
+                        return i;
+                    }
+                }
+                return -1;
+            })
+        };
+        if index >= 0 {
+            Err(Error::DonatedArgument {
+                index: index as usize,
+            })?
+        }
+        Ok(())
+    }
 }
@@ -1,24 +1,94 @@
 use crate::{
-    ElementType, Literal, NativeType, Result, Shape, Status, XlaComputation, XlaOp, XlaOpRaw,
-    XlaOpRef,
+    ElementType, Error, Literal, NativeType, Result, Shape, Status, XlaComputation, XlaOp,
+    XlaOpRaw, XlaOpRef,
 };
 use cpp::{cpp, cpp_class};
-use cxx::let_cxx_string;
+use cxx::{CxxString, UniquePtr, let_cxx_string};
 use std::pin::Pin;
 
 cpp! {{
     #include "xla/client/xla_builder.h"
     using namespace xla;
+
+    // Keeps the closure of a host callback alive, releasing it on the Rust side once the last
+    // builder, computation or executable that may call it is gone.
+    struct RustHostCallback {
+        uint64_t id;
+        void (*release)(uint64_t);
+        RustHostCallback(uint64_t id, void (*release)(uint64_t)) : id(id), release(release) {}
+        ~RustHostCallback() { release(id); }
+    };
+    using RustHostCallbacks = std::vector<std::shared_ptr<RustHostCallback>>;
+
+    // All the builders created from Rust, so that they can remember where the op that reported
+    // the first error was created, and which host callbacks their ops call.
+    class RustXlaBuilder : public XlaBuilder {
+    public:
+        using XlaBuilder::XlaBuilder;
+        OpMetadata first_error_metadata;
+        RustHostCallbacks host_callbacks;
+    };
+
+    // A computation along with the host callbacks it calls.
+    struct RustXlaComputation {
+        XlaComputation computation;
+        RustHostCallbacks host_callbacks;
+    };
+
+    static RustHostCallbacks& HostCallbacks(XlaBuilder* builder) {
+        return static_cast<RustXlaBuilder*>(builder)->host_callbacks;
+    }
+
+    // Builds the computation rooted at `op`, which takes over the host callbacks of the builder.
+    static absl::StatusOr<RustXlaComputation> BuildRustComputation(XlaBuilder* builder, XlaOp op) {
+        auto computation = builder->Build(op, false);
+        if (!computation.ok()) {
+            return computation.status();
+        }
+        return RustXlaComputation{std::move(computation.value()), std::exchange(HostCallbacks(builder), {})};
+    }
+
+    // Returns `computation` for use in an op of `builder`, which then keeps its host callbacks.
+    static const XlaComputation& EmbedRustComputation(XlaBuilder* builder, const RustXlaComputation& computation) {
+        auto& callbacks = HostCallbacks(builder);
+        callbacks.insert(callbacks.end(), computation.host_callbacks.begin(), computation.host_callbacks.end());
+        return computation.computation;
+    }
 }}
 
 cpp_class!(pub unsafe struct XlaBuilder as "std::shared_ptr<XlaBuilder>");
+cpp_class!(unsafe struct OpMetadata as "OpMetadata");
+
+/// Guard returned by [`XlaBuilder::call_site`].
+pub(crate) struct CallSite<'a> {
+    builder: &'a XlaBuilder,
+    previous: OpMetadata,
+    had_error: bool,
+}
+
+impl Drop for CallSite<'_> {
+    fn drop(&mut self) {
+        let builder = self.builder;
+        let previous = &self.previous;
+        let had_error = self.had_error;
+        unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", previous as "const OpMetadata*", had_error as "bool"] {
+                auto rust_builder = static_cast<RustXlaBuilder*>(builder->get());
+                OpMetadata current = rust_builder->SwapOpMetadata(*previous);
+                if (!had_error && !rust_builder->first_error().ok()) {
+                    rust_builder->first_error_metadata = current;
+                }
+            })
+        }
+    }
+}
 
 impl XlaBuilder {
     pub fn new(name: &str) -> Self {
         let_cxx_string!(name = name);
         unsafe {
             cpp!( [name as "std::string*"] -> XlaBuilder as "std::shared_ptr<XlaBuilder>" {
-                std::shared_ptr<XlaBuilder> builder(new XlaBuilder(*name));
+                std::shared_ptr<XlaBuilder> builder(new RustXlaBuilder(*name));
                 return builder;
             })
         }
@@ -27,26 +97,91 @@ impl XlaBuilder {
     pub fn build(&self, op: &XlaOp) -> Result<XlaComputation> {
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let comp = unsafe {
-            cpp!([self as "std::shared_ptr<XlaBuilder>*", op as "XlaOp*", out_status as "Status*"] -> XlaComputation as "XlaComputation" {
-                auto status = (*self)->Build(*op, false);
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", op as "XlaOp*", out_status as "Status*"] -> XlaComputation as "RustXlaComputation" {
+                auto status = BuildRustComputation(self->get(), *op);
                 if (status.ok()) {
                     return std::move(status.value());
                 }else{
                     *out_status = Status(status.status());
-                    return XlaComputation();
+                    return RustXlaComputation();
                 }
             })
         };
-        out_status.to_result()?;
+        out_status
+            .to_result()
+            .map_err(|err| self.with_call_site(err))?;
         Ok(comp)
     }
 
+    /// Records the caller location as the source file and line of the `OpMetadata` attached to
+    /// the ops created until the returned guard is dropped. Nested call sites keep the outermost
+    /// location, so that the ops created by composite functions point at the code calling them.
+    #[track_caller]
+    pub(crate) fn call_site(&self) -> CallSite<'_> {
+        let location = std::panic::Location::caller();
+        let file_ptr = location.file().as_ptr();
+        let file_len = location.file().len();
+        let line = location.line();
+        let had_error = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*"] -> bool as "bool" {
+                return !(*self)->first_error().ok();
+            })
+        };
+        let previous = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", file_ptr as "const char*", file_len as "size_t", line as "uint32_t"] -> OpMetadata as "OpMetadata" {
+                OpMetadata metadata;
+                metadata.set_source_file(std::string(file_ptr, file_len));
+                metadata.set_source_line(line);
+                OpMetadata previous = (*self)->SwapOpMetadata(metadata);
+                if (!previous.source_file().empty()) {
+                    (*self)->SetOpMetadata(previous);
+                }
+                return previous;
+            })
+        };
+        CallSite {
+            builder: self,
+            previous,
+            had_error,
+        }
+    }
+
+    /// Attaches to `err` the call site of the op that reported the first error of this builder.
+    pub(crate) fn with_call_site(&self, err: Error) -> Error {
+        let file = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*"] -> UniquePtr<CxxString> as "std::unique_ptr<std::string>" {
+                auto rust_builder = static_cast<RustXlaBuilder*>(self->get());
+                return std::make_unique<std::string>(rust_builder->first_error_metadata.source_file());
+            })
+        };
+        let line = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*"] -> u32 as "uint32_t" {
+                auto rust_builder = static_cast<RustXlaBuilder*>(self->get());
+                return rust_builder->first_error_metadata.source_line();
+            })
+        };
+        match file.as_ref().and_then(|file| file.to_str().ok()) {
+            Some(file) if !file.is_empty() => Error::BuildError {
+                file: file.to_string(),
+                line,
+                source: Box::new(err),
+            },
+            _ => err,
+        }
+    }
+
+    #[track_caller]
     pub fn concat_in_dim(&self, others: &[XlaOpRef<'_>], dim: i64) -> XlaOp {
+        let _site = self.call_site();
         let others_ptr = others.as_ptr();
         let others_len = others.len();
         let raw = unsafe {
             cpp!([self as "std::shared_ptr<XlaBuilder>*", others_ptr as "const XlaOp*", others_len as "size_t", dim as "int64_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConcatInDim(self->get(), absl::Span(others_ptr, others_len), dim));
+                try {
+                    return XlaOp(ConcatInDim(self->get(), absl::Span(others_ptr, others_len), dim));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -55,12 +190,18 @@ impl XlaBuilder {
         }
     }
 
+    #[track_caller]
     pub fn tuple(&self, elems: &[XlaOpRef<'_>]) -> XlaOp {
+        let _site = self.call_site();
         let elems_ptr = elems.as_ptr();
         let elems_len = elems.len();
         let raw = unsafe {
             cpp!([self as "std::shared_ptr<XlaBuilder>*", elems_ptr as "const XlaOp*", elems_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(Tuple(self->get(), absl::Span(elems_ptr, elems_len)));
+                try {
+                    return XlaOp(Tuple(self->get(), absl::Span(elems_ptr, elems_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -69,14 +210,20 @@ impl XlaBuilder {
         }
     }
 
+    #[track_caller]
     pub fn map(&self, args: &[XlaOpRef<'_>], comp: &XlaComputation, dims: &[i64]) -> XlaOp {
+        let _site = self.call_site();
         let args_ptr = args.as_ptr();
         let args_len = args.len();
         let dims_ptr = dims.as_ptr();
         let dims_len = dims.len();
         let raw = unsafe {
-            cpp!([self as "std::shared_ptr<XlaBuilder>*", args_ptr as "const XlaOp*", args_len as "size_t", comp as "const XlaComputation*", dims_ptr as "const int64_t*", dims_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(Map(self->get(), absl::Span(args_ptr, args_len), *comp, absl::Span(dims_ptr, dims_len)));
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", args_ptr as "const XlaOp*", args_len as "size_t", comp as "const RustXlaComputation*", dims_ptr as "const int64_t*", dims_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(Map(self->get(), absl::Span(args_ptr, args_len), EmbedRustComputation(self->get(), *comp), absl::Span(dims_ptr, dims_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -85,7 +232,9 @@ impl XlaBuilder {
         }
     }
 
+    #[track_caller]
     pub fn parameter(&self, num: i64, shape: Shape, name: &str) -> Result<XlaOp> {
+        let _site = self.call_site();
         let raw_shape = shape.raw_shape();
         let_cxx_string!(name = name);
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
@@ -106,11 +255,17 @@ impl XlaBuilder {
     }
 
     /// Create a node with a constant value defined by the specified literal.
+    #[track_caller]
     pub fn constant_literal(&self, literal: &Literal) -> Result<XlaOp> {
+        let _site = self.call_site();
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let op = unsafe {
             cpp!([self as "std::shared_ptr<XlaBuilder>*", literal as "std::shared_ptr<Literal>*"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantLiteral(self->get(), *literal->get()));
+                try {
+                    return XlaOp(ConstantLiteral(self->get(), *literal->get()));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         out_status.to_result()?;
@@ -120,20 +275,42 @@ impl XlaBuilder {
         })
     }
 
+    #[track_caller]
     pub fn constant<T: NativeType>(&self, val: T) -> XlaOp {
         T::constant_r0(self, val)
     }
 
+    #[track_caller]
     pub fn constant_vector<T: NativeType>(&self, vals: &[T]) -> XlaOp {
         T::constant_r1(self, vals)
     }
 
+    /// Alias the output at `output_index` with parameter `param_num`, so the output may reuse the
+    /// parameter's buffer when the argument is donated at execution time.
     pub fn setup_alias(&self, param_num: u64, output_index: u64) -> Result<()> {
+        self.setup_alias_with_index(param_num, &[], &[output_index as i64])
+    }
+
+    /// Alias a (possibly nested) tuple element of the output with a (possibly nested) element of
+    /// parameter `param_num`. Indices are shape indices, so `&[]` refers to the whole value and
+    /// `&[1, 0]` to the first element of the second tuple element.
+    pub fn setup_alias_with_index(
+        &self,
+        param_num: u64,
+        param_index: &[i64],
+        output_index: &[i64],
+    ) -> Result<()> {
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let param_index_ptr = param_index.as_ptr();
+        let param_index_len = param_index.len();
+        let output_index_ptr = output_index.as_ptr();
+        let output_index_len = output_index.len();
         unsafe {
-            cpp!([self as "std::shared_ptr<XlaBuilder>*", param_num as "uint64_t", output_index as "uint64_t", out_status as "Status*"] {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", param_num as "uint64_t", param_index_ptr as "const int64_t*", param_index_len as "size_t", output_index_ptr as "const int64_t*", output_index_len as "size_t", out_status as "Status*"] {
                 try {
-                    (*self)->SetUpAlias({(int64_t) output_index}, (int64_t) param_num, {}, HloInputOutputAliasConfig::AliasKind::kMustAlias);
+                    ShapeIndex output_shape_index(output_index_ptr, output_index_ptr + output_index_len);
+                    ShapeIndex param_shape_index(param_index_ptr, param_index_ptr + param_index_len);
+                    (*self)->SetUpAlias(output_shape_index, (int64_t) param_num, param_shape_index, HloInputOutputAliasConfig::AliasKind::kMustAlias);
                 }catch(std::exception& e) {
                     *out_status = Status(tsl::errors::Internal(e.what()));
                 }
@@ -141,7 +318,10 @@ impl XlaBuilder {
         };
         out_status.to_result()
     }
+
+    #[track_caller]
     pub fn iota(&self, dims: &[i64], elem_type: ElementType, iota_dim: i64) -> XlaOp {
+        let _site = self.call_site();
         let dims_ptr = dims.as_ptr();
         let dims_len = dims.len();
         let prim_type = elem_type.primitive_type() as i32;
@@ -161,12 +341,86 @@ impl XlaBuilder {
         }
     }
 
+    /// The sample frequencies of a discrete Fourier transform of length `n` with sample spacing
+    /// `d`, in the order used by [`XlaOp::fft`], like `numpy.fft.fftfreq`.
+    #[track_caller]
+    pub fn fftfreq(&self, n: usize, d: f64, elem_type: ElementType) -> XlaOp {
+        let scale = 1. / (d * n as f64);
+        let freqs = (0..n)
+            .map(|i| {
+                let i = if i < n.div_ceil(2) {
+                    i as f64
+                } else {
+                    i as f64 - n as f64
+                };
+                i * scale
+            })
+            .collect::<Vec<_>>();
+        self.constant_vector(&freqs)
+            .convert_element_type(elem_type.primitive_type())
+    }
+
+    /// The non-negative sample frequencies returned by an [`FftType::Rfft`](crate::FftType::Rfft)
+    /// transform of length `n`, like `numpy.fft.rfftfreq`.
+    #[track_caller]
+    pub fn rfftfreq(&self, n: usize, d: f64, elem_type: ElementType) -> XlaOp {
+        let scale = 1. / (d * n as f64);
+        let freqs = (0..n / 2 + 1).map(|i| i as f64 * scale).collect::<Vec<_>>();
+        self.constant_vector(&freqs)
+            .convert_element_type(elem_type.primitive_type())
+    }
+
+    #[track_caller]
     pub fn call(&self, args: &[XlaOpRef<'_>], comp: &XlaComputation) -> XlaOp {
+        let _site = self.call_site();
         let args_ptr = args.as_ptr();
         let args_len = args.len();
         let raw = unsafe {
-            cpp!([self as "std::shared_ptr<XlaBuilder>*", args_ptr as "const XlaOp*", args_len as "size_t", comp as "const XlaComputation*"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(Call(self->get(), *comp, absl::Span(args_ptr, args_len)));
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", args_ptr as "const XlaOp*", args_len as "size_t", comp as "const RustXlaComputation*"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(Call(self->get(), EmbedRustComputation(self->get(), *comp), absl::Span(args_ptr, args_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: self.clone(),
+        }
+    }
+
+    /// The id of the replica executing the computation, as a `u32` scalar.
+    #[track_caller]
+    pub fn replica_id(&self) -> XlaOp {
+        let _site = self.call_site();
+        let raw = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ReplicaId(self->get()));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: self.clone(),
+        }
+    }
+
+    /// The id of the partition executing the computation, as a `u32` scalar.
+    #[track_caller]
+    pub fn partition_id(&self) -> XlaOp {
+        let _site = self.call_site();
+        let raw = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto shape = ShapeUtil::MakeShape(U32, {});
+                    return XlaOp(internal::XlaBuilderFriend::BuildPartitionId(self->get(), shape));
+                }catch(std::exception& e) {
+                    return XlaOp(self->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
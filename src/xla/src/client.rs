@@ -1,6 +1,6 @@
 use crate::{
-    ArrayElement, CompileOptions, Error, Literal, PjRtBuffer, PjRtLoadedExecutable, Result, Status,
-    XlaComputation,
+    ArrayElement, CompileOptions, Error, Literal, PjRtBuffer, PjRtDevice, PjRtDeviceRaw,
+    PjRtFuture, PjRtLoadedExecutable, RawPjRtFuture, Result, Status, StatusCode, XlaComputation,
 };
 use cpp::{cpp, cpp_class};
 use std::pin::Pin;
@@ -147,6 +147,28 @@ cpp! {{
 
 cpp_class!(pub unsafe struct PjRtClient as "std::shared_ptr<PjRtClient>");
 
+/// Controls how long PjRt may read from a host buffer passed to
+/// [`PjRtClient::copy_host_buffer_async`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HostBufferSemantics {
+    /// The host buffer is copied before the call returns.
+    ImmutableOnlyDuringCall,
+    /// The host buffer is read asynchronously and released once the transfer completes.
+    ImmutableUntilTransferCompletes,
+    /// The device buffer may alias the host buffer for its whole lifetime.
+    ImmutableZeroCopy,
+}
+
+impl HostBufferSemantics {
+    fn as_i32(&self) -> i32 {
+        match self {
+            Self::ImmutableOnlyDuringCall => 0,
+            Self::ImmutableUntilTransferCompletes => 1,
+            Self::ImmutableZeroCopy => 2,
+        }
+    }
+}
+
 fn init_cpu_lapack() {
     cpp! {{
         namespace jax {
@@ -262,11 +284,18 @@ fn init_cpu_lapack() {
 
 impl PjRtClient {
     pub fn cpu() -> Result<Self> {
+        Self::cpu_with_devices(1)
+    }
+
+    /// Creates a CPU client exposing `device_count` virtual devices, which allows replicated and
+    /// sharded execution to be exercised without accelerators.
+    pub fn cpu_with_devices(device_count: usize) -> Result<Self> {
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         init_cpu_lapack();
+        let device_count = device_count as i32;
         let client = unsafe {
-            cpp!([out_status as "Status*"] -> PjRtClient as "std::shared_ptr<PjRtClient>" {
-                auto status = xla::GetTfrtCpuClient(false);
+            cpp!([out_status as "Status*", device_count as "int32_t"] -> PjRtClient as "std::shared_ptr<PjRtClient>" {
+                auto status = xla::GetTfrtCpuClient(false, device_count);
                 if (status.ok()) {
                     return std::shared_ptr(std::move(status.value()));
                 }else{
@@ -279,7 +308,9 @@ impl PjRtClient {
         if client.is_null() {
             let backtrace = std::backtrace::Backtrace::capture().to_string();
             return Err(Error::XlaError {
+                code: StatusCode::Internal,
                 msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
                 backtrace,
             });
         }
@@ -325,14 +356,76 @@ impl PjRtClient {
         if client.is_null() {
             let backtrace = std::backtrace::Backtrace::capture().to_string();
             return Err(Error::XlaError {
+                code: StatusCode::Internal,
                 msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
                 backtrace,
             });
         }
         Ok(client)
     }
 
+    /// The number of devices known to this client, including non-addressable ones.
+    pub fn device_count(&self) -> usize {
+        unsafe {
+            cpp!([self as "std::shared_ptr<PjRtClient>*"] -> usize as "size_t" {
+                return (*self)->device_count();
+            })
+        }
+    }
+
+    /// The number of devices the current process can execute on.
+    pub fn addressable_device_count(&self) -> usize {
+        unsafe {
+            cpp!([self as "std::shared_ptr<PjRtClient>*"] -> usize as "size_t" {
+                return (*self)->addressable_device_count();
+            })
+        }
+    }
+
+    pub fn devices(&self) -> Vec<PjRtDevice> {
+        (0..self.device_count()).map(|i| self.device(i)).collect()
+    }
+
+    pub fn addressable_devices(&self) -> Vec<PjRtDevice> {
+        (0..self.addressable_device_count())
+            .map(|i| {
+                let raw = unsafe {
+                    cpp!([self as "std::shared_ptr<PjRtClient>*", i as "size_t"] -> PjRtDeviceRaw as "PjRtDevice*" {
+                        return (*self)->addressable_devices()[i];
+                    })
+                };
+                PjRtDevice {
+                    raw,
+                    client: self.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn device(&self, index: usize) -> PjRtDevice {
+        let raw = unsafe {
+            cpp!([self as "std::shared_ptr<PjRtClient>*", index as "size_t"] -> PjRtDeviceRaw as "PjRtDevice*" {
+                return (*self)->devices()[index];
+            })
+        };
+        PjRtDevice {
+            raw,
+            client: self.clone(),
+        }
+    }
+
     pub fn copy_host_buffer<T: ArrayElement>(&self, buf: &[T], dims: &[i64]) -> Result<PjRtBuffer> {
+        self.copy_host_buffer_to(buf, dims, &self.device(0))
+    }
+
+    /// Copies `buf` to the given device.
+    pub fn copy_host_buffer_to<T: ArrayElement>(
+        &self,
+        buf: &[T],
+        dims: &[i64],
+        device: &PjRtDevice,
+    ) -> Result<PjRtBuffer> {
         let element_count: usize = dims.iter().product::<i64>() as usize;
         if element_count != buf.len() {
             return Err(Error::WrongElementCount {
@@ -344,16 +437,16 @@ impl PjRtClient {
         let dims_ptr = dims.as_ptr();
         let dims_len = dims.len();
         let prim_type = T::TY.primitive_type() as i32;
+        let device = &device.raw;
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let buffer = unsafe {
-            cpp!([self as "std::shared_ptr<PjRtClient>*", buf_ptr as "const uint8_t*", out_status as "Status*", dims_ptr as "const int64_t*", dims_len as "size_t", prim_type as "int32_t"] -> PjRtBuffer as "std::unique_ptr<PjRtBuffer>" {
+            cpp!([self as "std::shared_ptr<PjRtClient>*", buf_ptr as "const uint8_t*", out_status as "Status*", dims_ptr as "const int64_t*", dims_len as "size_t", prim_type as "int32_t", device as "PjRtDevice**"] -> PjRtBuffer as "std::unique_ptr<PjRtBuffer>" {
                 auto client = *self;
-                auto device = client->devices()[0];
                 auto status = client->BufferFromHostBuffer(
                     buf_ptr,
                     (PrimitiveType)prim_type,
                     absl::Span(dims_ptr, dims_len), {},
-                    PjRtClient::HostBufferSemantics::kImmutableOnlyDuringCall, []() {}, device
+                    PjRtClient::HostBufferSemantics::kImmutableOnlyDuringCall, []() {}, *device
                 );
                 if (status.ok()) {
                     return std::unique_ptr(std::move(status.value()));
@@ -367,13 +460,86 @@ impl PjRtClient {
         if buffer.is_null() {
             let backtrace = std::backtrace::Backtrace::capture().to_string();
             return Err(Error::XlaError {
+                code: StatusCode::Internal,
                 msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
                 backtrace,
             });
         }
         Ok(buffer)
     }
 
+    /// Starts copying `buf` to the device and returns a future that resolves once the
+    /// resulting buffer is ready.
+    ///
+    /// The host data is owned by the transfer and is only dropped once PjRt no longer needs it,
+    /// so any [`HostBufferSemantics`] is safe to use.
+    pub fn copy_host_buffer_async<T: ArrayElement + Send + 'static>(
+        &self,
+        buf: Vec<T>,
+        dims: &[i64],
+        semantics: HostBufferSemantics,
+    ) -> Result<PjRtFuture<PjRtBuffer>> {
+        let element_count: usize = dims.iter().product::<i64>() as usize;
+        if element_count != buf.len() {
+            return Err(Error::WrongElementCount {
+                dims: dims.to_vec(),
+                element_count,
+            });
+        }
+        let buf_ptr = buf.as_ptr() as *const u8;
+        // Released when PjRt runs or drops the `on_done_with_host_buffer` callback below, so it is
+        // also freed when the transfer fails to start.
+        let host_buf: Box<Box<dyn Send>> = Box::new(Box::new(buf));
+        let host_buf = Box::into_raw(host_buf);
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len();
+        let prim_type = T::TY.primitive_type() as i32;
+        let semantics = semantics.as_i32();
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let mut ready = RawPjRtFuture::default();
+        let ready_ptr = &mut ready;
+        let buffer = unsafe {
+            cpp!([self as "std::shared_ptr<PjRtClient>*", buf_ptr as "const uint8_t*", host_buf as "void*", out_status as "Status*", dims_ptr as "const int64_t*", dims_len as "size_t", prim_type as "int32_t", semantics as "int32_t", ready_ptr as "PjRtFuture<>*"] -> PjRtBuffer as "std::unique_ptr<PjRtBuffer>" {
+                auto client = *self;
+                auto device = client->devices()[0];
+                auto drop_host_buf = [](void* host_buf) {
+                    rust!(pjrt_client_drop_host_buffer [host_buf : *mut Box<dyn Send> as "void*"] {
+                        drop(unsafe { Box::from_raw(host_buf) });
+                    });
+                };
+                auto on_done = [guard = std::shared_ptr<void>(host_buf, drop_host_buf)]() mutable {
+                    guard.reset();
+                };
+                auto status = client->BufferFromHostBuffer(
+                    buf_ptr,
+                    (PrimitiveType)prim_type,
+                    absl::Span(dims_ptr, dims_len), {},
+                    (PjRtClient::HostBufferSemantics)semantics, std::move(on_done), device
+                );
+                if (status.ok()) {
+                    auto buffer = std::move(status.value());
+                    *ready_ptr = buffer->GetReadyFuture();
+                    return buffer;
+                }else{
+                    *out_status = Status(status.status());
+                    return std::unique_ptr<PjRtBuffer>();
+                }
+            })
+        };
+        out_status.to_result()?;
+        if buffer.is_null() {
+            let backtrace = std::backtrace::Backtrace::capture().to_string();
+            return Err(Error::XlaError {
+                code: StatusCode::Internal,
+                msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
+                backtrace,
+            });
+        }
+        Ok(PjRtFuture::new(ready, buffer))
+    }
+
     pub fn copy_raw_host_buffer(
         &self,
         ty: super::ElementType,
@@ -415,7 +581,9 @@ impl PjRtClient {
         if buffer.is_null() {
             let backtrace = std::backtrace::Backtrace::capture().to_string();
             return Err(Error::XlaError {
+                code: StatusCode::Internal,
                 msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
                 backtrace,
             });
         }
@@ -423,12 +591,17 @@ impl PjRtClient {
     }
 
     pub fn copy_literal(&self, literal: &Literal) -> Result<PjRtBuffer> {
+        self.copy_literal_to(literal, &self.device(0))
+    }
+
+    /// Copies `literal` to the given device.
+    pub fn copy_literal_to(&self, literal: &Literal, device: &PjRtDevice) -> Result<PjRtBuffer> {
+        let device = &device.raw;
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let buffer = unsafe {
-            cpp!([self as "std::shared_ptr<PjRtClient>*", literal as "const std::shared_ptr<Literal>*", out_status as "Status*"] -> PjRtBuffer as "std::unique_ptr<PjRtBuffer>" {
+            cpp!([self as "std::shared_ptr<PjRtClient>*", literal as "const std::shared_ptr<Literal>*", out_status as "Status*", device as "PjRtDevice**"] -> PjRtBuffer as "std::unique_ptr<PjRtBuffer>" {
                 auto client = *self;
-                auto device = client->devices()[0];
-                auto status = client->BufferFromHostLiteral(*literal->get(), device);
+                auto status = client->BufferFromHostLiteral(*literal->get(), *device);
                 if (status.ok()) {
                     return std::unique_ptr(std::move(status.value()));
                 }else{
@@ -441,7 +614,9 @@ impl PjRtClient {
         if buffer.is_null() {
             let backtrace = std::backtrace::Backtrace::capture().to_string();
             return Err(Error::XlaError {
+                code: StatusCode::Internal,
                 msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
                 backtrace,
             });
         }
@@ -456,13 +631,16 @@ impl PjRtClient {
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let mut options = options.0;
         let exec = unsafe {
-            cpp!([self as "std::shared_ptr<PjRtClient>*", mut options as "CompileOptions", comp as "const XlaComputation*", out_status as "Status*"] -> PjRtLoadedExecutable as "std::shared_ptr<PjRtLoadedExecutable>" {
+            cpp!([self as "std::shared_ptr<PjRtClient>*", mut options as "CompileOptions", comp as "const RustXlaComputation*", out_status as "Status*"] -> PjRtLoadedExecutable as "std::shared_ptr<PjRtLoadedExecutable>" {
                 auto client = *self;
                 auto thread_pool = std::make_unique<tsl::thread::ThreadPool>(tsl::Env::Default(), "", tsl::port::MaxParallelism());
                 options.executable_build_options.set_compile_thread_pool(thread_pool.get());
-                auto status = client->Compile(*comp, options);
+                auto status = client->Compile(comp->computation, options);
                 if (status.ok()) {
-                    return std::shared_ptr(std::move(status.value()));
+                    // The executable shares the ownership of the host callbacks it may call.
+                    using Owner = std::pair<std::shared_ptr<PjRtLoadedExecutable>, RustHostCallbacks>;
+                    auto owner = std::make_shared<Owner>(std::move(status.value()), comp->host_callbacks);
+                    return std::shared_ptr<PjRtLoadedExecutable>(owner, owner->first.get());
                 }else{
                     *out_status = Status(status.status());
                     return std::shared_ptr<PjRtLoadedExecutable>();
@@ -473,7 +651,9 @@ impl PjRtClient {
         if exec.is_null() {
             let backtrace = std::backtrace::Backtrace::capture().to_string();
             return Err(Error::XlaError {
+                code: StatusCode::Internal,
                 msg: "Unexpected null pointer".to_string(),
+                payloads: vec![],
                 backtrace,
             });
         }
@@ -508,9 +688,23 @@ impl PjRtClient {
         }
 
         let len = buffer.shape().size();
+        let src_ptr = self.unsafe_buffer_pointer(buffer)?;
+        let src: &[u8] = unsafe { std::slice::from_raw_parts(src_ptr as *const u8, len) };
+        dst.clear();
+        dst.extend_from_slice(src);
+        Ok(())
+    }
+
+    /// Returns the address of the device memory backing `buffer`, which is only meaningful while
+    /// the buffer is alive. This is mostly useful to check whether two buffers share memory,
+    /// e.g. when an output aliases a donated input.
+    pub fn unsafe_buffer_pointer(&self, buffer: &PjRtBuffer) -> Result<usize> {
+        if buffer.is_deleted() {
+            Err(Error::DonatedBuffer)?
+        }
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
-        let src: &[u8] = unsafe {
-            let src_ptr = cpp!([self as "std::shared_ptr<PjRtClient>*", buffer as "const std::unique_ptr<PjRtBuffer>*", out_status as "Status*"] -> *const u8 as "std::uintptr_t" {
+        let ptr = unsafe {
+            cpp!([self as "std::shared_ptr<PjRtClient>*", buffer as "const std::unique_ptr<PjRtBuffer>*", out_status as "Status*"] -> usize as "std::uintptr_t" {
                 auto status = (*self)->UnsafeBufferPointer(buffer->get());
                 if (status.ok()) {
                     return status.value();
@@ -518,12 +712,9 @@ impl PjRtClient {
                     *out_status = Status(status.status());
                     return 0;
                 }
-            });
-            std::slice::from_raw_parts(src_ptr, len)
+            })
         };
         out_status.to_result()?;
-        dst.clear();
-        dst.extend_from_slice(src);
-        Ok(())
+        Ok(ptr)
     }
 }
@@ -20,16 +20,25 @@ cpp! {{
     #include "xla/mlir_hlo/mhlo/IR/hlo_ops.h"
     #include "xla/mlir_hlo/mhlo/transforms/passes.h"
     #include "xla/translate/hlo_to_mhlo/hlo_to_mlir_hlo.h"
+    #include "xla/hlo/ir/hlo_module.h"
+    #include "xla/service/hlo_graph_dumper.h"
 
     using namespace xla;
 }}
 
-cpp_class!(pub unsafe struct XlaComputation as "XlaComputation");
+cpp_class!(pub unsafe struct XlaComputation as "RustXlaComputation");
 impl XlaComputation {
+    #[track_caller]
     pub fn stmt_while(&self, body: &XlaComputation, init_value: &XlaOp) -> XlaOp {
+        let _site = init_value.builder.call_site();
         let raw = unsafe {
-            cpp!([self as "const XlaComputation*", body as "const XlaComputation*", init_value as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(While(*self, *body, *init_value));
+            cpp!([self as "const RustXlaComputation*", body as "const RustXlaComputation*", init_value as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto builder = init_value->builder();
+                    return XlaOp(While(EmbedRustComputation(builder, *self), EmbedRustComputation(builder, *body), *init_value));
+                }catch(std::exception& e) {
+                    return XlaOp(init_value->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -41,14 +50,14 @@ impl XlaComputation {
     pub fn to_hlo_text(&self) -> Result<String, Error> {
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let cxx_string = unsafe {
-            cpp!([self as "const XlaComputation*", out_status as "Status*"] -> UniquePtr<CxxString> as "std::unique_ptr<std::string>" {
+            cpp!([self as "const RustXlaComputation*", out_status as "Status*"] -> UniquePtr<CxxString> as "std::unique_ptr<std::string>" {
                     CompileOptions options;
                     mlir::MLIRContext context;
                     mlir::OwningOpRef<mlir::ModuleOp> module =
                     mlir::ModuleOp::create(mlir::UnknownLoc::get(&context));
                     context.loadDialect<mlir::func::FuncDialect>();
                     context.loadDialect<mlir::mhlo::MhloDialect>();
-                    auto status = ConvertHloToMlirHlo(*module, &self->proto(), /*import_all_computations=*/true);
+                    auto status = ConvertHloToMlirHlo(*module, &self->computation.proto(), /*import_all_computations=*/true);
                     if(!status.ok()) {
                         *out_status = status;
                         return std::make_unique<std::string>();
@@ -73,10 +82,44 @@ impl XlaComputation {
         Ok(cxx_string.to_string_lossy().into_owned())
     }
 
+    /// Renders the HLO graph of the entry computation as Graphviz DOT text, using XLA's own
+    /// graph dumper.
+    pub fn to_dot(&self) -> Result<String, Error> {
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let cxx_string = unsafe {
+            cpp!([self as "const RustXlaComputation*", out_status as "Status*"] -> UniquePtr<CxxString> as "std::unique_ptr<std::string>" {
+                try {
+                    auto config = HloModule::CreateModuleConfigFromProto(self->computation.proto(), DebugOptions());
+                    if (!config.ok()) {
+                        *out_status = Status(config.status());
+                        return std::make_unique<std::string>();
+                    }
+                    auto module = HloModule::CreateFromProto(self->computation.proto(), config.value());
+                    if (!module.ok()) {
+                        *out_status = Status(module.status());
+                        return std::make_unique<std::string>();
+                    }
+                    auto computation = (*module)->entry_computation();
+                    auto dot = RenderGraph(*computation, computation->name(), (*module)->config().debug_options(), RenderedGraphFormat::kDot);
+                    if (!dot.ok()) {
+                        *out_status = Status(dot.status());
+                        return std::make_unique<std::string>();
+                    }
+                    return std::make_unique<std::string>(std::move(dot.value()));
+                }catch(std::exception& e) {
+                    *out_status = Status(tsl::errors::Internal(e.what()));
+                    return std::make_unique<std::string>();
+                }
+            })
+        };
+        out_status.to_result()?;
+        Ok(cxx_string.to_string_lossy().into_owned())
+    }
+
     pub fn to_hlo_module(&self) -> HloModuleProto {
         unsafe {
-            cpp!([self as "const XlaComputation*"] -> HloModuleProto as "HloModuleProto" {
-                return self->proto();
+            cpp!([self as "const RustXlaComputation*"] -> HloModuleProto as "HloModuleProto" {
+                return self->computation.proto();
             })
         }
     }
@@ -95,4 +138,35 @@ impl CompileOptions {
             })
         };
     }
+
+    /// Sets the number of replicas the computation is compiled for.
+    pub fn set_num_replicas(&mut self, num_replicas: i64) {
+        let raw = &mut self.0;
+        unsafe {
+            cpp!([raw as "CompileOptions*", num_replicas as "int64_t"] {
+                raw->executable_build_options.set_num_replicas(num_replicas);
+            })
+        };
+    }
+
+    /// Sets the number of partitions the computation is compiled for.
+    pub fn set_num_partitions(&mut self, num_partitions: i64) {
+        let raw = &mut self.0;
+        unsafe {
+            cpp!([raw as "CompileOptions*", num_partitions as "int64_t"] {
+                raw->executable_build_options.set_num_partitions(num_partitions);
+            })
+        };
+    }
+
+    /// Let XLA's SPMD partitioner split a single-program computation across partitions based on
+    /// its sharding annotations.
+    pub fn set_use_spmd_partitioning(&mut self, use_spmd_partitioning: bool) {
+        let raw = &mut self.0;
+        unsafe {
+            cpp!([raw as "CompileOptions*", use_spmd_partitioning as "bool"] {
+                raw->executable_build_options.set_use_spmd_partitioning(use_spmd_partitioning);
+            })
+        };
+    }
 }
@@ -0,0 +1,584 @@
+//! Host custom-call targets implemented in Rust.
+//!
+//! Two kinds of targets can be registered:
+//! - plain `extern "C"` functions using XLA's status-returning custom-call ABI, see
+//!   [`register_custom_call_target`],
+//! - closures, which are dispatched through the typed XLA FFI so that they receive the shapes
+//!   of their operands and results, see [`register_custom_call`].
+//!
+//! Both can then be invoked with [`XlaBuilder::custom_call`].
+use crate::{
+    ArrayElement, ArrayShape, ElementType, Error, Literal, PrimitiveType, Result, Shape,
+    XlaBuilder, XlaOp, XlaOpRaw, XlaOpRef,
+};
+
+use cpp::cpp;
+use cxx::{CxxString, let_cxx_string};
+use num_traits::FromPrimitive;
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_void},
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+cpp! {{
+    #include "xla/client/xla_builder.h"
+    #include "xla/ffi/ffi.h"
+    #include "xla/ffi/ffi_api.h"
+    #include "xla/service/custom_call_status.h"
+    #include "xla/service/custom_call_target_registry.h"
+    using namespace xla;
+
+    struct RustCustomCallBuffer {
+        void* data;
+        int32_t ty;
+        const int64_t* dims;
+        size_t rank;
+        size_t size_bytes;
+    };
+
+    // Set the first time a closure is registered, the function body lives in Rust.
+    static bool (*rust_custom_call_dispatch)(
+        std::string_view target, std::string_view opaque,
+        const RustCustomCallBuffer* args, size_t args_len,
+        RustCustomCallBuffer* rets, size_t rets_len, std::string* error) = nullptr;
+
+    static RustCustomCallBuffer ToRustBuffer(ffi::AnyBuffer buffer) {
+        auto dims = buffer.dimensions();
+        return RustCustomCallBuffer {
+            buffer.untyped_data(),
+            static_cast<int32_t>(buffer.element_type()),
+            dims.data(),
+            dims.size(),
+            buffer.size_bytes(),
+        };
+    }
+
+    static absl::Status RustCustomCall(ffi::RemainingArgs args, ffi::RemainingRets rets,
+                                       std::string_view target, std::string_view opaque) {
+        std::vector<RustCustomCallBuffer> rust_args;
+        for (size_t i = 0; i < args.size(); ++i) {
+            auto arg = args.get<ffi::AnyBuffer>(i);
+            if (!arg.ok()) {
+                return arg.status();
+            }
+            rust_args.push_back(ToRustBuffer(*arg));
+        }
+        std::vector<RustCustomCallBuffer> rust_rets;
+        for (size_t i = 0; i < rets.size(); ++i) {
+            auto ret = rets.get<ffi::AnyBuffer>(i);
+            if (!ret.ok()) {
+                return ret.status();
+            }
+            rust_rets.push_back(ToRustBuffer(**ret));
+        }
+        std::string error;
+        if (rust_custom_call_dispatch == nullptr) {
+            return absl::InternalError("no rust custom call has been registered");
+        }
+        if (!rust_custom_call_dispatch(target, opaque, rust_args.data(), rust_args.size(),
+                                       rust_rets.data(), rust_rets.size(), &error)) {
+            return absl::InternalError(error);
+        }
+        return absl::OkStatus();
+    }
+
+    XLA_FFI_DEFINE_HANDLER(kRustCustomCall, RustCustomCall,
+                           ffi::Ffi::Bind()
+                               .RemainingArgs()
+                               .RemainingRets()
+                               .Attr<std::string_view>("rust_target")
+                               .Attr<std::string_view>("opaque"));
+}}
+
+/// The calling convention XLA uses to invoke a custom-call target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CustomCallApiVersion {
+    /// `void(void* out, const void** ins)`.
+    Original,
+    /// `void(void* out, const void** ins, XlaCustomCallStatus* status)`.
+    StatusReturning,
+    /// `void(void* out, const void** ins, const char* opaque, size_t opaque_len,
+    /// XlaCustomCallStatus* status)`.
+    StatusReturningUnified,
+    /// Handlers registered with the typed XLA FFI.
+    TypedFfi,
+}
+
+impl CustomCallApiVersion {
+    fn as_i32(&self) -> i32 {
+        match self {
+            Self::Original => 1,
+            Self::StatusReturning => 2,
+            Self::StatusReturningUnified => 3,
+            Self::TypedFfi => 4,
+        }
+    }
+}
+
+/// A host custom-call target using the [`CustomCallApiVersion::StatusReturningUnified`] ABI.
+///
+/// `out` points to the result buffer, or to an array of buffer pointers for tuple results, and
+/// `ins` to the operand buffers. Failures are reported with [`custom_call_set_failure`].
+pub type CustomCallTarget = unsafe extern "C" fn(
+    out: *mut c_void,
+    ins: *const *const c_void,
+    opaque: *const c_char,
+    opaque_len: usize,
+    status: *mut c_void,
+);
+
+/// Reports a failure from a [`CustomCallTarget`], the execution then returns an error with
+/// message `msg`.
+///
+/// # Safety
+///
+/// `status` must be the status pointer passed to the currently running target.
+pub unsafe fn custom_call_set_failure(status: *mut c_void, msg: &str) {
+    let msg_ptr = msg.as_ptr();
+    let msg_len = msg.len();
+    unsafe {
+        cpp!([status as "XlaCustomCallStatus*", msg_ptr as "const char*", msg_len as "size_t"] {
+            XlaCustomCallStatusSetFailure(status, msg_ptr, msg_len);
+        })
+    }
+}
+
+#[repr(C)]
+struct RawBuffer {
+    data: *mut c_void,
+    ty: i32,
+    dims: *const i64,
+    rank: usize,
+    size_bytes: usize,
+}
+
+/// An operand or result of a custom call, only valid for the duration of the call.
+pub struct CustomCallBuffer<'a> {
+    ty: ElementType,
+    dims: &'a [i64],
+    data: *mut u8,
+    size_bytes: usize,
+    marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> CustomCallBuffer<'a> {
+    fn from_raw(raw: &'a RawBuffer) -> Result<Self> {
+        let ty = PrimitiveType::from_i32(raw.ty)
+            .ok_or(Error::UnexpectedElementType(raw.ty))?
+            .element_type()?;
+        let dims = if raw.rank == 0 {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(raw.dims, raw.rank) }
+        };
+        Ok(Self {
+            ty,
+            dims,
+            data: raw.data as *mut u8,
+            size_bytes: raw.size_bytes,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn ty(&self) -> ElementType {
+        self.ty
+    }
+
+    pub fn dims(&self) -> &[i64] {
+        self.dims
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.dims.iter().map(|d| *d as usize).product()
+    }
+
+    fn check_type<T: ArrayElement>(&self) -> Result<usize> {
+        if self.ty != T::TY {
+            Err(Error::ElementTypeMismatch {
+                on_device: self.ty,
+                on_host: T::TY,
+            })?
+        }
+        if self.size_bytes != self.element_count() * T::ELEMENT_SIZE_IN_BYTES
+            || self.data.align_offset(std::mem::align_of::<T>()) != 0
+        {
+            Err(Error::CastError)?
+        }
+        Ok(self.element_count())
+    }
+
+    /// The elements of the buffer, in row-major order.
+    pub fn data<T: ArrayElement>(&self) -> Result<&[T]> {
+        let len = self.check_type::<T>()?;
+        if len == 0 {
+            return Ok(&[]);
+        }
+        Ok(unsafe { std::slice::from_raw_parts(self.data as *const T, len) })
+    }
+
+    /// Mutable access to the elements of the buffer, used to write results.
+    pub fn data_mut<T: ArrayElement>(&mut self) -> Result<&mut [T]> {
+        let len = self.check_type::<T>()?;
+        if len == 0 {
+            return Ok(&mut []);
+        }
+        Ok(unsafe { std::slice::from_raw_parts_mut(self.data as *mut T, len) })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        if self.size_bytes == 0 {
+            return &[];
+        }
+        unsafe { std::slice::from_raw_parts(self.data, self.size_bytes) }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        if self.size_bytes == 0 {
+            return &mut [];
+        }
+        unsafe { std::slice::from_raw_parts_mut(self.data, self.size_bytes) }
+    }
+
+    fn array_shape(&self) -> ArrayShape {
+        ArrayShape::new_with_type(self.ty, self.dims.to_vec())
+    }
+
+    /// Copies the buffer into a new literal.
+    pub fn to_literal(&self) -> Literal {
+        let mut lit = Literal::create_from_shape(&Shape::Array(self.array_shape()));
+        lit.raw_buf_mut().copy_from_slice(self.bytes());
+        lit
+    }
+
+    /// Overwrites the buffer with the contents of `lit`, which must have the same shape.
+    pub fn copy_from_literal(&mut self, lit: &Literal) -> Result<()> {
+        let expected = Shape::Array(self.array_shape());
+        let actual = lit.shape()?;
+        if actual != expected {
+            Err(Error::ShapeMismatch { actual, expected })?
+        }
+        self.bytes_mut().copy_from_slice(lit.raw_buf());
+        Ok(())
+    }
+}
+
+type CustomCallFn =
+    dyn Fn(&[CustomCallBuffer<'_>], &mut [CustomCallBuffer<'_>], &str) -> Result<()> + Send + Sync;
+
+enum RegisteredTarget {
+    Function,
+    Closure(Arc<CustomCallFn>),
+}
+
+fn registry() -> &'static Mutex<HashMap<String, RegisteredTarget>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, RegisteredTarget>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn insert_target(name: &str, target: RegisteredTarget) -> Result<()> {
+    let mut registry = registry().lock().unwrap();
+    if registry.contains_key(name) {
+        Err(Error::CustomCallAlreadyRegistered {
+            name: name.to_string(),
+        })?
+    }
+    registry.insert(name.to_string(), target);
+    Ok(())
+}
+
+/// Registers `f` as the host custom-call target `name`.
+///
+/// # Safety
+///
+/// XLA calls `f` with buffers matching the operand and result shapes given to
+/// [`XlaBuilder::custom_call`], it is up to `f` to access them accordingly.
+pub unsafe fn register_custom_call_target(name: &str, f: CustomCallTarget) -> Result<()> {
+    insert_target(name, RegisteredTarget::Function)?;
+    let f = f as *const c_void;
+    let_cxx_string!(name = name);
+    unsafe {
+        cpp!([name as "std::string*", f as "void*"] {
+            CustomCallTargetRegistry::Global()->Register(*name, f, "Host");
+        })
+    }
+    Ok(())
+}
+
+fn dispatch(target: &str, opaque: &str, args: &[RawBuffer], rets: &mut [RawBuffer]) -> Result<()> {
+    let f = match registry().lock().unwrap().get(target) {
+        Some(RegisteredTarget::Closure(f)) => f.clone(),
+        _ => Err(Error::UnknownCustomCall {
+            name: target.to_string(),
+        })?,
+    };
+    let args = args
+        .iter()
+        .map(CustomCallBuffer::from_raw)
+        .collect::<Result<Vec<_>>>()?;
+    let mut rets = rets
+        .iter()
+        .map(CustomCallBuffer::from_raw)
+        .collect::<Result<Vec<_>>>()?;
+    f(&args, &mut rets, opaque)
+}
+
+/// Registers a closure as the host custom-call target `name`.
+///
+/// The closure receives the operand buffers, the result buffers to fill and the opaque string
+/// passed to [`XlaBuilder::custom_call`]. Returning an error makes the execution fail.
+pub fn register_custom_call<F>(name: &str, f: F) -> Result<()>
+where
+    F: Fn(&[CustomCallBuffer<'_>], &mut [CustomCallBuffer<'_>], &str) -> Result<()>
+        + Send
+        + Sync
+        + 'static,
+{
+    insert_target(name, RegisteredTarget::Closure(Arc::new(f)))?;
+    let_cxx_string!(name = name);
+    let status: Pin<&mut crate::Status> = std::pin::pin!(crate::Status::ok());
+    unsafe {
+        cpp!([name as "std::string*", status as "Status*"] {
+            rust_custom_call_dispatch = [](std::string_view target, std::string_view opaque,
+                                           const RustCustomCallBuffer* args, size_t args_len,
+                                           RustCustomCallBuffer* rets, size_t rets_len,
+                                           std::string* error) -> bool {
+                const char* target_ptr = target.data();
+                size_t target_len = target.size();
+                const char* opaque_ptr = opaque.data();
+                size_t opaque_len = opaque.size();
+                return rust!(rust_custom_call_dispatch_impl [target_ptr : *const u8 as "const char*", target_len : usize as "size_t", opaque_ptr : *const u8 as "const char*", opaque_len : usize as "size_t", args : *const RawBuffer as "const RustCustomCallBuffer*", args_len : usize as "size_t", rets : *mut RawBuffer as "RustCustomCallBuffer*", rets_len : usize as "size_t", error : Pin<&mut CxxString> as "std::string*"] -> bool as "bool" {
+                    let str_from_raw = |ptr: *const u8, len: usize| {
+                        if len == 0 {
+                            return "";
+                        }
+                        let bytes = unsafe { std::slice::from_raw_parts(ptr, len) };
+                        std::str::from_utf8(bytes).unwrap_or_default()
+                    };
+                    let args: &[RawBuffer] = if args_len == 0 {
+                        &[]
+                    } else {
+                        unsafe { std::slice::from_raw_parts(args, args_len) }
+                    };
+                    let rets: &mut [RawBuffer] = if rets_len == 0 {
+                        &mut []
+                    } else {
+                        unsafe { std::slice::from_raw_parts_mut(rets, rets_len) }
+                    };
+                    let target = str_from_raw(target_ptr, target_len);
+                    let opaque = str_from_raw(opaque_ptr, opaque_len);
+                    // Panics must not unwind into XLA.
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        dispatch(target, opaque, args, rets)
+                    }));
+                    let msg = match result {
+                        Ok(Ok(())) => return true,
+                        Ok(Err(err)) => err.to_string(),
+                        Err(_) => format!("custom call {target} panicked"),
+                    };
+                    error.push_str(&msg);
+                    false
+                });
+            };
+            *status = ffi::Ffi::RegisterStaticHandler(ffi::GetXlaFfiApi(), *name, "Host", kRustCustomCall);
+        })
+    }
+    status.to_result()
+}
+
+/// Quotes `s` as an MLIR string attribute.
+fn mlir_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7e => out.push(b as char),
+            _ => out.push_str(&format!("\\{b:02X}")),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl XlaBuilder {
+    /// Calls the custom-call target `target` on `operands`, producing a value of shape
+    /// `result_shape`.
+    ///
+    /// Targets registered through this crate are called with the matching ABI, other targets,
+    /// e.g. ones registered from C++, use [`CustomCallApiVersion::Original`] unless
+    /// [`XlaBuilder::custom_call_with_api_version`] is used.
+    #[track_caller]
+    pub fn custom_call(
+        &self,
+        target: &str,
+        operands: &[XlaOpRef<'_>],
+        result_shape: &Shape,
+        opaque: &str,
+    ) -> XlaOp {
+        let api_version = match registry().lock().unwrap().get(target) {
+            Some(RegisteredTarget::Function) => CustomCallApiVersion::StatusReturningUnified,
+            Some(RegisteredTarget::Closure(_)) => CustomCallApiVersion::TypedFfi,
+            None => CustomCallApiVersion::Original,
+        };
+        self.custom_call_with_api_version(target, operands, result_shape, opaque, api_version)
+    }
+
+    /// Same as [`XlaBuilder::custom_call`] with an explicit calling convention.
+    #[track_caller]
+    pub fn custom_call_with_api_version(
+        &self,
+        target: &str,
+        operands: &[XlaOpRef<'_>],
+        result_shape: &Shape,
+        opaque: &str,
+        api_version: CustomCallApiVersion,
+    ) -> XlaOp {
+        self.custom_call_impl(target, operands, result_shape, opaque, api_version, false)
+    }
+
+    #[track_caller]
+    pub(crate) fn custom_call_impl(
+        &self,
+        target: &str,
+        operands: &[XlaOpRef<'_>],
+        result_shape: &Shape,
+        opaque: &str,
+        api_version: CustomCallApiVersion,
+        has_side_effect: bool,
+    ) -> XlaOp {
+        let _site = self.call_site();
+        // Closures are all served by the same FFI handler, which finds them by name.
+        let backend_config = match api_version {
+            CustomCallApiVersion::TypedFfi if registry().lock().unwrap().contains_key(target) => {
+                format!(
+                    "{{rust_target = {}, opaque = {}}}",
+                    mlir_string(target),
+                    mlir_string(opaque)
+                )
+            }
+            _ => opaque.to_string(),
+        };
+        let_cxx_string!(target = target);
+        let_cxx_string!(backend_config = backend_config);
+        let operands_ptr = operands.as_ptr();
+        let operands_len = operands.len();
+        let shape = result_shape.raw_shape();
+        let api_version = api_version.as_i32();
+        let raw = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", target as "std::string*", operands_ptr as "const XlaOp*", operands_len as "size_t", shape as "const Shape*", backend_config as "std::string*", api_version as "int32_t", has_side_effect as "bool"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(CustomCall(self->get(), *target, absl::Span(operands_ptr, operands_len), *shape, *backend_config, has_side_effect, {}, nullptr, CustomCallSchedule::SCHEDULE_NONE, (CustomCallApiVersion)api_version));
+                }catch(std::exception& e) {
+                    return XlaOp((*self)->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: self.clone(),
+        }
+    }
+}
+
+type HostCallbackFn = dyn Fn(&[Literal]) -> Vec<Literal> + Send + Sync;
+
+/// The custom-call target serving every host callback, which finds the closure to run from the
+/// id passed as its opaque string.
+const HOST_CALLBACK_TARGET: &str = "xla_rs_host_callback";
+
+fn host_callbacks() -> &'static Mutex<HashMap<u64, Arc<HostCallbackFn>>> {
+    static HOST_CALLBACKS: OnceLock<Mutex<HashMap<u64, Arc<HostCallbackFn>>>> = OnceLock::new();
+    HOST_CALLBACKS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Called once the last builder, computation or executable that may run the callback `id` is
+/// dropped.
+extern "C" fn release_host_callback(id: u64) {
+    // The closure is dropped after releasing the lock, as it may own executables calling back
+    // into this function.
+    let callback = host_callbacks().lock().unwrap().remove(&id);
+    drop(callback)
+}
+
+fn register_host_callback_target() -> Result<()> {
+    static REGISTERED: Mutex<bool> = Mutex::new(false);
+    let mut registered = REGISTERED.lock().unwrap();
+    if *registered {
+        return Ok(());
+    }
+    register_custom_call(HOST_CALLBACK_TARGET, |args, rets, opaque| {
+        let callback = opaque
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| host_callbacks().lock().unwrap().get(&id).cloned())
+            .ok_or_else(|| Error::UnknownCustomCall {
+                name: format!("{HOST_CALLBACK_TARGET}[{opaque}]"),
+            })?;
+        let args = args.iter().map(|a| a.to_literal()).collect::<Vec<_>>();
+        let results = callback(&args);
+        if results.len() != rets.len() {
+            Err(Error::UnexpectedNumberOfElemsInTuple {
+                expected: rets.len(),
+                got: results.len(),
+            })?
+        }
+        for (ret, result) in rets.iter_mut().zip(results.iter()) {
+            ret.copy_from_literal(result)?
+        }
+        Ok(())
+    })?;
+    *registered = true;
+    Ok(())
+}
+
+impl XlaBuilder {
+    /// Calls back into `f` on the host when the computation runs, similar to
+    /// `jax.pure_callback`/`jax.experimental.io_callback`.
+    ///
+    /// `f` receives one literal per operand and must return one literal per element of
+    /// `result_shape`, or a single one if it is an array. The call is marked as having side
+    /// effects so that it is neither removed nor deduplicated, which makes it usable for
+    /// logging, e.g. with an empty tuple as `result_shape`.
+    ///
+    /// `f` is kept alive by this builder, then by the computations built from it or embedding
+    /// them and by the executables compiled from these, and is dropped along with the last of
+    /// them.
+    #[track_caller]
+    pub fn host_callback<F>(
+        &self,
+        operands: &[XlaOpRef<'_>],
+        result_shape: &Shape,
+        f: F,
+    ) -> Result<XlaOp>
+    where
+        F: Fn(&[Literal]) -> Vec<Literal> + Send + Sync + 'static,
+    {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        register_host_callback_target()?;
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        host_callbacks().lock().unwrap().insert(id, Arc::new(f));
+        let release = release_host_callback as extern "C" fn(u64);
+        unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", id as "uint64_t", release as "void (*)(uint64_t)"] {
+                HostCallbacks(self->get()).push_back(std::make_shared<RustHostCallback>(id, release));
+            })
+        };
+        Ok(self.custom_call_impl(
+            HOST_CALLBACK_TARGET,
+            operands,
+            result_shape,
+            &id.to_string(),
+            CustomCallApiVersion::TypedFfi,
+            true,
+        ))
+    }
+}
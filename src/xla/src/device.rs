@@ -0,0 +1,76 @@
+use crate::PjRtClient;
+
+use cpp::{cpp, cpp_class};
+use cxx::{CxxString, UniquePtr};
+
+cpp! {{
+    #include "xla/pjrt/pjrt_client.h"
+    using namespace xla;
+}}
+
+cpp_class!(pub unsafe struct PjRtDeviceRaw as "PjRtDevice*");
+
+/// A device attached to a [`PjRtClient`].
+///
+/// Devices are owned by their client, so each handle keeps the client alive.
+#[derive(Clone)]
+pub struct PjRtDevice {
+    pub(crate) raw: PjRtDeviceRaw,
+    pub(crate) client: PjRtClient,
+}
+
+impl PjRtDevice {
+    /// The globally unique id of this device.
+    pub fn id(&self) -> i32 {
+        let raw = &self.raw;
+        unsafe {
+            cpp!([raw as "PjRtDevice**"] -> i32 as "int32_t" {
+                return (*raw)->id();
+            })
+        }
+    }
+
+    /// The index of the process this device belongs to.
+    pub fn process_index(&self) -> i32 {
+        let raw = &self.raw;
+        unsafe {
+            cpp!([raw as "PjRtDevice**"] -> i32 as "int32_t" {
+                return (*raw)->process_index();
+            })
+        }
+    }
+
+    /// Whether this device can be used by the current process.
+    pub fn is_addressable(&self) -> bool {
+        let raw = &self.raw;
+        unsafe {
+            cpp!([raw as "PjRtDevice**"] -> bool as "bool" {
+                return (*raw)->IsAddressable();
+            })
+        }
+    }
+
+    /// A vendor-dependent string describing the kind of device, e.g. "cpu".
+    pub fn kind(&self) -> String {
+        let raw = &self.raw;
+        let kind = unsafe {
+            cpp!([raw as "PjRtDevice**"] -> UniquePtr<CxxString> as "std::unique_ptr<std::string>" {
+                return std::make_unique<std::string>(std::string((*raw)->device_kind()));
+            })
+        };
+        kind.to_string_lossy().into_owned()
+    }
+
+    pub fn client(&self) -> &PjRtClient {
+        &self.client
+    }
+}
+
+impl std::fmt::Debug for PjRtDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PjRtDevice")
+            .field("id", &self.id())
+            .field("kind", &self.kind())
+            .finish()
+    }
+}
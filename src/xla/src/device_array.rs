@@ -0,0 +1,136 @@
+use crate::{
+    ArrayElement, ArrayShape, BufferArgsRef, ElementType, Error, Literal, PjRtBuffer, PjRtClient,
+    PjRtDevice, Result,
+};
+
+/// An array stored on a device, along with its shape and the client owning it.
+///
+/// Nothing is copied back to the host until explicitly requested with [`DeviceArray::to_vec`]
+/// or [`DeviceArray::to_literal`], so the outputs of an executable can be fed to another one
+/// without leaving the device.
+pub struct DeviceArray {
+    buffer: PjRtBuffer,
+    shape: ArrayShape,
+    client: PjRtClient,
+}
+
+impl DeviceArray {
+    /// Wraps a buffer of `client`, returning an error if it holds a tuple.
+    pub fn new(client: &PjRtClient, buffer: PjRtBuffer) -> Result<Self> {
+        let shape = match buffer.shape().shape()? {
+            crate::Shape::Array(shape) => shape,
+            got => Err(Error::NotAnArray {
+                expected: None,
+                got,
+            })?,
+        };
+        Ok(Self {
+            buffer,
+            shape,
+            client: client.clone(),
+        })
+    }
+
+    /// Copies `data`, an array with the given dimensions, to the first device of `client`.
+    pub fn from_host<T: ArrayElement>(
+        client: &PjRtClient,
+        data: &[T],
+        dims: &[i64],
+    ) -> Result<Self> {
+        Self::new(client, client.copy_host_buffer(data, dims)?)
+    }
+
+    /// Copies `literal` to the first device of `client`.
+    pub fn from_literal(client: &PjRtClient, literal: &Literal) -> Result<Self> {
+        Self::new(client, client.copy_literal(literal)?)
+    }
+
+    pub fn shape(&self) -> &ArrayShape {
+        &self.shape
+    }
+
+    pub fn dims(&self) -> &[i64] {
+        self.shape.dims()
+    }
+
+    pub fn element_type(&self) -> ElementType {
+        self.shape.ty()
+    }
+
+    pub fn element_count(&self) -> usize {
+        self.shape.element_count()
+    }
+
+    pub fn client(&self) -> &PjRtClient {
+        &self.client
+    }
+
+    pub fn buffer(&self) -> &PjRtBuffer {
+        &self.buffer
+    }
+
+    pub fn into_buffer(self) -> PjRtBuffer {
+        self.buffer
+    }
+
+    /// Copies the array to a host literal, waiting for it to be computed.
+    pub fn to_literal(&self) -> Result<Literal> {
+        self.buffer.to_literal_sync()
+    }
+
+    /// Copies the elements of the array to the host, waiting for it to be computed.
+    pub fn to_vec<T: ArrayElement>(&self) -> Result<Vec<T>> {
+        if self.element_type() != T::TY {
+            Err(Error::ElementTypeMismatch {
+                on_device: self.element_type(),
+                on_host: T::TY,
+            })?
+        }
+        self.block_until_ready()?;
+        // Reshaping to the same dimensions converts the literal to the row-major layout in
+        // case the device uses another one.
+        let literal = self.buffer.to_literal_sync()?.reshape(self.dims())?;
+        Ok(literal.typed_buf::<T>()?.to_vec())
+    }
+
+    /// Blocks until the array has been computed.
+    pub fn block_until_ready(&self) -> Result<()> {
+        self.buffer.block_until_ready()
+    }
+
+    /// Copies the array to `device`, which may belong to another client, without going through
+    /// the host.
+    pub fn copy_to_device(&self, device: &PjRtDevice) -> Result<DeviceArray> {
+        Ok(Self {
+            buffer: self.buffer.copy_to_device(device)?,
+            shape: self.shape.clone(),
+            client: device.client().clone(),
+        })
+    }
+}
+
+impl AsRef<PjRtBuffer> for DeviceArray {
+    fn as_ref(&self) -> &PjRtBuffer {
+        &self.buffer
+    }
+}
+
+impl std::fmt::Debug for DeviceArray {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceArray")
+            .field("shape", &self.shape)
+            .finish()
+    }
+}
+
+impl<'a> FromIterator<&'a DeviceArray> for BufferArgsRef<'a> {
+    fn from_iter<I: IntoIterator<Item = &'a DeviceArray>>(iter: I) -> Self {
+        iter.into_iter().map(|array| &array.buffer).collect()
+    }
+}
+
+impl<'a, const N: usize> From<[&'a DeviceArray; N]> for BufferArgsRef<'a> {
+    fn from(arrays: [&'a DeviceArray; N]) -> Self {
+        arrays.into_iter().collect()
+    }
+}
@@ -0,0 +1,356 @@
+//! NumPy-like pretty printing for literals and buffers.
+use crate::{ArrayElement, ElementType, Literal, PjRtBuffer, Result, Shape};
+use half::{bf16, f16};
+use num_complex::Complex;
+use std::fmt::{self, Write};
+
+/// Options controlling how [`Literal`] values are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintOptions {
+    /// Number of digits printed after the decimal point for floating point values.
+    pub precision: usize,
+    /// Arrays with more elements than this are summarised with `...`.
+    pub threshold: usize,
+    /// Number of items printed at the beginning and end of each summarised dimension.
+    pub edge_items: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            precision: 4,
+            threshold: 1000,
+            edge_items: 3,
+        }
+    }
+}
+
+/// The short name XLA uses for an element type in shapes, e.g. `f32[2,3]`.
+fn dtype_name(ty: ElementType) -> &'static str {
+    match ty {
+        ElementType::Pred => "pred",
+        ElementType::S8 => "s8",
+        ElementType::S16 => "s16",
+        ElementType::S32 => "s32",
+        ElementType::S64 => "s64",
+        ElementType::U8 => "u8",
+        ElementType::U16 => "u16",
+        ElementType::U32 => "u32",
+        ElementType::U64 => "u64",
+        ElementType::F16 => "f16",
+        ElementType::F32 => "f32",
+        ElementType::Bf16 => "bf16",
+        ElementType::F64 => "f64",
+        ElementType::C64 => "c64",
+        ElementType::C128 => "c128",
+    }
+}
+
+/// The element type whose short name is `name`, the inverse of [`dtype_name`].
+fn element_type_from_name(name: &str) -> Option<ElementType> {
+    use ElementType::*;
+    [
+        Pred, S8, S16, S32, S64, U8, U16, U32, U64, F16, F32, Bf16, F64, C64, C128,
+    ]
+    .into_iter()
+    .find(|ty| dtype_name(*ty) == name)
+}
+
+/// Parses a shape printed by XLA at the start of `text`, e.g. `f32[2,3]{1,0}` or
+/// `(f32[2], s32[])`, returning it along with the rest of `text`. Layouts are skipped and
+/// bounded dynamic dimensions such as `<=3` are read as their bound.
+pub(crate) fn parse_shape(text: &str) -> Option<(Shape, &str)> {
+    if let Some(mut rest) = text.strip_prefix('(') {
+        let mut shapes = vec![];
+        loop {
+            rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix(')') {
+                return Some((Shape::Tuple(shapes), rest));
+            }
+            if !shapes.is_empty() {
+                rest = rest.strip_prefix(',')?.trim_start();
+            }
+            let (shape, tail) = parse_shape(rest)?;
+            shapes.push(shape);
+            rest = tail;
+        }
+    }
+    let (name, rest) = text.split_once('[')?;
+    let ty = element_type_from_name(name)?;
+    let (dims, mut rest) = rest.split_once(']')?;
+    let dims = if dims.is_empty() {
+        vec![]
+    } else {
+        dims.split(',')
+            .map(|d| d.trim().trim_start_matches("<=").parse::<i64>().ok())
+            .collect::<Option<Vec<_>>>()?
+    };
+    if rest.starts_with('{') {
+        rest = &rest[rest.find('}')? + 1..];
+    }
+    Some((Shape::array_with_type(ty, dims), rest))
+}
+
+fn write_shape(out: &mut String, shape: &Shape) {
+    match shape {
+        Shape::Array(a) => {
+            let dims = a.dims().iter().map(|d| d.to_string()).collect::<Vec<_>>();
+            let _ = write!(out, "{}[{}]", dtype_name(a.ty()), dims.join(","));
+        }
+        Shape::Tuple(shapes) => {
+            out.push('(');
+            for (i, shape) in shapes.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_shape(out, shape);
+            }
+            out.push(')');
+        }
+    }
+}
+
+fn format_float(v: f64, precision: usize) -> String {
+    if v.is_finite() {
+        format!("{v:.precision$}")
+    } else {
+        v.to_string()
+    }
+}
+
+fn format_complex(re: f64, im: f64, precision: usize) -> String {
+    let sign = if im.is_sign_negative() { '-' } else { '+' };
+    format!(
+        "{}{sign}{}j",
+        format_float(re, precision),
+        format_float(im.abs(), precision)
+    )
+}
+
+/// The indices shown along a dimension of size `size`, `None` standing for the elided part.
+fn shown_indices(size: usize, summarise: bool, edge_items: usize) -> Vec<Option<usize>> {
+    if summarise && size > 2 * edge_items {
+        (0..edge_items)
+            .map(Some)
+            .chain(std::iter::once(None))
+            .chain((size - edge_items..size).map(Some))
+            .collect()
+    } else {
+        (0..size).map(Some).collect()
+    }
+}
+
+struct ArrayPrinter<'a> {
+    shown: Vec<Vec<Option<usize>>>,
+    strides: Vec<usize>,
+    cell: &'a dyn Fn(usize) -> String,
+}
+
+impl ArrayPrinter<'_> {
+    /// Formats the shown elements in row-major order.
+    fn cells(&self, dim: usize, offset: usize, out: &mut Vec<String>) {
+        if dim == self.shown.len() {
+            out.push((self.cell)(offset));
+            return;
+        }
+        for index in self.shown[dim].iter().flatten() {
+            self.cells(dim + 1, offset + index * self.strides[dim], out)
+        }
+    }
+
+    fn write(
+        &self,
+        out: &mut String,
+        dim: usize,
+        indent: usize,
+        cells: &mut impl Iterator<Item = String>,
+        width: usize,
+    ) {
+        let rank = self.shown.len();
+        if dim == rank {
+            let _ = write!(out, "{:>width$}", cells.next().unwrap_or_default());
+            return;
+        }
+        out.push('[');
+        for (i, index) in self.shown[dim].iter().enumerate() {
+            if i > 0 {
+                if dim + 1 == rank {
+                    out.push_str(", ");
+                } else {
+                    out.push(',');
+                    out.push_str(&"\n".repeat(rank - dim - 1));
+                    out.push_str(&" ".repeat(indent + dim + 1));
+                }
+            }
+            match index {
+                None => out.push_str("..."),
+                Some(_) => self.write(out, dim + 1, indent, cells, width),
+            }
+        }
+        out.push(']');
+    }
+}
+
+fn write_array<T: ArrayElement>(
+    out: &mut String,
+    lit: &Literal,
+    dims: &[usize],
+    indent: usize,
+    options: &PrintOptions,
+    format: impl Fn(T) -> String,
+) -> Result<()> {
+    // The strides below index the elements in row-major order.
+    let lit = lit.to_row_major()?;
+    let buf = lit.typed_buf::<T>()?;
+    let summarise = buf.len() > options.threshold;
+    let mut strides = vec![1; dims.len()];
+    for i in (0..dims.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * dims[i + 1];
+    }
+    let cell = |offset: usize| format(buf[offset]);
+    let printer = ArrayPrinter {
+        shown: dims
+            .iter()
+            .map(|d| shown_indices(*d, summarise, options.edge_items))
+            .collect(),
+        strides,
+        cell: &cell,
+    };
+    let mut cells = vec![];
+    printer.cells(0, 0, &mut cells);
+    let width = cells.iter().map(|c| c.chars().count()).max().unwrap_or(0);
+    printer.write(out, 0, indent, &mut cells.into_iter(), width);
+    Ok(())
+}
+
+fn write_literal(
+    out: &mut String,
+    lit: &Literal,
+    indent: usize,
+    options: &PrintOptions,
+) -> Result<()> {
+    let shape = lit.shape()?;
+    write_shape(out, &shape);
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+    let array_shape = match &shape {
+        Shape::Array(a) => a,
+        Shape::Tuple(shapes) => {
+            out.push('(');
+            for i in 0..shapes.len() {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent + 2));
+                write_literal(out, &lit.tuple_element(i)?, indent + 2, options)?;
+                out.push(',');
+            }
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+            out.push(')');
+            return Ok(());
+        }
+    };
+    let dims = array_shape
+        .dims()
+        .iter()
+        .map(|d| *d as usize)
+        .collect::<Vec<_>>();
+    let p = options.precision;
+    match array_shape.ty() {
+        ElementType::Pred => write_array(out, lit, &dims, indent, options, |v: bool| v.to_string()),
+        ElementType::S8 => write_array(out, lit, &dims, indent, options, |v: i8| v.to_string()),
+        ElementType::S16 => write_array(out, lit, &dims, indent, options, |v: i16| v.to_string()),
+        ElementType::S32 => write_array(out, lit, &dims, indent, options, |v: i32| v.to_string()),
+        ElementType::S64 => write_array(out, lit, &dims, indent, options, |v: i64| v.to_string()),
+        ElementType::U8 => write_array(out, lit, &dims, indent, options, |v: u8| v.to_string()),
+        ElementType::U16 => write_array(out, lit, &dims, indent, options, |v: u16| v.to_string()),
+        ElementType::U32 => write_array(out, lit, &dims, indent, options, |v: u32| v.to_string()),
+        ElementType::U64 => write_array(out, lit, &dims, indent, options, |v: u64| v.to_string()),
+        ElementType::F16 => write_array(out, lit, &dims, indent, options, |v: f16| {
+            format_float(v.to_f64(), p)
+        }),
+        ElementType::Bf16 => write_array(out, lit, &dims, indent, options, |v: bf16| {
+            format_float(v.to_f64(), p)
+        }),
+        ElementType::F32 => write_array(out, lit, &dims, indent, options, |v: f32| {
+            format_float(v as f64, p)
+        }),
+        ElementType::F64 => write_array(out, lit, &dims, indent, options, |v: f64| {
+            format_float(v, p)
+        }),
+        ElementType::C64 => write_array(out, lit, &dims, indent, options, |v: Complex<f32>| {
+            format_complex(v.re as f64, v.im as f64, p)
+        }),
+        ElementType::C128 => write_array(out, lit, &dims, indent, options, |v: Complex<f64>| {
+            format_complex(v.re, v.im, p)
+        }),
+    }
+}
+
+/// Helper returned by [`Literal::display`] to print a literal with custom [`PrintOptions`].
+pub struct LiteralDisplay<'a> {
+    literal: &'a Literal,
+    options: PrintOptions,
+}
+
+impl fmt::Display for LiteralDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options = self.options;
+        if let Some(precision) = f.precision() {
+            options.precision = precision;
+        }
+        let mut out = String::new();
+        match write_literal(&mut out, self.literal, 0, &options) {
+            Ok(()) => f.write_str(&out),
+            Err(err) => write!(f, "<invalid literal: {err}>"),
+        }
+    }
+}
+
+impl Literal {
+    /// Returns a value printing this literal with the given options.
+    pub fn display(&self, options: PrintOptions) -> LiteralDisplay<'_> {
+        LiteralDisplay {
+            literal: self,
+            options,
+        }
+    }
+}
+
+/// Prints the shape followed by the values, e.g.
+///
+/// ```text
+/// f32[2,3]
+/// [[1.0000, 2.0000, 3.0000],
+///  [4.0000, 5.0000, 6.0000]]
+/// ```
+///
+/// The formatter precision, as in `{:.2}`, overrides the default number of decimals.
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.display(PrintOptions::default()), f)
+    }
+}
+
+impl fmt::Debug for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Literal({self})")
+    }
+}
+
+impl fmt::Display for PjRtBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_deleted() {
+            return f.write_str("<deleted buffer>");
+        }
+        match self.to_literal_sync() {
+            Ok(lit) => fmt::Display::fmt(&lit, f),
+            Err(err) => write!(f, "<buffer transfer failed: {err}>"),
+        }
+    }
+}
+
+impl fmt::Debug for PjRtBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PjRtBuffer({self})")
+    }
+}
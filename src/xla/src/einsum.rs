@@ -0,0 +1,403 @@
+//! Einstein summation, parsed in Rust and lowered to `dot_general`, `reduce`, `transpose`,
+//! `reshape` and `select`.
+use crate::{
+    DotDimensionNumbers, ElementType, Error, Result, Shape, XlaBuilder, XlaComputation, XlaOp,
+};
+use std::collections::{BTreeSet, HashMap};
+
+/// A dimension label: either a letter, or the `k`-th dimension covered by `...` once all
+/// ellipses are right-aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Label {
+    Ellipsis(usize),
+    Char(char),
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ellipsis(k) => write!(f, "...[{k}]"),
+            Self::Char(c) => write!(f, "'{c}'"),
+        }
+    }
+}
+
+/// The subscripts of one operand or of the output, before ellipses are resolved.
+struct Term {
+    chars: Vec<char>,
+    /// Position of `...` in `chars`, if any.
+    ellipsis: Option<usize>,
+}
+
+struct Spec {
+    inputs: Vec<Term>,
+    output: Option<Term>,
+}
+
+fn invalid(spec: &str, msg: impl Into<String>) -> Error {
+    Error::InvalidEinsum {
+        spec: spec.to_string(),
+        msg: msg.into(),
+    }
+}
+
+fn parse_term(spec: &str, term: &str) -> Result<Term> {
+    let mut chars = vec![];
+    let mut ellipsis = None;
+    let mut rest = term.trim();
+    while let Some(c) = rest.chars().next() {
+        if let Some(tail) = rest.strip_prefix("...") {
+            if ellipsis.is_some() {
+                Err(invalid(spec, format!("more than one ellipsis in {term:?}")))?
+            }
+            ellipsis = Some(chars.len());
+            rest = tail;
+            continue;
+        }
+        if !c.is_ascii_alphabetic() {
+            Err(invalid(
+                spec,
+                format!("unexpected character {c:?} in {term:?}"),
+            ))?
+        }
+        chars.push(c);
+        rest = &rest[1..];
+    }
+    Ok(Term { chars, ellipsis })
+}
+
+fn parse(spec: &str) -> Result<Spec> {
+    let (inputs, output) = match spec.split_once("->") {
+        Some((inputs, output)) => (inputs, Some(parse_term(spec, output)?)),
+        None => (spec, None),
+    };
+    let inputs = inputs
+        .split(',')
+        .map(|term| parse_term(spec, term))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Spec { inputs, output })
+}
+
+/// Replaces the ellipsis of `term` by `ellipsis_rank` labels, aligned to the right of the
+/// `total_ellipsis_rank` labels used across operands.
+fn resolve(term: &Term, ellipsis_rank: usize, total_ellipsis_rank: usize) -> Vec<Label> {
+    let mut labels = term
+        .chars
+        .iter()
+        .map(|c| Label::Char(*c))
+        .collect::<Vec<_>>();
+    if let Some(pos) = term.ellipsis {
+        let first = total_ellipsis_rank - ellipsis_rank;
+        labels.splice(pos..pos, (first..total_ellipsis_rank).map(Label::Ellipsis));
+    }
+    labels
+}
+
+/// An intermediate value along with the label of each of its dimensions.
+struct Operand {
+    op: XlaOp,
+    labels: Vec<Label>,
+}
+
+fn sum_computation(ty: ElementType) -> Result<XlaComputation> {
+    let builder = XlaBuilder::new("einsum_sum");
+    let x = builder.parameter(0, Shape::array_with_type(ty, vec![]), "x")?;
+    let y = builder.parameter(1, Shape::array_with_type(ty, vec![]), "y")?;
+    x.add(&y).build()
+}
+
+struct Lowering<'a> {
+    sizes: HashMap<Label, i64>,
+    output: &'a [Label],
+    ty: ElementType,
+}
+
+impl Lowering<'_> {
+    fn dims(&self, labels: &[Label]) -> Vec<i64> {
+        labels.iter().map(|l| self.sizes[l]).collect()
+    }
+
+    /// Sums `operand` over the dimensions whose labels satisfy `pred`.
+    fn sum_out(&self, operand: Operand, pred: impl Fn(&Label) -> bool) -> Result<Operand> {
+        let dims = (0..operand.labels.len() as i64)
+            .filter(|&i| pred(&operand.labels[i as usize]))
+            .collect::<Vec<_>>();
+        if dims.is_empty() {
+            return Ok(operand);
+        }
+        let op = operand
+            .op
+            .reduce(&operand.op.zero_like(), &sum_computation(self.ty)?, &dims);
+        let labels = operand.labels.into_iter().filter(|l| !pred(l)).collect();
+        Ok(Operand { op, labels })
+    }
+
+    /// Keeps the diagonal of every label repeated within the operand, e.g. `ii` or `iji`.
+    fn take_diagonals(&self, mut operand: Operand) -> Result<Operand> {
+        while let Some((p, q)) = (0..operand.labels.len()).find_map(|q| {
+            (0..q)
+                .find(|&p| operand.labels[p] == operand.labels[q])
+                .map(|p| (p, q))
+        }) {
+            let builder = operand.op.builder();
+            let dims = self.dims(&operand.labels);
+            let mask = builder
+                .iota(&dims, ElementType::S32, p as i64)
+                .eq(&builder.iota(&dims, ElementType::S32, q as i64));
+            let op = mask.select(&operand.op, &operand.op.zeros_like());
+            let op = op.reduce(&op.zero_like(), &sum_computation(self.ty)?, &[q as i64]);
+            operand.labels.remove(q);
+            operand.op = op;
+        }
+        Ok(operand)
+    }
+
+    /// Contracts two operands with a single `dot_general`, `needed` holding the labels still
+    /// used by other operands or by the output.
+    fn contract(&self, lhs: Operand, rhs: Operand, needed: &BTreeSet<Label>) -> Result<Operand> {
+        let lhs = self.sum_out(lhs, |l| !needed.contains(l) && !rhs.labels.contains(l))?;
+        let rhs = self.sum_out(rhs, |l| !needed.contains(l) && !lhs.labels.contains(l))?;
+        let position = |labels: &[Label], l: &Label| labels.iter().position(|x| x == l).unwrap();
+        let mut dims = DotDimensionNumbers::new();
+        let mut batch = vec![];
+        for l in lhs.labels.iter().filter(|l| rhs.labels.contains(l)) {
+            let (i, j) = (position(&lhs.labels, l), position(&rhs.labels, l));
+            if needed.contains(l) {
+                dims.add_lhs_batch_dimensions(i as i64);
+                dims.add_rhs_batch_dimensions(j as i64);
+                batch.push(*l);
+            } else {
+                dims.add_lhs_contracting_dimensions(i as i64);
+                dims.add_rhs_contracting_dimensions(j as i64);
+            }
+        }
+        let lhs_free = lhs.labels.iter().filter(|l| !rhs.labels.contains(l));
+        let rhs_free = rhs.labels.iter().filter(|l| !lhs.labels.contains(l));
+        let labels = batch
+            .iter()
+            .chain(lhs_free)
+            .chain(rhs_free)
+            .copied()
+            .collect();
+        Ok(Operand {
+            op: lhs.op.dot_general(&rhs.op, dims),
+            labels,
+        })
+    }
+
+    /// The number of elements produced by contracting `lhs` and `rhs`.
+    fn contraction_size(&self, lhs: &Operand, rhs: &Operand, needed: &BTreeSet<Label>) -> i64 {
+        let kept = lhs
+            .labels
+            .iter()
+            .chain(rhs.labels.iter())
+            .filter(|l| needed.contains(l))
+            .collect::<BTreeSet<_>>();
+        kept.into_iter().map(|l| self.sizes[l]).product()
+    }
+
+    /// Labels used by the output or by any operand except the ones at `skip`.
+    fn needed(&self, operands: &[Operand], skip: &[usize]) -> BTreeSet<Label> {
+        operands
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !skip.contains(i))
+            .flat_map(|(_, o)| o.labels.iter().copied())
+            .chain(self.output.iter().copied())
+            .collect()
+    }
+
+    fn lower(&self, mut operands: Vec<Operand>) -> Result<XlaOp> {
+        for i in 0..operands.len() {
+            let operand = operands.remove(i);
+            let operand = self.take_diagonals(operand)?;
+            let needed = self.needed(&operands, &[]);
+            let operand = self.sum_out(operand, |l| !needed.contains(l))?;
+            operands.insert(i, operand);
+        }
+        // Greedily contract the pair producing the smallest intermediate value.
+        while operands.len() > 1 {
+            let mut best: Option<(i64, usize, usize)> = None;
+            for i in 0..operands.len() {
+                for j in i + 1..operands.len() {
+                    let needed = self.needed(&operands, &[i, j]);
+                    let size = self.contraction_size(&operands[i], &operands[j], &needed);
+                    if best.is_none_or(|(best_size, _, _)| size < best_size) {
+                        best = Some((size, i, j))
+                    }
+                }
+            }
+            let (_, i, j) = best.unwrap();
+            let needed = self.needed(&operands, &[i, j]);
+            let rhs = operands.remove(j);
+            let lhs = operands.remove(i);
+            operands.push(self.contract(lhs, rhs, &needed)?);
+        }
+        let operand = operands.pop().unwrap();
+        let operand = self.sum_out(operand, |l| !self.output.contains(l))?;
+        let permutation = self
+            .output
+            .iter()
+            .map(|l| operand.labels.iter().position(|x| x == l).unwrap() as i64)
+            .collect::<Vec<_>>();
+        if permutation.iter().enumerate().all(|(i, &p)| i as i64 == p) {
+            Ok(operand.op)
+        } else {
+            Ok(operand.op.transpose(&permutation))
+        }
+    }
+}
+
+/// Einstein summation over `operands`, following the NumPy conventions.
+///
+/// `spec` lists the subscripts of each operand separated by commas, optionally followed by `->`
+/// and the output subscripts, e.g. `"bij,bjk->bik"`. Without `->`, the output holds the
+/// subscripts appearing exactly once, in alphabetical order. `...` stands for any number of
+/// leading dimensions, broadcast across operands. Repeating a subscript within an operand takes
+/// a diagonal, so `"ii->"` computes the trace.
+///
+/// Contractions involving more than two operands are evaluated pairwise, greedily picking the
+/// pair that produces the smallest intermediate value.
+#[track_caller]
+pub fn einsum(spec: &str, operands: &[&XlaOp]) -> Result<XlaOp> {
+    let parsed = parse(spec)?;
+    if parsed.inputs.len() != operands.len() {
+        Err(invalid(
+            spec,
+            format!(
+                "{} operand subscripts for {} operands",
+                parsed.inputs.len(),
+                operands.len()
+            ),
+        ))?
+    }
+    let _site = operands[0].builder.call_site();
+    let shapes = operands
+        .iter()
+        .map(|op| op.array_shape())
+        .collect::<Result<Vec<_>>>()?;
+    let ty = shapes[0].ty();
+
+    // Resolve ellipses now that operand ranks are known.
+    let mut ellipsis_ranks = vec![];
+    for (i, (term, shape)) in parsed.inputs.iter().zip(shapes.iter()).enumerate() {
+        let rank = shape.dims().len();
+        let ellipsis_rank = match term.ellipsis {
+            Some(_) if rank >= term.chars.len() => rank - term.chars.len(),
+            _ if rank == term.chars.len() => 0,
+            _ => Err(invalid(
+                spec,
+                format!(
+                    "operand {i} has rank {rank} but {} subscripts",
+                    term.chars.len()
+                ),
+            ))?,
+        };
+        ellipsis_ranks.push(ellipsis_rank);
+    }
+    let total_ellipsis_rank = ellipsis_ranks.iter().copied().max().unwrap_or(0);
+    let inputs = parsed
+        .inputs
+        .iter()
+        .zip(ellipsis_ranks.iter())
+        .map(|(term, e)| resolve(term, *e, total_ellipsis_rank))
+        .collect::<Vec<_>>();
+
+    // Check that each label has a consistent size, ellipsis dimensions of size 1 broadcast.
+    let mut sizes: HashMap<Label, (i64, usize)> = HashMap::new();
+    for (i, (labels, shape)) in inputs.iter().zip(shapes.iter()).enumerate() {
+        for (label, &size) in labels.iter().zip(shape.dims().iter()) {
+            match sizes.get(label) {
+                Some(&(s, _)) if s == size => {}
+                Some(&(1, _)) if matches!(label, Label::Ellipsis(_)) => {
+                    sizes.insert(*label, (size, i));
+                }
+                Some(_) if matches!(label, Label::Ellipsis(_)) && size == 1 => {}
+                Some(&(s, j)) => Err(invalid(
+                    spec,
+                    format!(
+                        "subscript {label} has size {s} in operand {j} but {size} in operand {i}"
+                    ),
+                ))?,
+                None => {
+                    sizes.insert(*label, (size, i));
+                }
+            }
+        }
+    }
+    let sizes = sizes
+        .into_iter()
+        .map(|(label, (size, _))| (label, size))
+        .collect::<HashMap<_, _>>();
+
+    let output = match &parsed.output {
+        Some(term) => {
+            let output = resolve(term, total_ellipsis_rank, total_ellipsis_rank);
+            for (i, label) in output.iter().enumerate() {
+                if output[..i].contains(label) {
+                    Err(invalid(
+                        spec,
+                        format!("subscript {label} repeated in output"),
+                    ))?
+                }
+                if !sizes.contains_key(label) {
+                    Err(invalid(
+                        spec,
+                        format!("output subscript {label} does not appear in any operand"),
+                    ))?
+                }
+            }
+            output
+        }
+        None => {
+            let mut counts: HashMap<char, usize> = HashMap::new();
+            for term in parsed.inputs.iter() {
+                for c in term.chars.iter() {
+                    *counts.entry(*c).or_default() += 1;
+                }
+            }
+            let chars = counts
+                .into_iter()
+                .filter(|(_, count)| *count == 1)
+                .map(|(c, _)| Label::Char(c))
+                .collect::<BTreeSet<_>>();
+            (0..total_ellipsis_rank)
+                .map(Label::Ellipsis)
+                .chain(chars)
+                .collect()
+        }
+    };
+
+    // Drop broadcast ellipsis dimensions so that they behave like absent labels.
+    let mut lowered = vec![];
+    for ((op, labels), shape) in operands.iter().zip(inputs).zip(shapes.iter()) {
+        let keep = labels
+            .iter()
+            .zip(shape.dims().iter())
+            .map(|(label, &size)| size == sizes[label])
+            .collect::<Vec<_>>();
+        if keep.iter().all(|k| *k) {
+            lowered.push(Operand {
+                op: (*op).clone(),
+                labels,
+            });
+        } else {
+            let labels = labels
+                .into_iter()
+                .zip(keep.iter())
+                .filter_map(|(l, k)| k.then_some(l))
+                .collect::<Vec<_>>();
+            let dims = labels.iter().map(|l| sizes[l]).collect::<Vec<_>>();
+            lowered.push(Operand {
+                op: op.reshape(&dims),
+                labels,
+            });
+        }
+    }
+    let lowering = Lowering {
+        sizes,
+        output: &output,
+        ty,
+    };
+    lowering.lower(lowered)
+}
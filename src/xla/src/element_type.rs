@@ -1,9 +1,17 @@
 use std::str::FromStr;
 
 use crate::error::{Error, Result};
+use half::{bf16, f16};
+use num_complex::Complex;
 use num_derive::FromPrimitive;
 
-pub trait ArrayElement: Copy {
+/// A Rust type that can be stored in XLA arrays.
+///
+/// # Safety
+///
+/// Every value XLA can store for `TY` must be a valid `Self`, with the same size and layout, so
+/// that host buffers can be reinterpreted as `&[Self]`.
+pub unsafe trait ArrayElement: Copy {
     const TY: ElementType;
     const ELEMENT_SIZE_IN_BYTES: usize;
     const ZERO: Self;
@@ -60,7 +68,7 @@ impl PrimitiveType {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum ElementType {
     Pred,
     S8,
@@ -147,29 +155,45 @@ impl ElementType {
     }
 }
 
-// Dummy F16 type.
-#[derive(Copy, Clone, Debug)]
-pub struct F16;
+/// Half precision float, kept as an alias for the previous marker type.
+pub type F16 = f16;
 
-impl ArrayElement for F16 {
+/// Brain float, kept as an alias for the previous marker type.
+pub type Bf16 = bf16;
+
+unsafe impl ArrayElement for bool {
+    const TY: ElementType = ElementType::Pred;
+    const ELEMENT_SIZE_IN_BYTES: usize = 1;
+    const ZERO: Self = false;
+}
+
+unsafe impl ArrayElement for f16 {
     const TY: ElementType = ElementType::F16;
     const ELEMENT_SIZE_IN_BYTES: usize = 2;
-    const ZERO: Self = Self;
+    const ZERO: Self = f16::ZERO;
 }
 
-// Dummy BF16 type.
-#[derive(Copy, Clone, Debug)]
-pub struct Bf16;
-
-impl ArrayElement for Bf16 {
+unsafe impl ArrayElement for bf16 {
     const TY: ElementType = ElementType::Bf16;
     const ELEMENT_SIZE_IN_BYTES: usize = 2;
-    const ZERO: Self = Self;
+    const ZERO: Self = bf16::ZERO;
+}
+
+unsafe impl ArrayElement for Complex<f32> {
+    const TY: ElementType = ElementType::C64;
+    const ELEMENT_SIZE_IN_BYTES: usize = 8;
+    const ZERO: Self = Complex::new(0., 0.);
+}
+
+unsafe impl ArrayElement for Complex<f64> {
+    const TY: ElementType = ElementType::C128;
+    const ELEMENT_SIZE_IN_BYTES: usize = 16;
+    const ZERO: Self = Complex::new(0., 0.);
 }
 
 macro_rules! element_type {
     ($ty:ty, $v:ident, $sz:tt) => {
-        impl ArrayElement for $ty {
+        unsafe impl ArrayElement for $ty {
             const TY: ElementType = ElementType::$v;
             const ELEMENT_SIZE_IN_BYTES: usize = $sz;
             const ZERO: Self = 0 as Self;
@@ -1,5 +1,5 @@
 use cpp::{cpp, cpp_class};
-use cxx::{CxxString, UniquePtr};
+use cxx::{CxxString, CxxVector, UniquePtr};
 
 cpp! {{
     #include "xla/statusor.h"
@@ -17,8 +17,36 @@ pub enum Error {
     },
 
     /// Error from the xla C++ library.
-    #[error("xla error {msg}\n{backtrace}")]
-    XlaError { msg: String, backtrace: String },
+    #[error("xla error ({code}) {msg}\n{backtrace}")]
+    XlaError {
+        code: StatusCode,
+        msg: String,
+        payloads: Vec<StatusPayload>,
+        backtrace: String,
+    },
+
+    /// Error from the xla C++ library about two shapes that do not match, with the shapes
+    /// decoded from its message.
+    #[error(
+        "xla shape mismatch ({code}), expected: {expected:?}, got: {got:?}, {msg}\n{backtrace}"
+    )]
+    XlaShapeMismatch {
+        code: StatusCode,
+        msg: String,
+        expected: crate::Shape,
+        got: crate::Shape,
+        payloads: Vec<StatusPayload>,
+        backtrace: String,
+    },
+
+    /// Error reported while building a computation, along with the Rust call site of the op
+    /// that caused it.
+    #[error("{source}\nop created at {file}:{line}")]
+    BuildError {
+        file: String,
+        line: u32,
+        source: Box<Error>,
+    },
 
     #[error("unexpected element type {0}")]
     UnexpectedElementType(i32),
@@ -39,6 +67,9 @@ pub enum Error {
         got: crate::Shape,
     },
 
+    #[error("not a tuple, got: {got:?}")]
+    NotATuple { got: crate::Shape },
+
     #[error("cannot handle unsupported shapes {shape:?}")]
     UnsupportedShape { shape: crate::Shape },
 
@@ -78,9 +109,30 @@ pub enum Error {
     #[error("index out of bounds {index}, rank {rank}")]
     IndexOutOfBounds { index: i64, rank: usize },
 
+    #[error("index {index:?} out of bounds for dims {dims:?}")]
+    MultiIndexOutOfBounds { index: Vec<i64>, dims: Vec<i64> },
+
+    #[error("invalid layout {minor_to_major:?} for rank {rank}")]
+    InvalidLayout {
+        minor_to_major: Vec<i64>,
+        rank: usize,
+    },
+
+    #[error("invalid slice {start_indices:?}..{limit_indices:?} for dims {dims:?}")]
+    InvalidSlice {
+        start_indices: Vec<i64>,
+        limit_indices: Vec<i64>,
+        dims: Vec<i64>,
+    },
+
     #[error("npy/npz error {0}")]
     Npy(String),
 
+    /// Shape error when converting to or from ndarray.
+    #[cfg(feature = "ndarray")]
+    #[error(transparent)]
+    NdArrayShape(#[from] ndarray::ShapeError),
+
     /// I/O error.
     #[error(transparent)]
     Io(#[from] std::io::Error),
@@ -105,12 +157,209 @@ pub enum Error {
         msg: &'static str,
     },
 
+    #[error("invalid einsum {spec:?}: {msg}")]
+    InvalidEinsum { spec: String, msg: String },
+
+    #[error("shape mismatch, actual: {actual:?}, expected: {expected:?}")]
+    ShapeMismatch {
+        actual: crate::Shape,
+        expected: crate::Shape,
+    },
+
+    #[error(
+        "arrays are not close (rtol={rtol}, atol={atol}), {mismatches}/{element_count} elements differ, \
+         worst at index {index:?}: actual {actual}, expected {expected}, \
+         abs err {abs_err:e}, rel err {rel_err:e}"
+    )]
+    NotClose {
+        rtol: f64,
+        atol: f64,
+        mismatches: usize,
+        element_count: usize,
+        index: Vec<i64>,
+        actual: String,
+        expected: String,
+        abs_err: f64,
+        rel_err: f64,
+    },
+
     #[error("cast error")]
     CastError,
+
+    #[error("buffer was donated to a previous execution and can no longer be used")]
+    DonatedBuffer,
+
+    #[error("argument {index} was donated to a previous execution and can no longer be used")]
+    DonatedArgument { index: usize },
+
+    #[error("the arguments of device {device} disagree with those of device 0 on {option}")]
+    InconsistentShardedArgs { device: usize, option: &'static str },
+
+    #[error("custom call target {name:?} is already registered")]
+    CustomCallAlreadyRegistered { name: String },
+
+    #[error("unknown custom call target {name:?}")]
+    UnknownCustomCall { name: String },
+
+    #[error("unknown mesh axis {axis:?}, mesh axes: {axes:?}")]
+    UnknownMeshAxis { axis: String, axes: Vec<String> },
+
+    #[error("mesh axis {axis:?} is used more than once in partition spec")]
+    DuplicateMeshAxis { axis: String },
+
+    #[error("partition spec has {spec_len} dimensions but the array has rank {rank}")]
+    PartitionSpecRankMismatch { spec_len: usize, rank: usize },
+
+    #[error("mesh of shape {axis_sizes:?} cannot be built from {device_count} devices")]
+    MeshDeviceCountMismatch {
+        axis_sizes: Vec<i64>,
+        device_count: usize,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// The status code of errors coming from the xla C++ library, e.g. to retry on
+    /// [`StatusCode::ResourceExhausted`].
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            Self::XlaError { code, .. } | Self::XlaShapeMismatch { code, .. } => Some(*code),
+            Self::BuildError { source, .. } => source.status_code(),
+            _ => None,
+        }
+    }
+}
+
+/// The canonical absl status codes used by XLA.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum StatusCode {
+    Cancelled,
+    Unknown,
+    InvalidArgument,
+    DeadlineExceeded,
+    NotFound,
+    AlreadyExists,
+    PermissionDenied,
+    ResourceExhausted,
+    FailedPrecondition,
+    Aborted,
+    OutOfRange,
+    Unimplemented,
+    Internal,
+    Unavailable,
+    DataLoss,
+    Unauthenticated,
+}
+
+impl StatusCode {
+    fn from_i32(code: i32) -> Self {
+        match code {
+            1 => Self::Cancelled,
+            3 => Self::InvalidArgument,
+            4 => Self::DeadlineExceeded,
+            5 => Self::NotFound,
+            6 => Self::AlreadyExists,
+            7 => Self::PermissionDenied,
+            8 => Self::ResourceExhausted,
+            9 => Self::FailedPrecondition,
+            10 => Self::Aborted,
+            11 => Self::OutOfRange,
+            12 => Self::Unimplemented,
+            13 => Self::Internal,
+            14 => Self::Unavailable,
+            15 => Self::DataLoss,
+            16 => Self::Unauthenticated,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The name used by absl, e.g. `INVALID_ARGUMENT`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Cancelled => "CANCELLED",
+            Self::Unknown => "UNKNOWN",
+            Self::InvalidArgument => "INVALID_ARGUMENT",
+            Self::DeadlineExceeded => "DEADLINE_EXCEEDED",
+            Self::NotFound => "NOT_FOUND",
+            Self::AlreadyExists => "ALREADY_EXISTS",
+            Self::PermissionDenied => "PERMISSION_DENIED",
+            Self::ResourceExhausted => "RESOURCE_EXHAUSTED",
+            Self::FailedPrecondition => "FAILED_PRECONDITION",
+            Self::Aborted => "ABORTED",
+            Self::OutOfRange => "OUT_OF_RANGE",
+            Self::Unimplemented => "UNIMPLEMENTED",
+            Self::Internal => "INTERNAL",
+            Self::Unavailable => "UNAVAILABLE",
+            Self::DataLoss => "DATA_LOSS",
+            Self::Unauthenticated => "UNAUTHENTICATED",
+        }
+    }
+}
+
+impl std::fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Additional data attached to a status, identified by a type URL.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct StatusPayload {
+    pub type_url: String,
+    pub data: Vec<u8>,
+}
+
+/// All the shapes printed in `msg` along with their offset.
+fn shapes_in(msg: &str) -> Vec<(usize, crate::Shape)> {
+    let mut shapes = vec![];
+    let mut offset = 0;
+    while offset < msg.len() {
+        let at_word_start = msg[..offset]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_ascii_alphanumeric());
+        if at_word_start {
+            if let Some((shape, rest)) = crate::display::parse_shape(&msg[offset..]) {
+                shapes.push((offset, shape));
+                offset = msg.len() - rest.len();
+                continue;
+            }
+        }
+        offset += msg[offset..].chars().next().map_or(1, |c| c.len_utf8());
+    }
+    shapes
+}
+
+/// Decodes the expected and actual shapes from messages such as "expected f32[2], got f32[3]"
+/// or "incompatible shapes: f32[2] and f32[3]", where the first shape is taken as the expected
+/// one unless it is introduced by "got".
+fn shape_mismatch(msg: &str) -> Option<(crate::Shape, crate::Shape)> {
+    let lower = msg.to_ascii_lowercase();
+    let keywords = [
+        "mismatch",
+        "incompatible",
+        "expect",
+        "want",
+        "must be the same",
+        "does not match",
+        "not compatible",
+    ];
+    if !keywords.iter().any(|k| lower.contains(k)) {
+        return None;
+    }
+    let mut shapes = shapes_in(msg).into_iter();
+    let (offset, first) = shapes.next()?;
+    let (_, second) = shapes.next()?;
+    let before = &lower[..offset];
+    let expected_pos = before.rfind("expect").max(before.rfind("want"));
+    if before.rfind("got") > expected_pos {
+        Some((second, first))
+    } else {
+        Some((first, second))
+    }
+}
+
 cpp_class!(pub unsafe struct Status as "Status");
 
 impl Status {
@@ -130,6 +379,32 @@ impl Status {
         }
     }
 
+    /// The payloads attached to this status.
+    pub fn payloads(&self) -> Vec<StatusPayload> {
+        // Type URLs and payloads are interleaved.
+        let entries = unsafe {
+            cpp!([self as "const Status*"] -> UniquePtr<CxxVector<CxxString>> as "std::unique_ptr<std::vector<std::string>>" {
+                auto entries = std::make_unique<std::vector<std::string>>();
+                self->ForEachPayload([&](absl::string_view type_url, const absl::Cord& payload) {
+                    entries->emplace_back(type_url);
+                    entries->emplace_back(std::string(payload));
+                });
+                return entries;
+            })
+        };
+        let entries = match entries.as_ref() {
+            Some(entries) => entries.iter().map(|e| e.as_bytes()).collect::<Vec<_>>(),
+            None => return vec![],
+        };
+        entries
+            .chunks_exact(2)
+            .map(|entry| StatusPayload {
+                type_url: String::from_utf8_lossy(entry[0]).into_owned(),
+                data: entry[1].to_vec(),
+            })
+            .collect()
+    }
+
     pub fn to_result(&self) -> Result<()> {
         if self.is_ok() {
             Ok(())
@@ -144,8 +419,30 @@ impl Status {
                 .and_then(|msg| msg.to_str().ok())
                 .map(|msg| msg.to_string())
                 .unwrap_or_default();
+            let code = unsafe {
+                cpp!([self as "const Status*"] -> i32 as "int32_t" {
+                    return (int32_t)self->code();
+                })
+            };
+            let code = StatusCode::from_i32(code);
             let backtrace = std::backtrace::Backtrace::capture().to_string();
-            Err(Error::XlaError { msg, backtrace })
+            let payloads = self.payloads();
+            match shape_mismatch(&msg) {
+                Some((expected, got)) => Err(Error::XlaShapeMismatch {
+                    code,
+                    msg,
+                    expected,
+                    got,
+                    payloads,
+                    backtrace,
+                }),
+                None => Err(Error::XlaError {
+                    code,
+                    msg,
+                    payloads,
+                    backtrace,
+                }),
+            }
         }
     }
 }
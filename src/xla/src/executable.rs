@@ -1,4 +1,6 @@
-use crate::{BufferArgs, PjRtBuffer, Result, Status};
+use crate::{
+    BufferArgs, BufferArgsInnerRaw, Error, PjRtBuffer, PjRtFuture, RawPjRtFuture, Result, Status,
+};
 
 use cpp::{cpp, cpp_class};
 
@@ -33,14 +35,21 @@ impl PjRtLoadedExecutable {
     pub fn execute_buffers(&self, buffers: impl BufferArgs) -> Result<Vec<PjRtBuffer>> {
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let untuple_result = buffers.untuple_result();
+        let non_donatable = buffers.non_donatable_input_indices();
+        let non_donatable_ptr = non_donatable.as_ptr();
+        let non_donatable_len = non_donatable.len();
         let buffers = buffers.get();
+        buffers.check_not_donated()?;
         let mut out = vec![];
         {
             let out_ptr = &mut out;
             unsafe {
-                cpp!([self as "const std::shared_ptr<PjRtLoadedExecutable>*", buffers as "std::unique_ptr<std::vector<PjRtBuffer*>>", out_status as "Status*", out_ptr as "void*", untuple_result as "bool"] {
+                cpp!([self as "const std::shared_ptr<PjRtLoadedExecutable>*", buffers as "std::unique_ptr<std::vector<PjRtBuffer*>>", out_status as "Status*", out_ptr as "void*", untuple_result as "bool", non_donatable_ptr as "const int64_t*", non_donatable_len as "size_t"] {
                     ExecuteOptions options;
                     options.untuple_result = untuple_result;
+                    for (size_t i = 0; i < non_donatable_len; ++i) {
+                        options.non_donatable_input_indices.insert(non_donatable_ptr[i]);
+                    }
                     auto status = (*self)->Execute(absl::Span(buffers.get(), 1), options);
                     if (status.ok()) {
                         std::vector<std::vector<std::unique_ptr<PjRtBuffer>>> bufs = std::move(status).value();
@@ -64,4 +73,135 @@ impl PjRtLoadedExecutable {
         out_status.to_result()?;
         Ok(out)
     }
+
+    /// Executes the computation on several devices at once.
+    ///
+    /// `args` holds one argument list per addressable device, in the order of the executable's
+    /// device assignment. The outer vector of the result is indexed the same way, so the buffers
+    /// produced by each replica or partition stay separate.
+    ///
+    /// The execution options are shared by all the devices, so every argument list must agree
+    /// on whether to untuple the result and on which arguments may not be donated.
+    pub fn execute_sharded<A: BufferArgs>(&self, args: &[A]) -> Result<Vec<Vec<PjRtBuffer>>> {
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let untuple_result = args.first().is_some_and(|a| a.untuple_result());
+        let non_donatable = args
+            .first()
+            .map_or(&[][..], |a| a.non_donatable_input_indices());
+        for (device, a) in args.iter().enumerate().skip(1) {
+            if a.untuple_result() != untuple_result {
+                Err(Error::InconsistentShardedArgs {
+                    device,
+                    option: "untuple_result",
+                })?
+            }
+            if a.non_donatable_input_indices() != non_donatable {
+                Err(Error::InconsistentShardedArgs {
+                    device,
+                    option: "non-donatable inputs",
+                })?
+            }
+        }
+        let non_donatable_ptr = non_donatable.as_ptr();
+        let non_donatable_len = non_donatable.len();
+        for a in args {
+            a.get().check_not_donated()?;
+        }
+        let args = args
+            .iter()
+            .map(|a| a.get() as *const BufferArgsInnerRaw)
+            .collect::<Vec<_>>();
+        let args_ptr = args.as_ptr();
+        let args_len = args.len();
+        let mut out: Vec<Vec<PjRtBuffer>> = vec![];
+        {
+            let out_ptr = &mut out;
+            unsafe {
+                cpp!([self as "const std::shared_ptr<PjRtLoadedExecutable>*", args_ptr as "const std::vector<PjRtBuffer*>* const*", args_len as "size_t", out_status as "Status*", out_ptr as "void*", untuple_result as "bool", non_donatable_ptr as "const int64_t*", non_donatable_len as "size_t"] {
+                    ExecuteOptions options;
+                    options.untuple_result = untuple_result;
+                    for (size_t i = 0; i < non_donatable_len; ++i) {
+                        options.non_donatable_input_indices.insert(non_donatable_ptr[i]);
+                    }
+                    std::vector<std::vector<PjRtBuffer*>> handles;
+                    for (size_t i = 0; i < args_len; ++i) {
+                        handles.push_back(*args_ptr[i]);
+                    }
+                    auto status = (*self)->Execute(absl::MakeSpan(handles), options);
+                    if (status.ok()) {
+                        std::vector<std::vector<std::unique_ptr<PjRtBuffer>>> bufs = std::move(status).value();
+                        for (size_t replica = 0; replica < bufs.size(); ++replica) {
+                             for (auto& buf : bufs[replica]) {
+                                 auto out_buf_ptr = rust!(push_out_buf_loaded_exec_sharded [out_ptr : &mut Vec<Vec<PjRtBuffer>> as "void*", replica : usize as "size_t"] -> *mut PjRtBuffer as "std::unique_ptr<PjRtBuffer>*" {
+                                     if out_ptr.len() <= replica {
+                                         out_ptr.resize_with(replica + 1, Vec::new);
+                                     }
+                                     let replica_bufs = &mut out_ptr[replica];
+                                     replica_bufs.push(PjRtBuffer::default());
+                                     let i = replica_bufs.len() - 1;
+                                     let ptr = &mut replica_bufs[i];
+                                     ptr as *mut PjRtBuffer
+                                 });
+                                 *out_buf_ptr = std::move(buf);
+                             }
+                        }
+                    }else{
+                        *out_status = Status(status.status());
+                    }
+                })
+            };
+        }
+        out_status.to_result()?;
+        Ok(out)
+    }
+
+    /// Dispatches the executable without waiting for it to finish.
+    ///
+    /// The returned future resolves to the output buffers once execution has completed, which
+    /// allows host-side work such as staging the next inputs to overlap with compute.
+    pub fn execute_async(&self, buffers: impl BufferArgs) -> Result<PjRtFuture<Vec<PjRtBuffer>>> {
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let untuple_result = buffers.untuple_result();
+        let non_donatable = buffers.non_donatable_input_indices();
+        let non_donatable_ptr = non_donatable.as_ptr();
+        let non_donatable_len = non_donatable.len();
+        let buffers = buffers.get();
+        buffers.check_not_donated()?;
+        let mut out = vec![];
+        let done = {
+            let out_ptr = &mut out;
+            unsafe {
+                cpp!([self as "const std::shared_ptr<PjRtLoadedExecutable>*", buffers as "std::unique_ptr<std::vector<PjRtBuffer*>>", out_status as "Status*", out_ptr as "void*", untuple_result as "bool", non_donatable_ptr as "const int64_t*", non_donatable_len as "size_t"] -> RawPjRtFuture as "PjRtFuture<>" {
+                    ExecuteOptions options;
+                    options.untuple_result = untuple_result;
+                    for (size_t i = 0; i < non_donatable_len; ++i) {
+                        options.non_donatable_input_indices.insert(non_donatable_ptr[i]);
+                    }
+                    std::optional<std::vector<PjRtFuture<>>> futures;
+                    futures.emplace();
+                    auto status = (*self)->Execute(absl::Span(buffers.get(), 1), options, futures);
+                    if (status.ok()) {
+                        std::vector<std::vector<std::unique_ptr<PjRtBuffer>>> bufs = std::move(status).value();
+                        for (auto& replica_bufs : bufs) {
+                             for (auto& buf : replica_bufs) {
+                                 auto out_buf_ptr = rust!(push_out_buf_loaded_exec_async [out_ptr : &mut Vec<PjRtBuffer> as "void*"] -> *mut PjRtBuffer as "std::unique_ptr<PjRtBuffer>*" {
+                                     out_ptr.push(PjRtBuffer::default());
+                                     let i = out_ptr.len() - 1;
+                                     let ptr = &mut out_ptr[i];
+                                     ptr as *mut PjRtBuffer
+                                 });
+                                 *out_buf_ptr = std::move(buf);
+                             }
+                        }
+                        return JoinFutures(*futures);
+                    }else{
+                        *out_status = Status(status.status());
+                        return PjRtFuture<>(status.status());
+                    }
+                })
+            }
+        };
+        out_status.to_result()?;
+        Ok(PjRtFuture::new(done, out))
+    }
 }
@@ -0,0 +1,115 @@
+use crate::{Result, Status};
+
+use cpp::{cpp, cpp_class};
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+cpp! {{
+    #include "xla/pjrt/pjrt_future.h"
+    using namespace xla;
+}}
+
+cpp_class!(pub unsafe struct RawPjRtFuture as "PjRtFuture<>");
+
+#[derive(Default)]
+struct FutureState {
+    status: Option<Result<()>>,
+    waker: Option<Waker>,
+}
+
+/// State shared between the Rust future and the C++ `OnReady` callback.
+#[derive(Default)]
+struct FutureShared {
+    state: Mutex<FutureState>,
+    ready: Condvar,
+}
+
+impl FutureShared {
+    fn complete(&self, status: Result<()>) {
+        let mut state = self.state.lock().unwrap();
+        state.status = Some(status);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// A value that becomes available once an asynchronous PjRt operation completes.
+///
+/// The future is driven by PjRt's own threads, so it can be awaited from any executor (or
+/// blocked on with [`PjRtFuture::wait`]) without requiring a specific async runtime.
+pub struct PjRtFuture<T> {
+    shared: Arc<FutureShared>,
+    value: Option<T>,
+}
+
+impl<T> PjRtFuture<T> {
+    /// Wraps a C++ `PjRtFuture<>`, yielding `value` once it resolves successfully.
+    ///
+    /// `value` must stay valid for as long as the C++ side may write into it, which is
+    /// guaranteed by keeping any backing memory alive in the `OnReady` callback.
+    pub(crate) fn new(raw: RawPjRtFuture, value: T) -> Self {
+        let shared = Arc::new(FutureShared::default());
+        // The callback owns one strong reference until it fires.
+        let shared_ptr = Arc::into_raw(shared.clone());
+        unsafe {
+            cpp!([raw as "PjRtFuture<>", shared_ptr as "const void*"] {
+                raw.OnReady([shared_ptr](absl::Status status) {
+                    const Status* status_ptr = &status;
+                    rust!(pjrt_future_on_ready [shared_ptr : *const FutureShared as "const void*", status_ptr : &Status as "const Status*"] {
+                        let shared = unsafe { Arc::from_raw(shared_ptr) };
+                        shared.complete(status_ptr.to_result());
+                    });
+                });
+            })
+        };
+        Self {
+            shared,
+            value: Some(value),
+        }
+    }
+
+    /// Returns true if the underlying operation has completed, successfully or not.
+    pub fn is_ready(&self) -> bool {
+        self.shared.state.lock().unwrap().status.is_some()
+    }
+
+    /// Blocks the current thread until the operation completes.
+    pub fn wait(mut self) -> Result<T> {
+        let mut state = self.shared.state.lock().unwrap();
+        while state.status.is_none() {
+            state = self.shared.ready.wait(state).unwrap();
+        }
+        let status = state.status.take().unwrap();
+        drop(state);
+        status.map(|()| self.value.take().expect("PjRtFuture resolved twice"))
+    }
+}
+
+impl<T: Unpin> Future for PjRtFuture<T> {
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let status = {
+            let mut state = self.shared.state.lock().unwrap();
+            match state.status.take() {
+                Some(status) => status,
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+        Poll::Ready(status.map(|()| {
+            self.value
+                .take()
+                .expect("PjRtFuture polled after completion")
+        }))
+    }
+}
@@ -53,8 +53,8 @@ impl HloModuleProto {
 
     pub fn computation(&self) -> XlaComputation {
         unsafe {
-            cpp!([self as "HloModuleProto*"] -> XlaComputation as "XlaComputation" {
-                return XlaComputation(*self);
+            cpp!([self as "HloModuleProto*"] -> XlaComputation as "RustXlaComputation" {
+                return RustXlaComputation{XlaComputation(*self), {}};
             })
         }
     }
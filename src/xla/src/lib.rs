@@ -38,27 +38,47 @@ mod buffer;
 mod builder;
 mod client;
 mod computation;
+mod custom_call;
+mod device;
+mod device_array;
+mod display;
+mod einsum;
 mod element_type;
 mod error;
 mod executable;
+mod future;
 mod hlo_module;
 mod literal;
 mod native_type;
+#[cfg(feature = "ndarray")]
+mod ndarray_impl;
 mod op;
+mod prng;
 mod shape;
+mod sharding;
+mod testing;
 
 pub use buffer::*;
 pub use builder::*;
 pub use client::*;
 pub use computation::*;
+pub use custom_call::*;
+pub use device::*;
+pub use device_array::*;
+pub use display::*;
+pub use einsum::*;
 pub use element_type::*;
-pub use error::{Error, Result, Status};
+pub use error::{Error, Result, Status, StatusCode, StatusPayload};
 pub use executable::*;
+pub use future::*;
 pub use hlo_module::*;
 pub use literal::*;
 pub use native_type::*;
 pub use op::*;
+pub use prng::*;
 pub use shape::*;
+pub use sharding::*;
+pub use testing::*;
 
 extern crate lapack_src as _;
 
@@ -1,6 +1,8 @@
-use crate::{ArrayElement, Error, NativeType, PrimitiveType, RawShape, Result, Shape, Status};
+use crate::{
+    ArrayElement, ArrayShape, ElementType, Error, NativeType, PrimitiveType, RawShape, Result,
+    Shape, Status,
+};
 use cpp::{cpp, cpp_class};
-use zerocopy::{FromBytes, Immutable};
 
 use num_traits::FromPrimitive;
 use std::pin::Pin;
@@ -9,6 +11,8 @@ cpp! {{
     #include "xla/client/lib/constants.h"
     #include "xla/client/lib/matrix.h"
     #include "xla/statusor.h"
+    #include "xla/index_util.h"
+    #include "xla/layout_util.h"
     #include "xla/literal_util.h"
     using namespace xla;
 }}
@@ -21,8 +25,8 @@ impl PartialEq for Literal {
             return true;
         }
 
-        // If not the same object, compare raw buffers
-        self.raw_buf() == other.raw_buf()
+        // If not the same object, compare shapes and raw buffers
+        self.shape().ok() == other.shape().ok() && self.raw_buf() == other.raw_buf()
     }
 }
 
@@ -59,7 +63,34 @@ impl Literal {
         }
     }
 
-    pub fn typed_buf<T: ArrayElement + Immutable + FromBytes>(&self) -> Result<&[T]> {
+    /// Ensures that this literal does not share its storage with any clone, so that it can be
+    /// mutated in place.
+    fn make_mut(&mut self) {
+        unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*"] {
+                if (self->use_count() > 1) {
+                    *self = std::make_shared<Literal>((*self)->Clone());
+                }
+            })
+        }
+    }
+
+    pub(crate) fn raw_buf_mut(&mut self) -> &mut [u8] {
+        self.make_mut();
+        let len: Pin<&mut usize> = std::pin::pin!(0);
+        unsafe {
+            let data = cpp!([self as "std::shared_ptr<Literal>*", len as "size_t*"] -> *mut u8 as "uint8_t*" {
+                *len = (*self)->size_bytes();
+                return (uint8_t*) (*self)->untyped_data();
+            });
+            if *len == 0 {
+                return &mut [];
+            }
+            std::slice::from_raw_parts_mut(data, *len)
+        }
+    }
+
+    fn check_element_type<T: ArrayElement>(&self) -> Result<()> {
         let ty = self.primitive_type()?.element_type()?;
         if ty != T::TY {
             Err(Error::ElementTypeMismatch {
@@ -67,7 +98,230 @@ impl Literal {
                 on_host: T::TY,
             })?
         }
-        <[T]>::ref_from_bytes(self.raw_buf()).map_err(|_| Error::CastError)
+        Ok(())
+    }
+
+    /// Returns the number of `T` elements in a non-empty buffer, checking its size and alignment.
+    fn typed_len<T: ArrayElement>(ptr: *const u8, len: usize) -> Result<usize> {
+        if len % std::mem::size_of::<T>() != 0 || ptr.align_offset(std::mem::align_of::<T>()) != 0 {
+            Err(Error::CastError)?
+        }
+        Ok(len / std::mem::size_of::<T>())
+    }
+
+    pub fn typed_buf<T: ArrayElement>(&self) -> Result<&[T]> {
+        self.check_element_type::<T>()?;
+        let buf = self.raw_buf();
+        if buf.is_empty() {
+            return Ok(&[]);
+        }
+        let len = Self::typed_len::<T>(buf.as_ptr(), buf.len())?;
+        // Safety: `ArrayElement` guarantees that `T` has the layout of `T::TY`, which was checked
+        // above, and the buffer has been checked for size and alignment.
+        Ok(unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const T, len) })
+    }
+
+    /// Mutable access to the elements of an array literal, in the order of its layout.
+    ///
+    /// If the storage is shared with a clone of this literal, it is copied first.
+    pub fn typed_buf_mut<T: ArrayElement>(&mut self) -> Result<&mut [T]> {
+        self.check_element_type::<T>()?;
+        let buf = self.raw_buf_mut();
+        if buf.is_empty() {
+            return Ok(&mut []);
+        }
+        let len = Self::typed_len::<T>(buf.as_ptr(), buf.len())?;
+        // Safety: same as `typed_buf`.
+        Ok(unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, len) })
+    }
+
+    /// Creates a zero-filled literal of the given shape, tuple shapes are supported.
+    pub fn create_from_shape(shape: &Shape) -> Literal {
+        let shape = shape.raw_shape();
+        unsafe {
+            cpp!([shape as "const Shape*"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(Literal::CreateFromShape(*shape));
+            })
+        }
+    }
+
+    /// Creates an array literal with dimensions `dims` from row-major data.
+    pub fn from_slice_with_shape<T: ArrayElement>(data: &[T], dims: &[i64]) -> Result<Literal> {
+        let shape = ArrayShape::new::<T>(dims.to_vec());
+        if dims.iter().any(|d| *d < 0) || shape.element_count() != data.len() {
+            Err(Error::WrongElementCount {
+                dims: dims.to_vec(),
+                element_count: data.len(),
+            })?
+        }
+        let mut lit = Self::create_from_shape(&Shape::Array(shape));
+        lit.typed_buf_mut::<T>()?.copy_from_slice(data);
+        Ok(lit)
+    }
+
+    /// Creates a tuple literal holding `elems`.
+    ///
+    /// Elements that are not shared with another handle are moved into the tuple rather than
+    /// copied.
+    pub fn tuple(mut elems: Vec<Literal>) -> Literal {
+        let elems_ptr = elems.as_mut_ptr();
+        let elems_len = elems.len();
+        unsafe {
+            cpp!([elems_ptr as "std::shared_ptr<Literal>*", elems_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                std::vector<Literal> elems;
+                for (size_t i = 0; i < elems_len; ++i) {
+                    if (elems_ptr[i].use_count() == 1) {
+                        elems.push_back(std::move(*elems_ptr[i]));
+                    } else {
+                        elems.push_back(elems_ptr[i]->Clone());
+                    }
+                }
+                return std::make_shared<Literal>(LiteralUtil::MakeTupleOwned(std::move(elems)));
+            })
+        }
+    }
+
+    /// The shape of this literal, returning an error for tuples.
+    pub fn array_shape(&self) -> Result<ArrayShape> {
+        match self.shape()? {
+            Shape::Array(shape) => Ok(shape),
+            got => Err(Error::NotAnArray {
+                expected: None,
+                got,
+            }),
+        }
+    }
+
+    /// Converts a multi-dimensional index into an offset in the literal's buffer, taking the
+    /// layout into account.
+    fn linear_index(&self, index: &[i64]) -> Result<usize> {
+        let shape = self.array_shape()?;
+        let dims = shape.dims();
+        if index.len() != dims.len() {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: dims.len(),
+                got: index.len(),
+                dims: dims.to_vec(),
+            })?
+        }
+        if index.iter().zip(dims.iter()).any(|(i, d)| *i < 0 || i >= d) {
+            Err(Error::MultiIndexOutOfBounds {
+                index: index.to_vec(),
+                dims: dims.to_vec(),
+            })?
+        }
+        let index_ptr = index.as_ptr();
+        let index_len = index.len();
+        let linear_index = unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*", index_ptr as "const int64_t*", index_len as "size_t"] -> i64 as "int64_t" {
+                return IndexUtil::MultidimensionalIndexToLinearIndex((*self)->shape(), absl::Span<const int64_t>(index_ptr, index_len));
+            })
+        };
+        Ok(linear_index as usize)
+    }
+
+    /// Reads the element at the given multi-dimensional index.
+    pub fn get<T: ArrayElement>(&self, index: &[i64]) -> Result<T> {
+        let linear_index = self.linear_index(index)?;
+        Ok(self.typed_buf::<T>()?[linear_index])
+    }
+
+    /// Writes the element at the given multi-dimensional index.
+    pub fn set<T: ArrayElement>(&mut self, index: &[i64], value: T) -> Result<()> {
+        let linear_index = self.linear_index(index)?;
+        self.typed_buf_mut::<T>()?[linear_index] = value;
+        Ok(())
+    }
+
+    /// Extracts the sub-array `[start_indices, limit_indices)`.
+    pub fn slice(&self, start_indices: &[i64], limit_indices: &[i64]) -> Result<Literal> {
+        let shape = self.array_shape()?;
+        let dims = shape.dims();
+        if start_indices.len() != dims.len() || limit_indices.len() != dims.len() {
+            Err(Error::UnexpectedNumberOfDims {
+                expected: dims.len(),
+                got: start_indices.len().max(limit_indices.len()),
+                dims: dims.to_vec(),
+            })?
+        }
+        let valid = start_indices
+            .iter()
+            .zip(limit_indices.iter())
+            .zip(dims.iter())
+            .all(|((start, limit), dim)| 0 <= *start && start <= limit && limit <= dim);
+        if !valid {
+            Err(Error::InvalidSlice {
+                start_indices: start_indices.to_vec(),
+                limit_indices: limit_indices.to_vec(),
+                dims: dims.to_vec(),
+            })?
+        }
+        let start_ptr = start_indices.as_ptr();
+        let limit_ptr = limit_indices.as_ptr();
+        let rank = dims.len();
+        let lit = unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*", start_ptr as "const int64_t*", limit_ptr as "const int64_t*", rank as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>((*self)->Slice(absl::Span<const int64_t>(start_ptr, rank), absl::Span<const int64_t>(limit_ptr, rank)));
+            })
+        };
+        Ok(lit)
+    }
+
+    /// Broadcasts the literal by adding leading dimensions `dims`, similar to [`crate::XlaOp::broadcast`].
+    pub fn broadcast(&self, dims: &[i64]) -> Result<Literal> {
+        let shape = self.array_shape()?;
+        let rank = shape.dims().len();
+        let out_dims = dims
+            .iter()
+            .chain(shape.dims().iter())
+            .copied()
+            .collect::<Vec<_>>();
+        let broadcast_dims = (dims.len()..dims.len() + rank)
+            .map(|d| d as i64)
+            .collect::<Vec<_>>();
+        self.broadcast_in_dim(&out_dims, &broadcast_dims)
+    }
+
+    /// Broadcasts the literal to `out_dims`, dimension `i` of this literal being mapped to
+    /// dimension `broadcast_dims[i]` of the result.
+    pub fn broadcast_in_dim(&self, out_dims: &[i64], broadcast_dims: &[i64]) -> Result<Literal> {
+        let ty = self.array_shape()?.ty();
+        let shape = Shape::array_with_type(ty, out_dims.to_vec()).raw_shape();
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let broadcast_dims_ptr = broadcast_dims.as_ptr();
+        let broadcast_dims_len = broadcast_dims.len();
+        let lit = unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*", shape as "const Shape*", broadcast_dims_ptr as "const int64_t*", broadcast_dims_len as "size_t", out_status as "Status*"] -> Literal as "std::shared_ptr<Literal>" {
+                auto status = (*self)->Broadcast(*shape, absl::Span<const int64_t>(broadcast_dims_ptr, broadcast_dims_len));
+                if (status.ok()) {
+                    return std::make_shared<Literal>(std::move(status.value()));
+                }else{
+                    *out_status = Status(status.status());
+                    return std::make_shared<Literal>(Literal());
+                }
+            })
+        };
+        out_status.to_result()?;
+        Ok(lit)
+    }
+
+    /// Converts the elements of this literal to another element type.
+    pub fn convert(&self, ty: ElementType) -> Result<Literal> {
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let ty = ty.primitive_type() as i32;
+        let lit = unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*", ty as "int32_t", out_status as "Status*"] -> Literal as "std::shared_ptr<Literal>" {
+                auto status = (*self)->Convert((PrimitiveType)ty);
+                if (status.ok()) {
+                    return std::make_shared<Literal>(std::move(status.value()));
+                }else{
+                    *out_status = Status(status.status());
+                    return std::make_shared<Literal>(Literal());
+                }
+            })
+        };
+        out_status.to_result()?;
+        Ok(lit)
     }
 
     pub fn reshape(&self, dims: &[i64]) -> Result<Literal> {
@@ -89,6 +343,36 @@ impl Literal {
         Ok(lit)
     }
 
+    /// Copies the literal into a new one whose layout orders the dimensions as in
+    /// `minor_to_major`, a permutation of `0..rank` starting with the fastest varying dimension.
+    pub fn relayout(&self, minor_to_major: &[i64]) -> Result<Literal> {
+        let rank = self.array_shape()?.dims().len();
+        let mut sorted = minor_to_major.to_vec();
+        sorted.sort_unstable();
+        if !sorted.iter().copied().eq(0..rank as i64) {
+            Err(Error::InvalidLayout {
+                minor_to_major: minor_to_major.to_vec(),
+                rank,
+            })?
+        }
+        let ptr = minor_to_major.as_ptr();
+        let len = minor_to_major.len();
+        let lit = unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*", ptr as "const int64_t*", len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                auto layout = LayoutUtil::MakeLayout(absl::Span<const int64_t>(ptr, len));
+                return std::make_shared<Literal>((*self)->Relayout(layout));
+            })
+        };
+        Ok(lit)
+    }
+
+    /// Copies the literal to the row-major layout, in which [`Literal::typed_buf`] returns the
+    /// elements in the order of their indices.
+    pub(crate) fn to_row_major(&self) -> Result<Literal> {
+        let rank = self.array_shape()?.dims().len() as i64;
+        self.relayout(&(0..rank).rev().collect::<Vec<_>>())
+    }
+
     pub fn vector<T: NativeType>(vals: &[T]) -> Literal {
         T::create_r1(vals)
     }
@@ -109,6 +393,28 @@ impl Literal {
         self.raw_shape().shape()
     }
 
+    /// Returns a copy of the `index`-th element of a tuple literal, leaving the tuple untouched
+    /// unlike [`Literal::decompose_tuple`].
+    pub fn tuple_element(&self, index: usize) -> Result<Literal> {
+        let shape = self.shape()?;
+        let rank = match shape.tuple_size() {
+            Some(size) => size,
+            None => Err(Error::NotATuple { got: shape })?,
+        };
+        if index >= rank {
+            Err(Error::IndexOutOfBounds {
+                index: index as i64,
+                rank,
+            })?
+        }
+        let lit = unsafe {
+            cpp!([self as "std::shared_ptr<Literal>*", index as "int64_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralSlice(**self, {index}).Clone());
+            })
+        };
+        Ok(lit)
+    }
+
     pub fn decompose_tuple(&mut self) -> Result<Vec<Literal>> {
         match self.shape()? {
             Shape::Array(_) => Ok(vec![]),
@@ -1,6 +1,7 @@
-use crate::{Literal, XlaBuilder, XlaOp, XlaOpRaw};
+use crate::{ArrayElement, Literal, XlaBuilder, XlaOp, XlaOpRaw};
 use cpp::cpp;
-use zerocopy::{FromBytes, Immutable};
+use half::{bf16, f16};
+use num_complex::Complex;
 
 cpp! {{
     #include "xla/client/xla_builder.h"
@@ -16,18 +17,26 @@ cpp! {{
 
 /// A type implementing the `NativeType` trait can be directly converted to constant ops or
 /// literals.
-pub trait NativeType: Copy + FromBytes + Immutable {
+pub trait NativeType: ArrayElement {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp;
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp;
     fn literal(self) -> Literal;
     fn create_r1(slice: &[Self]) -> Literal;
 }
 
 impl NativeType for f64 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "double"] ->XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<double>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<double>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -36,12 +45,18 @@ impl NativeType for f64 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const double*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<double>(builder->get(), absl::Span<const double>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<double>(builder->get(), absl::Span<const double>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -70,10 +85,16 @@ impl NativeType for f64 {
 }
 
 impl NativeType for f32 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "float"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<float>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<float>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -82,12 +103,18 @@ impl NativeType for f32 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const float*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<float>(builder->get(), absl::Span<const float>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<float>(builder->get(), absl::Span<const float>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -116,10 +143,16 @@ impl NativeType for f32 {
 }
 
 impl NativeType for u64 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "uint64_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<uint64_t>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<uint64_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -128,12 +161,18 @@ impl NativeType for u64 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const uint64_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<uint64_t>(builder->get(), absl::Span<const uint64_t>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<uint64_t>(builder->get(), absl::Span<const uint64_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -162,10 +201,16 @@ impl NativeType for u64 {
 }
 
 impl NativeType for u32 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "uint32_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<uint32_t>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<uint32_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -174,12 +219,18 @@ impl NativeType for u32 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const uint32_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<uint32_t>(builder->get(), absl::Span<const uint32_t>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<uint32_t>(builder->get(), absl::Span<const uint32_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -208,10 +259,16 @@ impl NativeType for u32 {
 }
 
 impl NativeType for u16 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "uint16_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<uint16_t>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<uint16_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -220,12 +277,18 @@ impl NativeType for u16 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const uint16_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<uint16_t>(builder->get(), absl::Span<const uint16_t>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<uint16_t>(builder->get(), absl::Span<const uint16_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -254,10 +317,16 @@ impl NativeType for u16 {
 }
 
 impl NativeType for i64 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "int64_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<int64_t>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<int64_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -266,12 +335,18 @@ impl NativeType for i64 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const int64_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<int64_t>(builder->get(), absl::Span<const int64_t>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<int64_t>(builder->get(), absl::Span<const int64_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -300,10 +375,16 @@ impl NativeType for i64 {
 }
 
 impl NativeType for i32 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "int32_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<int32_t>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<int32_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -312,12 +393,18 @@ impl NativeType for i32 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const int32_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<int32_t>(builder->get(), absl::Span<const int32_t>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<int32_t>(builder->get(), absl::Span<const int32_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -346,10 +433,16 @@ impl NativeType for i32 {
 }
 
 impl NativeType for i16 {
+    #[track_caller]
     fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "int16_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR0<int16_t>(builder->get(), value));
+                try {
+                    return XlaOp(ConstantR0<int16_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -358,12 +451,18 @@ impl NativeType for i16 {
         }
     }
 
+    #[track_caller]
     fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
         let value_ptr = value.as_ptr();
         let value_len = value.len();
         let raw = unsafe {
             cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const int16_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(ConstantR1<int16_t>(builder->get(), absl::Span<const int16_t>(value_ptr, value_len)));
+                try {
+                    return XlaOp(ConstantR1<int16_t>(builder->get(), absl::Span<const int16_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -390,3 +489,417 @@ impl NativeType for i16 {
         }
     }
 }
+
+impl NativeType for u8 {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "uint8_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<uint8_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const uint8_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<uint8_t>(builder->get(), absl::Span<const uint8_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        unsafe {
+            cpp!([self as "uint8_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<uint8_t>(self));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const uint8_t*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<uint8_t>(absl::Span<const uint8_t>(value_ptr, value_len)));
+            })
+        }
+    }
+}
+
+impl NativeType for i8 {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "int8_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<int8_t>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const int8_t*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<int8_t>(builder->get(), absl::Span<const int8_t>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        unsafe {
+            cpp!([self as "int8_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<int8_t>(self));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const int8_t*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<int8_t>(absl::Span<const int8_t>(value_ptr, value_len)));
+            })
+        }
+    }
+}
+
+impl NativeType for bool {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "bool"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<bool>(builder->get(), value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const bool*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<bool>(builder->get(), absl::Span<const bool>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        unsafe {
+            cpp!([self as "bool"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<bool>(self));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const bool*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<bool>(absl::Span<const bool>(value_ptr, value_len)));
+            })
+        }
+    }
+}
+
+impl NativeType for f16 {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let bits = value.to_bits();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", bits as "uint16_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<Eigen::half>(builder->get(), Eigen::numext::bit_cast<Eigen::half>(bits)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const Eigen::half*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<Eigen::half>(builder->get(), absl::Span<const Eigen::half>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        let bits = self.to_bits();
+        unsafe {
+            cpp!([bits as "uint16_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<Eigen::half>(Eigen::numext::bit_cast<Eigen::half>(bits)));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const Eigen::half*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<Eigen::half>(absl::Span<const Eigen::half>(value_ptr, value_len)));
+            })
+        }
+    }
+}
+
+impl NativeType for bf16 {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let bits = value.to_bits();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", bits as "uint16_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<Eigen::bfloat16>(builder->get(), Eigen::numext::bit_cast<Eigen::bfloat16>(bits)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const Eigen::bfloat16*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<Eigen::bfloat16>(builder->get(), absl::Span<const Eigen::bfloat16>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        let bits = self.to_bits();
+        unsafe {
+            cpp!([bits as "uint16_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<Eigen::bfloat16>(Eigen::numext::bit_cast<Eigen::bfloat16>(bits)));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const Eigen::bfloat16*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<Eigen::bfloat16>(absl::Span<const Eigen::bfloat16>(value_ptr, value_len)));
+            })
+        }
+    }
+}
+
+impl NativeType for Complex<f32> {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let value = &value;
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "const std::complex<float>*"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<std::complex<float>>(builder->get(), *value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const std::complex<float>*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<std::complex<float>>(builder->get(), absl::Span<const std::complex<float>>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        let value = &self;
+        unsafe {
+            cpp!([value as "const std::complex<float>*"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<std::complex<float>>(*value));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const std::complex<float>*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<std::complex<float>>(absl::Span<const std::complex<float>>(value_ptr, value_len)));
+            })
+        }
+    }
+}
+
+impl NativeType for Complex<f64> {
+    #[track_caller]
+    fn constant_r0(builder: &XlaBuilder, value: Self) -> XlaOp {
+        let _site = builder.call_site();
+        let value = &value;
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value as "const std::complex<double>*"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR0<std::complex<double>>(builder->get(), *value));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    #[track_caller]
+    fn constant_r1(builder: &XlaBuilder, value: &[Self]) -> XlaOp {
+        let _site = builder.call_site();
+        let value_ptr = value.as_ptr();
+        let value_len = value.len();
+        let raw = unsafe {
+            cpp!([builder as "std::shared_ptr<XlaBuilder>*", value_ptr as "const std::complex<double>*", value_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ConstantR1<std::complex<double>>(builder->get(), absl::Span<const std::complex<double>>(value_ptr, value_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->get()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        XlaOp {
+            raw,
+            builder: builder.clone(),
+        }
+    }
+
+    fn literal(self) -> Literal {
+        let value = &self;
+        unsafe {
+            cpp!([value as "const std::complex<double>*"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR0<std::complex<double>>(*value));
+            })
+        }
+    }
+
+    fn create_r1(slice: &[Self]) -> Literal {
+        let value_ptr = slice.as_ptr();
+        let value_len = slice.len();
+        unsafe {
+            cpp!([value_ptr as "const std::complex<double>*", value_len as "size_t"] -> Literal as "std::shared_ptr<Literal>" {
+                return std::make_shared<Literal>(LiteralUtil::CreateR1<std::complex<double>>(absl::Span<const std::complex<double>>(value_ptr, value_len)));
+            })
+        }
+    }
+}
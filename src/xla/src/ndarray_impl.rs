@@ -0,0 +1,56 @@
+//! Conversions between [`Literal`]/[`PjRtBuffer`] and [`ndarray`] arrays.
+use crate::{ArrayElement, Error, Literal, PjRtBuffer, PjRtClient, Result};
+use ndarray::{ArrayD, ArrayView, Dimension, IxDyn};
+
+/// Returns the row-major elements of `view` along with its dimensions, copying only if the view
+/// is not already in standard layout.
+fn standard_layout<T: ArrayElement, D: Dimension>(
+    view: &ArrayView<'_, T, D>,
+) -> (ndarray::CowArray<'_, T, D>, Vec<i64>) {
+    let dims = view.shape().iter().map(|d| *d as i64).collect();
+    (view.as_standard_layout(), dims)
+}
+
+impl<T: ArrayElement> TryFrom<&Literal> for ArrayD<T> {
+    type Error = Error;
+
+    fn try_from(lit: &Literal) -> Result<Self> {
+        let shape = lit.array_shape()?;
+        let dims = shape.dims().iter().map(|d| *d as usize).collect::<Vec<_>>();
+        let data = lit.to_row_major()?.typed_buf::<T>()?.to_vec();
+        Ok(ArrayD::from_shape_vec(IxDyn(&dims), data)?)
+    }
+}
+
+impl<T: ArrayElement, D: Dimension> From<ArrayView<'_, T, D>> for Literal {
+    fn from(view: ArrayView<'_, T, D>) -> Self {
+        let (data, dims) = standard_layout(&view);
+        let data = data
+            .as_slice()
+            .expect("standard layout arrays are contiguous");
+        Literal::from_slice_with_shape(data, &dims)
+            .expect("element count and type match the array view")
+    }
+}
+
+impl PjRtClient {
+    /// Copies an ndarray view to the first device, views that are not in standard layout, e.g.
+    /// transposed or strided ones, are made contiguous first.
+    pub fn copy_ndarray<T: ArrayElement, D: Dimension>(
+        &self,
+        view: &ArrayView<'_, T, D>,
+    ) -> Result<PjRtBuffer> {
+        let (data, dims) = standard_layout(view);
+        let data = data
+            .as_slice()
+            .expect("standard layout arrays are contiguous");
+        self.copy_host_buffer(data, &dims)
+    }
+}
+
+impl PjRtBuffer {
+    /// Copies the buffer to the host as a dynamically dimensioned ndarray.
+    pub fn to_ndarray<T: ArrayElement>(&self) -> Result<ArrayD<T>> {
+        ArrayD::try_from(&self.to_literal_sync()?)
+    }
+}
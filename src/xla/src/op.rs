@@ -2,7 +2,7 @@ use cpp::{cpp, cpp_class};
 
 use super::ArrayShape;
 use crate::Result;
-use crate::{PrimitiveType, Status};
+use crate::{Error, PrimitiveType, RawShape, Shape, Status};
 use crate::{XlaBuilder, XlaComputation};
 use core::marker::PhantomData;
 use core::ops::{Add, Div, Mul, Sub};
@@ -17,6 +17,18 @@ cpp! {{
     #include "xla/statusor.h"
     #include "xla/literal_util.h"
     using namespace xla;
+
+    static std::vector<ReplicaGroup> MakeReplicaGroups(const int64_t* ids, const size_t* sizes, size_t len) {
+        std::vector<ReplicaGroup> groups;
+        for (size_t i = 0; i < len; ++i) {
+            ReplicaGroup group;
+            for (size_t j = 0; j < sizes[i]; ++j) {
+                group.add_replica_ids(*ids++);
+            }
+            groups.push_back(group);
+        }
+        return groups;
+    }
 }}
 
 cpp_class!(pub unsafe struct XlaOpRaw as "XlaOp");
@@ -29,6 +41,30 @@ pub struct XlaOp {
     pub(crate) builder: XlaBuilder,
 }
 
+/// The kind of transform computed by [`XlaOp::fft`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FftType {
+    /// Complex to complex forward transform.
+    Fft,
+    /// Complex to complex inverse transform.
+    Ifft,
+    /// Real to complex forward transform, only the non-negative frequencies are kept.
+    Rfft,
+    /// Inverse of [`FftType::Rfft`], producing real values.
+    Irfft,
+}
+
+impl FftType {
+    fn as_i32(&self) -> i32 {
+        match self {
+            Self::Fft => 0,
+            Self::Ifft => 1,
+            Self::Rfft => 2,
+            Self::Irfft => 3,
+        }
+    }
+}
+
 #[repr(transparent)]
 pub struct XlaOpRef<'a> {
     _raw: XlaOpRaw, // we directly cast `XlaOpRef` to `xla::XlaOp` in cpp so this field is actually used
@@ -47,18 +83,19 @@ impl XlaOp {
         let op = &self.raw;
         let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
         let comp = unsafe {
-            cpp!([op as "XlaOp*", out_status as "Status*"] -> XlaComputation as "XlaComputation" {
-                auto builder = op->builder();
-                auto status = builder->Build(*op, false);
+            cpp!([op as "XlaOp*", out_status as "Status*"] -> XlaComputation as "RustXlaComputation" {
+                auto status = BuildRustComputation(op->builder(), *op);
                 if (status.ok()) {
                     return std::move(status.value());
                 }else{
                     *out_status = Status(status.status());
-                    return XlaComputation();
+                    return RustXlaComputation();
                 }
             })
         };
-        out_status.to_result()?;
+        out_status
+            .to_result()
+            .map_err(|err| self.builder.with_call_site(err))?;
         Ok(comp)
     }
 
@@ -69,7 +106,9 @@ impl XlaOp {
         }
     }
 
+    #[track_caller]
     pub fn add(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -83,7 +122,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn sub(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -97,7 +138,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn mul(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -111,7 +154,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn div(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -125,7 +170,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn rem(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -139,7 +186,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn neg(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -153,7 +202,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn abs(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -167,7 +218,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn sqrt(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -181,7 +234,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn pow(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -195,7 +250,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn dot(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -209,7 +266,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn dot_general(&self, rhs: &Self, dims: DotDimensionNumbers) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let dims = ManuallyDrop::new(dims);
         let raw = unsafe {
@@ -224,7 +283,49 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    /// Convolves this `[batch, features, spatial...]` input with a kernel of shape
+    /// `[out_features, in_features / feature_group_count, spatial...]`, padding each spatial
+    /// dimension with `padding[i] = (low, high)` elements. The output has the layout of the
+    /// input.
+    #[track_caller]
+    pub fn conv_with_general_padding(
+        &self,
+        rhs: &Self,
+        window_strides: &[i64],
+        padding: &[(i64, i64)],
+        feature_group_count: i64,
+        batch_group_count: i64,
+    ) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let rhs = &rhs.raw;
+        let strides_ptr = window_strides.as_ptr();
+        let strides_len = window_strides.len();
+        let padding = padding
+            .iter()
+            .flat_map(|&(low, high)| [low, high])
+            .collect::<Vec<_>>();
+        let padding_ptr = padding.as_ptr();
+        let padding_len = padding.len() / 2;
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", rhs as "const XlaOp*", strides_ptr as "const int64_t*", strides_len as "size_t", padding_ptr as "const int64_t*", padding_len as "size_t", feature_group_count as "int64_t", batch_group_count as "int64_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    std::vector<std::pair<int64_t, int64_t>> padding;
+                    for (size_t i = 0; i < padding_len; ++i) {
+                        padding.emplace_back(padding_ptr[2 * i], padding_ptr[2 * i + 1]);
+                    }
+                    return XlaOp(ConvWithGeneralPadding(*op, *rhs, absl::Span(strides_ptr, strides_len), padding, feature_group_count, batch_group_count));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    #[track_caller]
     pub fn atan2(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -238,7 +339,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn max(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -252,7 +355,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn min(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -266,7 +371,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn or(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -280,7 +387,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn and(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -294,7 +403,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn xor(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -308,7 +419,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn eq(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -322,7 +435,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn ne(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -336,7 +451,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn ge(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -350,7 +467,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn gt(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -364,7 +483,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn le(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -378,7 +499,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn lt(&self, rhs: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", rhs as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -392,7 +515,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn not(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -406,7 +531,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn exp(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -420,7 +547,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn expm1(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -434,7 +563,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn floor(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -448,7 +579,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn ceil(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -462,7 +595,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn round(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -476,7 +611,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn log(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -490,7 +627,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn log1p(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -504,7 +643,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn logistic(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -518,7 +659,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn sign(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -532,7 +675,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn clz(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -546,7 +691,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn cos(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -560,7 +707,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn sin(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -574,7 +723,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn asin(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -588,7 +739,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn acos(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -602,7 +755,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn tanh(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -616,7 +771,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn real(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -630,7 +787,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn imag(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -644,7 +803,73 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    /// Fourier transform over the innermost `fft_length.len()` dimensions, at most 3.
+    ///
+    /// For [`FftType::Irfft`], `fft_length` gives the length of the real output, the innermost
+    /// input dimension having `fft_length / 2 + 1` elements.
+    #[track_caller]
+    pub fn fft(&self, fft_type: FftType, fft_length: &[i64]) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let fft_type = fft_type.as_i32();
+        let fft_length_ptr = fft_length.as_ptr();
+        let fft_length_len = fft_length.len();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", fft_type as "int32_t", fft_length_ptr as "const int64_t*", fft_length_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(Fft(*op, (FftType)fft_type, absl::Span(fft_length_ptr, fft_length_len)));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Fourier transform over the innermost `rank` dimensions, with lengths taken from the
+    /// operand shape. As in NumPy, [`FftType::Irfft`] produces `2 * (n - 1)` real values along
+    /// the innermost dimension.
+    #[track_caller]
+    pub fn fftn(&self, fft_type: FftType, rank: usize) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let irfft = fft_type == FftType::Irfft;
+        let fft_type = fft_type.as_i32();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", fft_type as "int32_t", rank as "size_t", irfft as "bool"] -> XlaOpRaw as "XlaOp" {
+                auto builder = op->builder();
+                try {
+                    auto shape = builder->GetShape(*op);
+                    if (!shape.ok()) {
+                        return XlaOp(builder->ReportError(shape.status()));
+                    }
+                    auto dims = shape->dimensions();
+                    if (rank > dims.size()) {
+                        return XlaOp(builder->ReportError(InvalidArgument(
+                            "fft over %d dimensions of a rank %d operand", rank, dims.size())));
+                    }
+                    std::vector<int64_t> fft_length(dims.end() - rank, dims.end());
+                    if (irfft && rank > 0) {
+                        fft_length.back() = 2 * (fft_length.back() - 1);
+                    }
+                    return XlaOp(Fft(*op, (FftType)fft_type, fft_length));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Fourier transform over the two innermost dimensions.
+    #[track_caller]
+    pub fn fft2(&self, fft_type: FftType) -> Self {
+        self.fftn(fft_type, 2)
+    }
+
+    #[track_caller]
     pub fn rsqrt(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -658,7 +883,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn cbrt(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -672,7 +899,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn is_finite(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -686,7 +915,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn lower_triangle(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -700,7 +931,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn upper_triangle(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -714,25 +947,23 @@ impl XlaOp {
         self.wrap(raw)
     }
 
-    /*pub fn einsum1(&self, config: &str,) -> Self {
-     * let op = &self.raw;
-        let raw = unsafe {
-            cpp!([op as "const XlaOp*", config as "const char*"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(Einsum(*op, config));
-            })
-        }
+    /// Einstein summation over this operand, e.g. `"ii->i"` for the diagonal, see
+    /// [`crate::einsum`].
+    #[track_caller]
+    pub fn einsum1(&self, config: &str) -> Result<Self> {
+        crate::einsum(config, &[self])
     }
 
-    pub fn einsum2(&self, arg2: &Self, config: &str) -> Self {
-    let op = &self.raw;
-        unsafe {
-            cpp!([op as "const XlaOp*", arg2 as "const XlaOp*", config as "const char*"] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(Einsum(*op, arg2, config));
-            })
-        }
-    }*/
+    /// Einstein summation over this operand and `arg2`, e.g. `"bij,bjk->bik"` for a batched
+    /// matrix product, see [`crate::einsum`].
+    #[track_caller]
+    pub fn einsum2(&self, arg2: &Self, config: &str) -> Result<Self> {
+        crate::einsum(config, &[self, arg2])
+    }
 
+    #[track_caller]
     pub fn clamp(&self, min: &Self, max: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", min as "const XlaOp*", max as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -746,7 +977,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn copy(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -760,7 +993,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn zeros_like(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -774,7 +1009,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn zero_like(&self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -789,7 +1026,26 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    /// A scalar holding `value` converted to the element type of this value.
+    #[track_caller]
+    pub fn scalar_like(&self, value: f64) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", value as "double"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return XlaOp(ScalarLike(*op, value));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    #[track_caller]
     pub fn reshape(&self, ds: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let ds_ptr = ds.as_ptr();
         let ds_len = ds.len();
@@ -805,7 +1061,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn broadcast(&self, ds: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let ds_ptr = ds.as_ptr();
         let ds_len = ds.len();
@@ -821,7 +1079,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn broadcast_in_dim(&self, dims: &[i64], broadcast_dims: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let dims_ptr = dims.as_ptr();
         let dims_len = dims.len();
@@ -839,7 +1099,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn collapse(&self, ds: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let ds_ptr = ds.as_ptr();
         let ds_len = ds.len();
@@ -855,7 +1117,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn transpose(&self, dims: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let dims_ptr = dims.as_ptr();
         let dims_len = dims.len();
@@ -871,7 +1135,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn select(&self, on_true: &Self, on_false: &Self) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", on_true as "const XlaOp*", on_false as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
@@ -885,7 +1151,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn rng_uniform(&self, sigma: &Self, shape: &ArrayShape) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let dims = shape.dims();
         let dims_ptr = dims.as_ptr();
@@ -904,7 +1172,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn rng_normal(&self, sigma: &Self, shape: &ArrayShape) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let dims = shape.dims();
         let dims_ptr = dims.as_ptr();
@@ -923,7 +1193,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn slice(&self, start_indices: &[i64], limit_indices: &[i64], strides: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let start_indices_ptr = start_indices.as_ptr();
         let start_indices_len = start_indices.len();
@@ -943,7 +1215,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn slice_in_dim(&self, start_index: i64, limit_index: i64, stride: i64, dim: i64) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", start_index as "int64_t", limit_index as "int64_t", dim as "int64_t", stride as "int64_t"] -> XlaOpRaw as "XlaOp" {
@@ -957,7 +1231,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn dynamic_slice(&self, start_indices: &[XlaOpRef<'_>], size_indices: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let start_indices_ptr = start_indices.as_ptr();
         let start_indices_len = start_indices.len();
@@ -975,7 +1251,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn dynamic_update_slice(&self, update: &XlaOp, start_indices: &[XlaOpRef<'_>]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let start_indices_ptr = start_indices.as_ptr();
         let start_indices_len = start_indices.len();
@@ -991,7 +1269,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn get_tuple_element(&self, index: i64) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", index as "int64_t"] -> XlaOpRaw as "XlaOp" {
@@ -1005,6 +1285,7 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn gather(
         &self,
         indices: &Self,
@@ -1014,6 +1295,7 @@ impl XlaOp {
         slice_sizes: &[i64],
         index_vector_dim: i64,
     ) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let offset_dims_ptr = offset_dims.as_ptr();
         let offset_dims_len = offset_dims.len();
@@ -1037,6 +1319,7 @@ impl XlaOp {
                 slice_sizes_len as "size_t",
                 index_vector_dim as "int64_t"
             ] -> XlaOpRaw as "XlaOp" {
+                try {
                     GatherDimensionNumbers dn;
                     for (size_t i = 0; i < offset_dims_len; ++i) {
                         dn.add_offset_dims(offset_dims_ptr[i]);
@@ -1050,12 +1333,17 @@ impl XlaOp {
                     dn.set_index_vector_dim(index_vector_dim);
                     auto ss = absl::Span<const int64_t>(slice_sizes_ptr, slice_sizes_len);
                     return XlaOp(Gather(*op, *indices, dn, ss));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn convert_element_type(&self, ty: PrimitiveType) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let ty = ty as i32;
         let raw = unsafe {
@@ -1070,7 +1358,9 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn get_dimension_size(&self, dim: i64) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", dim as "int64_t"] -> XlaOpRaw as "XlaOp" {
@@ -1084,14 +1374,16 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn reduce(&self, init_value: &Self, comp: &XlaComputation, dims: &[i64]) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let dims_ptr = dims.as_ptr();
         let dims_len = dims.len();
         let raw = unsafe {
-            cpp!([op as "const XlaOp*", init_value as "const XlaOp*", comp as "const XlaComputation*", dims_ptr as "const int64_t*", dims_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+            cpp!([op as "const XlaOp*", init_value as "const XlaOp*", comp as "const RustXlaComputation*", dims_ptr as "const int64_t*", dims_len as "size_t"] -> XlaOpRaw as "XlaOp" {
                 try {
-                    return XlaOp(Reduce(*op, *init_value, *comp, absl::Span(dims_ptr, dims_len)));
+                    return XlaOp(Reduce(*op, *init_value, EmbedRustComputation(op->builder(), *comp), absl::Span(dims_ptr, dims_len)));
                 }catch(std::exception& e) {
                     return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
                 }
@@ -1100,6 +1392,7 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    #[track_caller]
     pub fn conditional(
         &self,
         true_op: &Self,
@@ -1107,11 +1400,13 @@ impl XlaOp {
         false_op: &Self,
         on_false: &XlaComputation,
     ) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
-            cpp!([op as "const XlaOp*", true_op as "const XlaOp*", on_true as "const XlaComputation*", false_op as "const XlaOp*", on_false as "const XlaComputation*"] -> XlaOpRaw as "XlaOp" {
+            cpp!([op as "const XlaOp*", true_op as "const XlaOp*", on_true as "const RustXlaComputation*", false_op as "const XlaOp*", on_false as "const RustXlaComputation*"] -> XlaOpRaw as "XlaOp" {
                 try {
-                    return XlaOp(Conditional(*op, *true_op, *on_true, *false_op, *on_false));
+                    auto builder = op->builder();
+                    return XlaOp(Conditional(*op, *true_op, EmbedRustComputation(builder, *on_true), *false_op, EmbedRustComputation(builder, *on_false)));
                 }catch(std::exception& e) {
                     return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
                 }
@@ -1121,6 +1416,7 @@ impl XlaOp {
     }
 
     #[allow(clippy::too_many_arguments)]
+    #[track_caller]
     pub fn scatter(
         &self,
         inputs: &[XlaOpRef<'_>],
@@ -1131,6 +1427,7 @@ impl XlaOp {
         indices_are_sorted: bool,
         unique_indices: bool,
     ) -> XlaOp {
+        let _site = self.builder.call_site();
         let inputs_ptr = inputs.as_ptr();
         let inputs_len = inputs.len();
         let updates_ptr = updates.as_ptr();
@@ -1140,23 +1437,27 @@ impl XlaOp {
             cpp!([
                 inputs_ptr as "const XlaOp*", inputs_len as "size_t",
                 scatter_indices as "XlaOp*",
-                update_comp as "const XlaComputation*",
+                update_comp as "const RustXlaComputation*",
                 updates_ptr as "const XlaOp*", updates_len as "size_t",
                 scatter_dimension_numbers as "std::unique_ptr<ScatterDimensionNumbers>",
                 indices_are_sorted as "bool",
                 unique_indices as "bool"
             ] -> XlaOpRaw as "XlaOp" {
-                return XlaOp(
-                    Scatter(
-                        absl::Span(inputs_ptr, inputs_len),
-                        *scatter_indices,
-                        absl::Span(updates_ptr, updates_len),
-                        *update_comp,
-                        *scatter_dimension_numbers.get(),
-                        indices_are_sorted,
-                        unique_indices
-                    )
-                );
+                try {
+                    return XlaOp(
+                        Scatter(
+                            absl::Span(inputs_ptr, inputs_len),
+                            *scatter_indices,
+                            absl::Span(updates_ptr, updates_len),
+                            EmbedRustComputation(scatter_indices->builder(), *update_comp),
+                            *scatter_dimension_numbers.get(),
+                            indices_are_sorted,
+                            unique_indices
+                        )
+                    );
+                }catch(std::exception& e) {
+                    return XlaOp(scatter_indices->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
             })
         };
         XlaOp {
@@ -1165,7 +1466,9 @@ impl XlaOp {
         }
     }
 
+    #[track_caller]
     pub fn cholesky(&self, lower: bool) -> Self {
+        let _site = self.builder.call_site();
         let op = &self.raw;
         let raw = unsafe {
             cpp!([op as "const XlaOp*", lower as "bool"] -> XlaOpRaw as "XlaOp" {
@@ -1180,15 +1483,184 @@ impl XlaOp {
         self.wrap(raw)
     }
 
+    /// Reduces the operand across replicas with `comp`.
+    ///
+    /// An empty `replica_groups` reduces across all replicas.
+    #[track_caller]
+    pub fn all_reduce(&self, comp: &XlaComputation, replica_groups: &[Vec<i64>]) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let (ids, sizes) = flatten_replica_groups(replica_groups);
+        let ids_ptr = ids.as_ptr();
+        let sizes_ptr = sizes.as_ptr();
+        let groups_len = sizes.len();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", comp as "const RustXlaComputation*", ids_ptr as "const int64_t*", sizes_ptr as "const size_t*", groups_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto groups = MakeReplicaGroups(ids_ptr, sizes_ptr, groups_len);
+                    return XlaOp(AllReduce(*op, EmbedRustComputation(op->builder(), *comp), groups));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Concatenates the operand from every replica along `dim`.
+    #[track_caller]
+    pub fn all_gather(&self, dim: i64, shard_count: i64, replica_groups: &[Vec<i64>]) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let (ids, sizes) = flatten_replica_groups(replica_groups);
+        let ids_ptr = ids.as_ptr();
+        let sizes_ptr = sizes.as_ptr();
+        let groups_len = sizes.len();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", dim as "int64_t", shard_count as "int64_t", ids_ptr as "const int64_t*", sizes_ptr as "const size_t*", groups_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto groups = MakeReplicaGroups(ids_ptr, sizes_ptr, groups_len);
+                    return XlaOp(AllGather(*op, dim, shard_count, groups));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Reduces the operand across replicas with `comp` and leaves each replica with its shard of
+    /// the result along `scatter_dim`.
+    #[track_caller]
+    pub fn reduce_scatter(
+        &self,
+        comp: &XlaComputation,
+        scatter_dim: i64,
+        shard_count: i64,
+        replica_groups: &[Vec<i64>],
+    ) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let (ids, sizes) = flatten_replica_groups(replica_groups);
+        let ids_ptr = ids.as_ptr();
+        let sizes_ptr = sizes.as_ptr();
+        let groups_len = sizes.len();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", comp as "const RustXlaComputation*", scatter_dim as "int64_t", shard_count as "int64_t", ids_ptr as "const int64_t*", sizes_ptr as "const size_t*", groups_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto groups = MakeReplicaGroups(ids_ptr, sizes_ptr, groups_len);
+                    return XlaOp(ReduceScatter(*op, EmbedRustComputation(op->builder(), *comp), scatter_dim, shard_count, groups));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Splits the operand into `split_count` blocks along `split_dim`, sends block `i` to replica
+    /// `i` and concatenates the received blocks along `concat_dim`.
+    #[track_caller]
+    pub fn all_to_all(
+        &self,
+        split_dim: i64,
+        concat_dim: i64,
+        split_count: i64,
+        replica_groups: &[Vec<i64>],
+    ) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let (ids, sizes) = flatten_replica_groups(replica_groups);
+        let ids_ptr = ids.as_ptr();
+        let sizes_ptr = sizes.as_ptr();
+        let groups_len = sizes.len();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", split_dim as "int64_t", concat_dim as "int64_t", split_count as "int64_t", ids_ptr as "const int64_t*", sizes_ptr as "const size_t*", groups_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto groups = MakeReplicaGroups(ids_ptr, sizes_ptr, groups_len);
+                    return XlaOp(AllToAll(*op, split_dim, concat_dim, split_count, groups));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Sends the operand from each `source` replica to its `target` replica. Replicas that are not
+    /// a target of any pair receive zeros.
+    #[track_caller]
+    pub fn collective_permute(&self, source_target_pairs: &[(i64, i64)]) -> Self {
+        let _site = self.builder.call_site();
+        let op = &self.raw;
+        let pairs = source_target_pairs
+            .iter()
+            .flat_map(|&(source, target)| [source, target])
+            .collect::<Vec<_>>();
+        let pairs_ptr = pairs.as_ptr();
+        let pairs_len = source_target_pairs.len();
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", pairs_ptr as "const int64_t*", pairs_len as "size_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    std::vector<std::pair<int64_t, int64_t>> pairs;
+                    for (size_t i = 0; i < pairs_len; ++i) {
+                        pairs.emplace_back(pairs_ptr[2 * i], pairs_ptr[2 * i + 1]);
+                    }
+                    return XlaOp(CollectivePermute(*op, pairs));
+                }catch(std::exception& e) {
+                    return XlaOp(op->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// The shape of this value, as inferred by the builder.
+    pub fn shape(&self) -> Result<Shape> {
+        let op = &self.raw;
+        let out_status: Pin<&mut Status> = std::pin::pin!(Status::ok());
+        let shape = unsafe {
+            cpp!([op as "const XlaOp*", out_status as "Status*"] -> RawShape as "Shape" {
+                auto shape = op->builder()->GetShape(*op);
+                if (shape.ok()) {
+                    return shape.value();
+                }else{
+                    *out_status = Status(shape.status());
+                    return Shape();
+                }
+            })
+        };
+        out_status.to_result()?;
+        shape.shape()
+    }
+
+    /// The shape of this value, returning an error for tuples.
+    pub fn array_shape(&self) -> Result<ArrayShape> {
+        match self.shape()? {
+            Shape::Array(shape) => Ok(shape),
+            got => Err(Error::NotAnArray {
+                expected: None,
+                got,
+            }),
+        }
+    }
+
     pub fn builder(&self) -> &XlaBuilder {
         &self.builder
     }
 }
 
+fn flatten_replica_groups(groups: &[Vec<i64>]) -> (Vec<i64>, Vec<usize>) {
+    let ids = groups.iter().flatten().copied().collect();
+    let sizes = groups.iter().map(|g| g.len()).collect();
+    (ids, sizes)
+}
+
 macro_rules! bin_op_impl {
     ($trait:ident, $op:tt) => {
         impl $trait for XlaOp {
             type Output = XlaOp;
+            #[track_caller]
             fn $op(self, rhs: Self) -> Self {
                 XlaOp::$op(&self, &rhs)
             }
@@ -1196,6 +1668,7 @@ macro_rules! bin_op_impl {
 
         impl<'a> $trait<&'a Self> for &'a XlaOp {
             type Output = XlaOp;
+            #[track_caller]
             fn $op(self, rhs: &'a Self) -> XlaOp {
                 XlaOp::$op(self, rhs)
             }
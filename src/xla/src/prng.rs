@@ -0,0 +1,314 @@
+//! A stateless, key-based pseudo random number generator in the style of `jax.random`.
+//!
+//! Keys are values in the computation graph. Sampling never updates a key, so the same key
+//! always yields the same numbers: [`PrngKey::split`] and [`PrngKey::fold_in`] derive new
+//! independent keys instead. All samplers are built on XLA's `RngBitGenerator` with an
+//! explicit algorithm, so that results are bit-identical across runs and backends.
+use crate::{ArrayShape, XlaBuilder, XlaOp, XlaOpRaw};
+
+use cpp::cpp;
+
+cpp! {{
+    #include "xla/client/xla_builder.h"
+    #include "xla/client/lib/arithmetic.h"
+    #include "xla/client/lib/comparators.h"
+    #include "xla/client/lib/constants.h"
+    #include "xla/client/lib/math.h"
+    using namespace xla;
+
+    static XlaOp RngBits(XlaOp state, int32_t algorithm, const Shape& shape) {
+        return GetTupleElement(RngBitGenerator((RandomAlgorithm)algorithm, state, shape), 1);
+    }
+
+    // Uniform values in [0, 1), obtained by filling the mantissa of a float in [1, 2).
+    static XlaOp Uniform01(XlaOp state, int32_t algorithm, PrimitiveType ty, absl::Span<const int64_t> dims) {
+        XlaBuilder* b = state.builder();
+        if (ty == F64) {
+            auto bits = RngBits(state, algorithm, ShapeUtil::MakeShape(U64, dims));
+            auto mantissa = Or(ShiftRightLogical(bits, ConstantR0<uint64_t>(b, 12)),
+                               ConstantR0<uint64_t>(b, 0x3FF0000000000000ull));
+            return Sub(BitcastConvertType(mantissa, F64), ConstantR0<double>(b, 1.0));
+        }
+        if (ty != F32 && ty != F16 && ty != BF16) {
+            return b->ReportError(InvalidArgument(
+                "random floats must be f16, bf16, f32 or f64, got %s",
+                PrimitiveType_Name(ty)));
+        }
+        auto bits = RngBits(state, algorithm, ShapeUtil::MakeShape(U32, dims));
+        auto mantissa = Or(ShiftRightLogical(bits, ConstantR0<uint32_t>(b, 9)),
+                           ConstantR0<uint32_t>(b, 0x3F800000u));
+        auto u = Sub(BitcastConvertType(mantissa, F32), ConstantR0<float>(b, 1.0f));
+        if (ty == F32) {
+            return u;
+        }
+        // Rounding to a narrower type could produce 1, keep the upper bound exclusive.
+        double below_one = ty == F16 ? 1.0 - std::ldexp(1.0, -11) : 1.0 - std::ldexp(1.0, -8);
+        return Min(ConvertElementType(u, ty), ConstantR0WithType(b, ty, below_one));
+    }
+
+    static XlaOp Uniform(XlaOp state, int32_t algorithm, PrimitiveType ty, absl::Span<const int64_t> dims,
+                         double minval, double maxval) {
+        XlaBuilder* b = state.builder();
+        auto u = Uniform01(state, algorithm, ty, dims);
+        auto lo = ConstantR0WithType(b, ty, minval);
+        auto hi = ConstantR0WithType(b, ty, maxval);
+        return Max(lo, Add(Mul(u, Sub(hi, lo)), lo));
+    }
+
+    // Samples with the inverse CDF method, computing in f32 for half precision types.
+    static XlaOp Normal(XlaOp state, int32_t algorithm, PrimitiveType ty, absl::Span<const int64_t> dims,
+                        double lower, double upper) {
+        PrimitiveType compute_ty = ty == F64 ? F64 : F32;
+        double lo = std::erf(lower / M_SQRT2);
+        double hi = std::erf(upper / M_SQRT2);
+        // The bounds must be excluded in the precision of the computation, `ErfInv` being
+        // infinite at -1 and 1.
+        double lo_open = compute_ty == F64 ? std::nextafter(lo, hi)
+                                           : std::nextafterf((float)lo, (float)hi);
+        double hi_open = compute_ty == F64 ? std::nextafter(hi, lo)
+                                           : std::nextafterf((float)hi, (float)lo);
+        auto u = Uniform(state, algorithm, compute_ty, dims, lo_open, hi);
+        u = Min(u, ScalarLike(u, hi_open));
+        auto z = Mul(ScalarLike(u, M_SQRT2), ErfInv(u));
+        if (std::isfinite(lower) || std::isfinite(upper)) {
+            z = Clamp(ScalarLike(z, std::nextafter(lower, upper)), z,
+                      ScalarLike(z, std::nextafter(upper, lower)));
+        }
+        return compute_ty == ty ? z : ConvertElementType(z, ty);
+    }
+}}
+
+/// The counter-based algorithm used to generate random bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RngAlgorithm {
+    ThreeFry,
+    Philox,
+}
+
+impl RngAlgorithm {
+    fn as_i32(&self) -> i32 {
+        match self {
+            Self::ThreeFry => 1,
+            Self::Philox => 2,
+        }
+    }
+}
+
+/// A key for the stateless PRNG, held as a `u64[2]` value of the computation.
+#[derive(Clone)]
+pub struct PrngKey {
+    state: XlaOp,
+    algorithm: RngAlgorithm,
+}
+
+impl PrngKey {
+    /// Creates a threefry key from a seed, like `jax.random.PRNGKey`.
+    #[track_caller]
+    pub fn new(builder: &XlaBuilder, seed: u64) -> Self {
+        Self::with_algorithm(builder, seed, RngAlgorithm::ThreeFry)
+    }
+
+    #[track_caller]
+    pub fn with_algorithm(builder: &XlaBuilder, seed: u64, algorithm: RngAlgorithm) -> Self {
+        let state = builder.constant_vector(&[0u64, seed]);
+        Self { state, algorithm }
+    }
+
+    /// Wraps an existing `u64[2]` key, e.g. a parameter of the computation.
+    pub fn from_state(state: XlaOp, algorithm: RngAlgorithm) -> Self {
+        Self { state, algorithm }
+    }
+
+    /// The `u64[2]` key value, which can be returned from a computation to be reused later.
+    pub fn state(&self) -> &XlaOp {
+        &self.state
+    }
+
+    pub fn algorithm(&self) -> RngAlgorithm {
+        self.algorithm
+    }
+
+    fn wrap(&self, raw: XlaOpRaw) -> XlaOp {
+        XlaOp {
+            raw,
+            builder: self.state.builder.clone(),
+        }
+    }
+
+    /// Raw random bits with the given unsigned integer shape.
+    #[track_caller]
+    pub fn bits(&self, shape: &ArrayShape) -> XlaOp {
+        let _site = self.state.builder.call_site();
+        let state = &self.state.raw;
+        let algorithm = self.algorithm.as_i32();
+        let dims = shape.dims();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len();
+        let prim_type = shape.primitive_type() as i32;
+        let raw = unsafe {
+            cpp!([state as "const XlaOp*", algorithm as "int32_t", dims_ptr as "const int64_t*", dims_len as "size_t", prim_type as "int32_t"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    auto shape = ShapeUtil::MakeShape((PrimitiveType)prim_type, absl::Span(dims_ptr, dims_len));
+                    return RngBits(*state, algorithm, shape);
+                }catch(std::exception& e) {
+                    return XlaOp(state->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Derives `n` new independent keys, like `jax.random.split`.
+    #[track_caller]
+    pub fn split(&self, n: usize) -> Vec<PrngKey> {
+        let _site = self.state.builder.call_site();
+        let keys = self.bits(&ArrayShape::new::<u64>(vec![n as i64, 2]));
+        (0..n as i64)
+            .map(|i| PrngKey {
+                state: keys.slice_in_dim(i, i + 1, 1, 0).reshape(&[2]),
+                algorithm: self.algorithm,
+            })
+            .collect()
+    }
+
+    /// Derives a new key from this one and an integer scalar `data`, like
+    /// `jax.random.fold_in`. This is typically used with a step or device index.
+    #[track_caller]
+    pub fn fold_in(&self, data: &XlaOp) -> PrngKey {
+        let _site = self.state.builder.call_site();
+        let state = &self.state.raw;
+        let algorithm = self.algorithm.as_i32();
+        let data = &data.raw;
+        let raw = unsafe {
+            cpp!([state as "const XlaOp*", algorithm as "int32_t", data as "const XlaOp*"] -> XlaOpRaw as "XlaOp" {
+                XlaBuilder* b = state->builder();
+                try {
+                    auto word = Reshape(ConvertElementType(*data, U64), {1});
+                    auto mixed = Xor(*state, ConcatInDim(b, {word, ConstantR1<uint64_t>(b, {0})}, 0));
+                    return RngBits(mixed, algorithm, ShapeUtil::MakeShape(U64, {2}));
+                }catch(std::exception& e) {
+                    return XlaOp(b->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        PrngKey {
+            state: self.wrap(raw),
+            algorithm: self.algorithm,
+        }
+    }
+
+    /// Floats sampled uniformly in `[minval, maxval)`.
+    #[track_caller]
+    pub fn uniform(&self, shape: &ArrayShape, minval: f64, maxval: f64) -> XlaOp {
+        let _site = self.state.builder.call_site();
+        let state = &self.state.raw;
+        let algorithm = self.algorithm.as_i32();
+        let dims = shape.dims();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len();
+        let prim_type = shape.primitive_type() as i32;
+        let raw = unsafe {
+            cpp!([state as "const XlaOp*", algorithm as "int32_t", dims_ptr as "const int64_t*", dims_len as "size_t", prim_type as "int32_t", minval as "double", maxval as "double"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return Uniform(*state, algorithm, (PrimitiveType)prim_type, absl::Span(dims_ptr, dims_len), minval, maxval);
+                }catch(std::exception& e) {
+                    return XlaOp(state->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Samples from a normal distribution truncated to `[lower, upper]`, infinite bounds give
+    /// the standard normal distribution.
+    #[track_caller]
+    pub fn truncated_normal(&self, shape: &ArrayShape, lower: f64, upper: f64) -> XlaOp {
+        let _site = self.state.builder.call_site();
+        let state = &self.state.raw;
+        let algorithm = self.algorithm.as_i32();
+        let dims = shape.dims();
+        let dims_ptr = dims.as_ptr();
+        let dims_len = dims.len();
+        let prim_type = shape.primitive_type() as i32;
+        let raw = unsafe {
+            cpp!([state as "const XlaOp*", algorithm as "int32_t", dims_ptr as "const int64_t*", dims_len as "size_t", prim_type as "int32_t", lower as "double", upper as "double"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    return Normal(*state, algorithm, (PrimitiveType)prim_type, absl::Span(dims_ptr, dims_len), lower, upper);
+                }catch(std::exception& e) {
+                    return XlaOp(state->builder()->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// Samples from the standard normal distribution.
+    #[track_caller]
+    pub fn normal(&self, shape: &ArrayShape) -> XlaOp {
+        self.truncated_normal(shape, f64::NEG_INFINITY, f64::INFINITY)
+    }
+
+    /// Booleans with the dimensions of `shape` that are true with probability `p`, drawn by
+    /// comparing uniform floats of the element type of `shape` to `p`.
+    #[track_caller]
+    pub fn bernoulli(&self, p: f64, shape: &ArrayShape) -> XlaOp {
+        let u = self.uniform(shape, 0., 1.);
+        let p = self
+            .state
+            .builder
+            .constant(p)
+            .convert_element_type(shape.primitive_type());
+        u.lt(&p)
+    }
+
+    /// Samples indices along `axis` with probabilities given by the softmax of `logits`, using
+    /// the Gumbel-max trick. The result has the shape of `logits` without `axis`, and type `s32`.
+    #[track_caller]
+    pub fn categorical(&self, logits: &XlaOp, axis: i64) -> XlaOp {
+        let _site = self.state.builder.call_site();
+        let state = &self.state.raw;
+        let algorithm = self.algorithm.as_i32();
+        let logits = &logits.raw;
+        let raw = unsafe {
+            cpp!([state as "const XlaOp*", algorithm as "int32_t", logits as "const XlaOp*", axis as "int64_t"] -> XlaOpRaw as "XlaOp" {
+                XlaBuilder* b = state->builder();
+                try {
+                    auto shape = b->GetShape(*logits);
+                    if (!shape.ok()) {
+                        return b->ReportError(shape.status());
+                    }
+                    auto ty = shape->element_type();
+                    auto u = Uniform01(*state, algorithm, ty, shape->dimensions());
+                    u = Max(u, MinPositiveNormalValue(b, ty));
+                    auto gumbel = Neg(Log(Neg(Log(u))));
+                    return ArgMax(Add(*logits, gumbel), S32, axis);
+                }catch(std::exception& e) {
+                    return XlaOp(b->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+
+    /// A random permutation of `0..n` as a `s32[n]` value.
+    #[track_caller]
+    pub fn permutation(&self, n: i64) -> XlaOp {
+        let _site = self.state.builder.call_site();
+        let state = &self.state.raw;
+        let algorithm = self.algorithm.as_i32();
+        let raw = unsafe {
+            cpp!([state as "const XlaOp*", algorithm as "int32_t", n as "int64_t"] -> XlaOpRaw as "XlaOp" {
+                XlaBuilder* b = state->builder();
+                try {
+                    auto keys = RngBits(*state, algorithm, ShapeUtil::MakeShape(U64, {n}));
+                    auto sorted = Sort({keys, Iota(b, S32, n)},
+                                       CreateScalarLtComputation({U64, S32}, b), 0, true);
+                    return GetTupleElement(sorted, 1);
+                }catch(std::exception& e) {
+                    return XlaOp(b->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        self.wrap(raw)
+    }
+}
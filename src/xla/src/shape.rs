@@ -3,7 +3,7 @@ use cpp::{cpp, cpp_class};
 use num_traits::FromPrimitive;
 cpp_class!(pub unsafe struct RawShape as "Shape");
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct ArrayShape {
     ty: ElementType,
     dims: Vec<i64>,
@@ -57,7 +57,7 @@ impl ArrayShape {
 }
 
 /// A shape specifies a primitive type as well as some array dimensions.
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum Shape {
     Tuple(Vec<Shape>),
     Array(ArrayShape),
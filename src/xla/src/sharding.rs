@@ -0,0 +1,298 @@
+use crate::{Error, Result, Shape, XlaBuilder, XlaOp, XlaOpRaw};
+
+use cpp::{cpp, cpp_class};
+use cxx::let_cxx_string;
+
+cpp! {{
+    #include "xla/client/xla_builder.h"
+    using namespace xla;
+}}
+
+cpp_class!(pub unsafe struct OpSharding as "OpSharding");
+
+/// A logical arrangement of devices as an n-dimensional array with named axes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Mesh {
+    axis_names: Vec<String>,
+    axis_sizes: Vec<i64>,
+    devices: Vec<i64>,
+}
+
+impl Mesh {
+    /// Create a mesh over devices `0..n` laid out in row-major order, where `n` is the product of
+    /// the axis sizes.
+    pub fn new(axes: &[(&str, i64)]) -> Self {
+        let device_count = axes.iter().map(|(_, size)| size).product::<i64>();
+        Self {
+            axis_names: axes.iter().map(|(name, _)| name.to_string()).collect(),
+            axis_sizes: axes.iter().map(|(_, size)| *size).collect(),
+            devices: (0..device_count).collect(),
+        }
+    }
+
+    /// Create a mesh over an explicit list of device ids laid out in row-major order.
+    pub fn with_devices(axes: &[(&str, i64)], devices: Vec<i64>) -> Result<Self> {
+        let mut mesh = Self::new(axes);
+        if mesh.devices.len() != devices.len() {
+            Err(Error::MeshDeviceCountMismatch {
+                axis_sizes: mesh.axis_sizes,
+                device_count: devices.len(),
+            })?
+        }
+        mesh.devices = devices;
+        Ok(mesh)
+    }
+
+    pub fn axis_names(&self) -> &[String] {
+        &self.axis_names
+    }
+
+    pub fn axis_sizes(&self) -> &[i64] {
+        &self.axis_sizes
+    }
+
+    pub fn devices(&self) -> &[i64] {
+        &self.devices
+    }
+
+    /// The total number of devices in the mesh.
+    pub fn size(&self) -> usize {
+        self.devices.len()
+    }
+
+    pub fn axis_size(&self, name: &str) -> Option<i64> {
+        self.axis_index(name).map(|i| self.axis_sizes[i])
+    }
+
+    fn axis_index(&self, name: &str) -> Option<usize> {
+        self.axis_names.iter().position(|n| n == name)
+    }
+}
+
+/// Describes how each dimension of an array is split over the axes of a [`Mesh`].
+///
+/// Dimension `i` is split over the axes in `spec[i]`, in major-to-minor order; an empty entry
+/// leaves the dimension whole. Mesh axes that are not mentioned replicate the array.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct PartitionSpec(Vec<Vec<String>>);
+
+impl PartitionSpec {
+    /// Create a spec where every dimension is split over at most one mesh axis.
+    pub fn new(dims: &[Option<&str>]) -> Self {
+        Self(
+            dims.iter()
+                .map(|axis| axis.iter().map(|a| a.to_string()).collect())
+                .collect(),
+        )
+    }
+
+    /// Create a spec where dimensions may be split over several mesh axes.
+    pub fn from_axes(dims: Vec<Vec<String>>) -> Self {
+        Self(dims)
+    }
+
+    pub fn dims(&self) -> &[Vec<String>] {
+        &self.0
+    }
+}
+
+/// How an array is distributed over the devices of a computation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Sharding {
+    /// Every device holds a full copy.
+    Replicated,
+    /// The array lives on a single device.
+    Maximal { device: i64 },
+    /// The array is tiled over a device mesh.
+    Tiled { mesh: Mesh, spec: PartitionSpec },
+}
+
+impl Sharding {
+    pub fn tiled(mesh: Mesh, spec: PartitionSpec) -> Self {
+        Self::Tiled { mesh, spec }
+    }
+
+    /// Check that a tiled sharding has a spec entry for each of the `shape` dimensions.
+    fn check_shape(&self, shape: &Shape) -> Result<()> {
+        let Self::Tiled { spec, .. } = self else {
+            return Ok(());
+        };
+        let rank = match shape {
+            Shape::Array(shape) => shape.dims().len(),
+            got => Err(Error::NotAnArray {
+                expected: Some(spec.dims().len()),
+                got: got.clone(),
+            })?,
+        };
+        if spec.dims().len() != rank {
+            Err(Error::PartitionSpecRankMismatch {
+                spec_len: spec.dims().len(),
+                rank,
+            })?
+        }
+        Ok(())
+    }
+
+    /// Lower the sharding to XLA's `OpSharding` proto.
+    pub fn to_proto(&self) -> Result<OpSharding> {
+        match self {
+            Self::Replicated => Ok(unsafe {
+                cpp!([] -> OpSharding as "OpSharding" {
+                    OpSharding sharding;
+                    sharding.set_type(OpSharding::REPLICATED);
+                    return sharding;
+                })
+            }),
+            Self::Maximal { device } => {
+                let device = *device;
+                Ok(unsafe {
+                    cpp!([device as "int64_t"] -> OpSharding as "OpSharding" {
+                        OpSharding sharding;
+                        sharding.set_type(OpSharding::MAXIMAL);
+                        sharding.add_tile_assignment_dimensions(1);
+                        sharding.add_tile_assignment_devices(device);
+                        return sharding;
+                    })
+                })
+            }
+            Self::Tiled { mesh, spec } => {
+                let (tile_dims, devices, replicate_last) = tile_assignment(mesh, spec)?;
+                let tile_dims_ptr = tile_dims.as_ptr();
+                let tile_dims_len = tile_dims.len();
+                let devices_ptr = devices.as_ptr();
+                let devices_len = devices.len();
+                Ok(unsafe {
+                    cpp!([tile_dims_ptr as "const int64_t*", tile_dims_len as "size_t", devices_ptr as "const int64_t*", devices_len as "size_t", replicate_last as "bool"] -> OpSharding as "OpSharding" {
+                        OpSharding sharding;
+                        sharding.set_type(OpSharding::OTHER);
+                        for (size_t i = 0; i < tile_dims_len; ++i) {
+                            sharding.add_tile_assignment_dimensions(tile_dims_ptr[i]);
+                        }
+                        for (size_t i = 0; i < devices_len; ++i) {
+                            sharding.add_tile_assignment_devices(devices_ptr[i]);
+                        }
+                        sharding.set_replicate_on_last_tile_dim(replicate_last);
+                        return sharding;
+                    })
+                })
+            }
+        }
+    }
+}
+
+/// Compute the tile assignment dimensions and the device order for a tiled sharding.
+///
+/// The mesh is transposed so that the axes used by each array dimension come first, in spec
+/// order, followed by the unused axes which become a trailing replication dimension.
+pub(crate) fn tile_assignment(
+    mesh: &Mesh,
+    spec: &PartitionSpec,
+) -> Result<(Vec<i64>, Vec<i64>, bool)> {
+    let mut perm = vec![];
+    let mut tile_dims = vec![];
+    for axes in spec.dims() {
+        let mut dim = 1;
+        for axis in axes {
+            let index = mesh
+                .axis_index(axis)
+                .ok_or_else(|| Error::UnknownMeshAxis {
+                    axis: axis.clone(),
+                    axes: mesh.axis_names.clone(),
+                })?;
+            if perm.contains(&index) {
+                Err(Error::DuplicateMeshAxis { axis: axis.clone() })?
+            }
+            perm.push(index);
+            dim *= mesh.axis_sizes[index];
+        }
+        tile_dims.push(dim);
+    }
+    let unused = (0..mesh.axis_sizes.len())
+        .filter(|i| !perm.contains(i))
+        .collect::<Vec<_>>();
+    let replicated = unused.iter().map(|&i| mesh.axis_sizes[i]).product::<i64>();
+    let replicate_last = replicated > 1;
+    if replicate_last {
+        tile_dims.push(replicated);
+    }
+    perm.extend(unused);
+
+    let mut strides = vec![1; mesh.axis_sizes.len()];
+    for i in (0..mesh.axis_sizes.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * mesh.axis_sizes[i + 1];
+    }
+    let permuted_sizes = perm.iter().map(|&i| mesh.axis_sizes[i]).collect::<Vec<_>>();
+    let devices = (0..mesh.devices.len() as i64)
+        .map(|mut flat| {
+            let mut offset = 0;
+            for (k, size) in permuted_sizes.iter().enumerate().rev() {
+                offset += (flat % size) * strides[perm[k]];
+                flat /= size;
+            }
+            mesh.devices[offset as usize]
+        })
+        .collect();
+    Ok((tile_dims, devices, replicate_last))
+}
+
+impl XlaOp {
+    /// Constrain the sharding of this value, like `jax.lax.with_sharding_constraint`.
+    #[track_caller]
+    pub fn with_sharding(&self, sharding: &Sharding) -> Result<XlaOp> {
+        let _site = self.builder.call_site();
+        sharding.check_shape(&self.shape()?)?;
+        let op = &self.raw;
+        let sharding = sharding.to_proto()?;
+        let raw = unsafe {
+            cpp!([op as "const XlaOp*", sharding as "OpSharding"] -> XlaOpRaw as "XlaOp" {
+                auto builder = op->builder();
+                try {
+                    auto shape = builder->GetShape(*op);
+                    if (!shape.ok()) {
+                        return XlaOp(builder->ReportError(shape.status()));
+                    }
+                    XlaScopedShardingAssignment scoped(builder, sharding);
+                    return XlaOp(CustomCall(builder, "Sharding", {*op}, shape.value()));
+                }catch(std::exception& e) {
+                    return XlaOp(builder->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        Ok(XlaOp {
+            raw,
+            builder: self.builder.clone(),
+        })
+    }
+}
+
+impl XlaBuilder {
+    /// Create a parameter whose layout across devices is described by `sharding`.
+    #[track_caller]
+    pub fn parameter_with_sharding(
+        &self,
+        num: i64,
+        shape: Shape,
+        name: &str,
+        sharding: &Sharding,
+    ) -> Result<XlaOp> {
+        let _site = self.call_site();
+        sharding.check_shape(&shape)?;
+        let raw_shape = shape.raw_shape();
+        let sharding = sharding.to_proto()?;
+        let_cxx_string!(name = name);
+        let raw = unsafe {
+            cpp!([self as "std::shared_ptr<XlaBuilder>*", num as "int64_t", name as "std::string*", raw_shape as "Shape", sharding as "OpSharding"] -> XlaOpRaw as "XlaOp" {
+                try {
+                    XlaScopedShardingAssignment scoped(self->get(), sharding);
+                    return XlaOp(Parameter((self->get()), num, raw_shape, *name));
+                }catch(std::exception& e) {
+                    return XlaOp((*self)->ReportError(tsl::errors::Internal(e.what())));
+                }
+            })
+        };
+        Ok(XlaOp {
+            raw,
+            builder: self.clone(),
+        })
+    }
+}
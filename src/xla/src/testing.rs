@@ -0,0 +1,151 @@
+//! Tolerant comparisons of literals, mostly useful in tests.
+use crate::{ArrayElement, ElementType, Error, Literal, Result, Shape};
+use half::{bf16, f16};
+use num_complex::Complex;
+
+fn to_complex<T: ArrayElement>(
+    lit: &Literal,
+    f: impl Fn(T) -> Complex<f64>,
+) -> Result<Vec<Complex<f64>>> {
+    Ok(lit.typed_buf::<T>()?.iter().map(|v| f(*v)).collect())
+}
+
+/// Widens every element to a `Complex<f64>` so that all element types share the same comparison.
+fn complex_values(lit: &Literal, ty: ElementType) -> Result<Vec<Complex<f64>>> {
+    let re = |v: f64| Complex::new(v, 0.);
+    match ty {
+        ElementType::Pred => to_complex(lit, |v: bool| re(v as u8 as f64)),
+        ElementType::S8 => to_complex(lit, |v: i8| re(v as f64)),
+        ElementType::S16 => to_complex(lit, |v: i16| re(v as f64)),
+        ElementType::S32 => to_complex(lit, |v: i32| re(v as f64)),
+        ElementType::S64 => to_complex(lit, |v: i64| re(v as f64)),
+        ElementType::U8 => to_complex(lit, |v: u8| re(v as f64)),
+        ElementType::U16 => to_complex(lit, |v: u16| re(v as f64)),
+        ElementType::U32 => to_complex(lit, |v: u32| re(v as f64)),
+        ElementType::U64 => to_complex(lit, |v: u64| re(v as f64)),
+        ElementType::F16 => to_complex(lit, |v: f16| re(v.to_f64())),
+        ElementType::Bf16 => to_complex(lit, |v: bf16| re(v.to_f64())),
+        ElementType::F32 => to_complex(lit, |v: f32| re(v as f64)),
+        ElementType::F64 => to_complex(lit, re),
+        ElementType::C64 => to_complex(lit, |v: Complex<f32>| {
+            Complex::new(v.re as f64, v.im as f64)
+        }),
+        ElementType::C128 => to_complex(lit, |v: Complex<f64>| v),
+    }
+}
+
+/// Returns the error between two elements, `None` meaning that they are considered equal.
+///
+/// NaNs compare equal to NaNs and infinities to infinities of the same sign, any other
+/// non-finite mismatch has an infinite error.
+fn element_error(
+    actual: Complex<f64>,
+    expected: Complex<f64>,
+    rtol: f64,
+    atol: f64,
+) -> Option<(f64, f64)> {
+    if actual.is_nan() || expected.is_nan() {
+        return if actual.is_nan() && expected.is_nan() {
+            None
+        } else {
+            Some((f64::INFINITY, f64::INFINITY))
+        };
+    }
+    if actual.is_infinite() || expected.is_infinite() {
+        return if actual == expected {
+            None
+        } else {
+            Some((f64::INFINITY, f64::INFINITY))
+        };
+    }
+    let abs_err = (actual - expected).norm();
+    let rel_err = abs_err / expected.norm();
+    if abs_err <= atol + rtol * expected.norm() {
+        None
+    } else {
+        Some((abs_err, rel_err))
+    }
+}
+
+fn format_value(v: Complex<f64>, ty: ElementType) -> String {
+    match ty {
+        ElementType::C64 | ElementType::C128 => v.to_string(),
+        _ => v.re.to_string(),
+    }
+}
+
+/// Unravels a row-major linear index.
+fn multi_index(mut linear_index: usize, dims: &[i64]) -> Vec<i64> {
+    let mut index = vec![0; dims.len()];
+    for (i, d) in dims.iter().enumerate().rev() {
+        let d = *d as usize;
+        index[i] = (linear_index % d) as i64;
+        linear_index /= d;
+    }
+    index
+}
+
+/// Checks that `actual` and `expected` have the same shape and element type, and that every
+/// element satisfies `|actual - expected| <= atol + rtol * |expected|`.
+///
+/// Tuples are compared element-wise. On mismatch, the returned [`Error::NotClose`] reports the
+/// number of differing elements along with the worst one.
+pub fn allclose(actual: &Literal, expected: &Literal, rtol: f64, atol: f64) -> Result<()> {
+    let actual_shape = actual.shape()?;
+    let expected_shape = expected.shape()?;
+    if actual_shape != expected_shape {
+        Err(Error::ShapeMismatch {
+            actual: actual_shape,
+            expected: expected_shape,
+        })?
+    }
+    let shape = match actual_shape {
+        Shape::Array(shape) => shape,
+        Shape::Tuple(shapes) => {
+            for i in 0..shapes.len() {
+                allclose(
+                    &actual.tuple_element(i)?,
+                    &expected.tuple_element(i)?,
+                    rtol,
+                    atol,
+                )?
+            }
+            return Ok(());
+        }
+    };
+    // Shapes compare without their layouts, so read both operands in row-major order.
+    let actual_values = complex_values(&actual.to_row_major()?, shape.ty())?;
+    let expected_values = complex_values(&expected.to_row_major()?, shape.ty())?;
+    let mut mismatches = 0;
+    let mut worst: Option<(usize, f64, f64)> = None;
+    for (i, (a, e)) in actual_values.iter().zip(expected_values.iter()).enumerate() {
+        if let Some((abs_err, rel_err)) = element_error(*a, *e, rtol, atol) {
+            mismatches += 1;
+            if worst.is_none_or(|(_, worst_err, _)| abs_err > worst_err) {
+                worst = Some((i, abs_err, rel_err))
+            }
+        }
+    }
+    match worst {
+        None => Ok(()),
+        Some((i, abs_err, rel_err)) => Err(Error::NotClose {
+            rtol,
+            atol,
+            mismatches,
+            element_count: actual_values.len(),
+            index: multi_index(i, shape.dims()),
+            actual: format_value(actual_values[i], shape.ty()),
+            expected: format_value(expected_values[i], shape.ty()),
+            abs_err,
+            rel_err,
+        }),
+    }
+}
+
+/// Panics if [`allclose`] fails, with a message describing the mismatch.
+#[track_caller]
+pub fn assert_allclose(actual: &Literal, expected: &Literal, rtol: f64, atol: f64) {
+    if let Err(err) = allclose(actual, expected, rtol, atol) {
+        panic!("assert_allclose failed: {err}")
+    }
+}
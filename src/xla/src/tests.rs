@@ -143,15 +143,1065 @@ fn test_iota() {
     );
 }
 
-// #[test]
-// fn tuple_literal() -> Result<()> {
-//     let x = crate::Literal::scalar(3.1f32);
-//     let y = crate::Literal::vector(&[4.2f32, 1.337f32]);
-//     let result = crate::Literal::tuple(vec![x, y]);
-//     assert_eq!(result.shape()?.tuple_size(), Some(2));
-//     let mut result = result;
-//     let result = result.decompose_tuple()?;
-//     assert_eq!(result[1].to_vec::<f32>()?, [4.2, 1.337]);
-//     assert_eq!(result[0].to_vec::<f32>()?, [3.1]);
-//     Ok(())
-// }
+#[test]
+fn tuple_literal() -> Result<()> {
+    let x = crate::Literal::scalar(3.1f32);
+    let y = crate::Literal::vector(&[4.2f32, 1.337f32]);
+    let result = crate::Literal::tuple(vec![x, y]);
+    assert_eq!(result.shape()?.tuple_size(), Some(2));
+    let mut result = result;
+    let result = result.decompose_tuple()?;
+    assert_eq!(result[1].typed_buf::<f32>()?, [4.2, 1.337]);
+    assert_eq!(result[0].typed_buf::<f32>()?, [3.1]);
+    Ok(())
+}
+
+fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct ThreadWaker(std::thread::Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = std::pin::pin!(fut);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(out) => return out,
+            Poll::Pending => std::thread::park(),
+        }
+    }
+}
+
+#[test]
+fn execute_async() -> Result<()> {
+    let client = PjRtClient::cpu()?;
+    let builder = XlaBuilder::new("test");
+    let a = builder.parameter(0, Shape::array::<f32>(vec![2]), "a")?;
+    let b = builder.parameter(1, Shape::array::<f32>(vec![2]), "b")?;
+    let comp = builder.build(&(a + b))?;
+    let exec = client.compile_with_default_options(&comp)?;
+    let a = client.copy_host_buffer_async(
+        vec![1.0f32, 2.0],
+        &[2],
+        HostBufferSemantics::ImmutableUntilTransferCompletes,
+    )?;
+    let b = client.copy_host_buffer_async(
+        vec![3.0f32, 4.0],
+        &[2],
+        HostBufferSemantics::ImmutableZeroCopy,
+    )?;
+    let (a, b) = (block_on(a)?, block_on(b)?);
+    let out = block_on(exec.execute_async(BufferArgsRef::from([&a, &b]))?)?;
+    out[0].block_until_ready()?;
+    let lit = block_on(out[0].to_literal_async())?;
+    assert_eq!(lit.typed_buf::<f32>()?, &[4.0, 6.0]);
+
+    // The host data of a transfer that fails to start is released with the callback.
+    let invalid = client.copy_host_buffer_async(
+        vec![1.0f32],
+        &[-1, -1],
+        HostBufferSemantics::ImmutableZeroCopy,
+    );
+    assert!(invalid.is_err());
+    Ok(())
+}
+
+#[test]
+fn to_literal_async_wait() -> Result<()> {
+    let client = PjRtClient::cpu()?;
+    let buf = client.copy_host_buffer(&[1i32, 2, 3], &[3])?;
+    let lit = buf.to_literal_async().wait()?;
+    assert_eq!(lit.typed_buf::<i32>()?, &[1, 2, 3]);
+    Ok(())
+}
+
+#[test]
+fn cpu_devices() -> Result<()> {
+    let client = PjRtClient::cpu_with_devices(4)?;
+    assert_eq!(client.device_count(), 4);
+    assert_eq!(client.addressable_device_count(), 4);
+    let ids = client.devices().iter().map(|d| d.id()).collect::<Vec<_>>();
+    assert_eq!(ids, [0, 1, 2, 3]);
+    let device = &client.addressable_devices()[2];
+    let buf = client.copy_host_buffer_to(&[1.0f32, 2.0], &[2], device)?;
+    assert_eq!(buf.to_literal_sync()?.typed_buf::<f32>()?, &[1.0, 2.0]);
+    Ok(())
+}
+
+#[test]
+fn execute_sharded() -> Result<()> {
+    let client = PjRtClient::cpu_with_devices(2)?;
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = builder.build(&(&x + &x))?;
+    let mut options = CompileOptions::default();
+    options.set_num_replicas(2);
+    let exec = client.compile_with_options(&comp, options)?;
+    let devices = client.addressable_devices();
+    let a = client.copy_host_buffer_to(&[1.0f32, 2.0], &[2], &devices[0])?;
+    let b = client.copy_host_buffer_to(&[3.0f32, 4.0], &[2], &devices[1])?;
+    let out = exec.execute_sharded(&[BufferArgsRef::from([&a]), BufferArgsRef::from([&b])])?;
+    assert_eq!(out.len(), 2);
+    assert_eq!(
+        out[0][0].to_literal_sync()?.typed_buf::<f32>()?,
+        &[2.0, 4.0]
+    );
+    assert_eq!(
+        out[1][0].to_literal_sync()?.typed_buf::<f32>()?,
+        &[6.0, 8.0]
+    );
+    // The options are shared by all devices and cannot differ between argument lists.
+    let mismatched = [
+        BufferArgsRef::from([&a]),
+        BufferArgsRef::from([&b]).untuple_result(true),
+    ];
+    assert!(matches!(
+        exec.execute_sharded(&mismatched),
+        Err(Error::InconsistentShardedArgs { device: 1, .. })
+    ));
+    Ok(())
+}
+
+fn sum_computation() -> Result<XlaComputation> {
+    let builder = XlaBuilder::new("sum");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![]), "x")?;
+    let y = builder.parameter(1, Shape::array::<f32>(vec![]), "y")?;
+    builder.build(&(x + y))
+}
+
+fn run_replicated(comp: &XlaComputation, inputs: &[&[f32]]) -> Result<Vec<Vec<f32>>> {
+    let client = PjRtClient::cpu_with_devices(inputs.len())?;
+    let mut options = CompileOptions::default();
+    options.set_num_replicas(inputs.len() as i64);
+    let exec = client.compile_with_options(comp, options)?;
+    let bufs = client
+        .addressable_devices()
+        .iter()
+        .zip(inputs)
+        .map(|(device, input)| client.copy_host_buffer_to(input, &[input.len() as i64], device))
+        .collect::<Result<Vec<_>>>()?;
+    let args = bufs
+        .iter()
+        .map(|b| BufferArgsRef::from([b]))
+        .collect::<Vec<_>>();
+    exec.execute_sharded(&args)?
+        .iter()
+        .map(|out| Ok(out[0].to_literal_sync()?.typed_buf::<f32>()?.to_vec()))
+        .collect()
+}
+
+#[test]
+fn all_reduce() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = builder.build(&x.all_reduce(&sum_computation()?, &[]))?;
+    let out = run_replicated(&comp, &[&[1.0, 2.0], &[3.0, 4.0]])?;
+    assert_eq!(out, [[4.0, 6.0], [4.0, 6.0]]);
+    Ok(())
+}
+
+#[test]
+fn all_gather_and_reduce_scatter() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = builder.build(&x.all_gather(0, 2, &[vec![0, 1]]))?;
+    let out = run_replicated(&comp, &[&[1.0, 2.0], &[3.0, 4.0]])?;
+    assert_eq!(out, [[1.0, 2.0, 3.0, 4.0], [1.0, 2.0, 3.0, 4.0]]);
+
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = builder.build(&x.reduce_scatter(&sum_computation()?, 0, 2, &[]))?;
+    let out = run_replicated(&comp, &[&[1.0, 2.0], &[3.0, 4.0]])?;
+    assert_eq!(out, [[4.0], [6.0]]);
+    Ok(())
+}
+
+#[test]
+fn all_to_all_and_permute() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = builder.build(&x.all_to_all(0, 0, 2, &[]))?;
+    let out = run_replicated(&comp, &[&[1.0, 2.0], &[3.0, 4.0]])?;
+    assert_eq!(out, [[1.0, 3.0], [2.0, 4.0]]);
+
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = builder.build(&x.collective_permute(&[(0, 1), (1, 0)]))?;
+    let out = run_replicated(&comp, &[&[1.0, 2.0], &[3.0, 4.0]])?;
+    assert_eq!(out, [[3.0, 4.0], [1.0, 2.0]]);
+    Ok(())
+}
+
+#[test]
+fn replica_id() -> Result<()> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![1]), "x")?;
+    let id = builder
+        .replica_id()
+        .convert_element_type(PrimitiveType::F32)
+        .reshape(&[1]);
+    let comp = builder.build(&(x + id))?;
+    let out = run_replicated(&comp, &[&[10.0], &[10.0], &[10.0]])?;
+    assert_eq!(out, [[10.0], [11.0], [12.0]]);
+    Ok(())
+}
+
+#[test]
+fn mesh_tile_assignment() -> Result<()> {
+    use crate::sharding::tile_assignment;
+    let mesh = Mesh::new(&[("x", 2), ("y", 3)]);
+    assert_eq!(mesh.size(), 6);
+    assert_eq!(mesh.axis_size("y"), Some(3));
+    // Device (x, y) of the mesh has id 3x + y.
+    let spec = PartitionSpec::new(&[Some("x"), Some("y")]);
+    assert_eq!(
+        tile_assignment(&mesh, &spec)?,
+        (vec![2, 3], vec![0, 1, 2, 3, 4, 5], false)
+    );
+    // A transposed spec iterates over x fastest.
+    let spec = PartitionSpec::new(&[Some("y"), Some("x")]);
+    assert_eq!(
+        tile_assignment(&mesh, &spec)?,
+        (vec![3, 2], vec![0, 3, 1, 4, 2, 5], false)
+    );
+    // Unused axes are replicated over the last tile dimension.
+    let spec = PartitionSpec::new(&[Some("y")]);
+    assert_eq!(
+        tile_assignment(&mesh, &spec)?,
+        (vec![3, 2], vec![0, 3, 1, 4, 2, 5], true)
+    );
+    let spec = PartitionSpec::new(&[None, Some("x")]);
+    assert_eq!(
+        tile_assignment(&mesh, &spec)?,
+        (vec![1, 2, 3], vec![0, 1, 2, 3, 4, 5], true)
+    );
+    let spec = PartitionSpec::from_axes(vec![vec!["y".to_string(), "x".to_string()]]);
+    assert_eq!(
+        tile_assignment(&mesh, &spec)?,
+        (vec![6], vec![0, 3, 1, 4, 2, 5], false)
+    );
+    let mesh = Mesh::with_devices(&[("x", 2), ("y", 2)], vec![3, 2, 1, 0])?;
+    let spec = PartitionSpec::new(&[Some("y"), Some("x")]);
+    assert_eq!(
+        tile_assignment(&mesh, &spec)?,
+        (vec![2, 2], vec![3, 1, 2, 0], false)
+    );
+
+    let mesh = Mesh::new(&[("x", 2), ("y", 3)]);
+    let sharding = Sharding::tiled(mesh.clone(), PartitionSpec::new(&[Some("z")]));
+    assert!(matches!(
+        sharding.to_proto(),
+        Err(Error::UnknownMeshAxis { .. })
+    ));
+    let sharding = Sharding::tiled(mesh, PartitionSpec::new(&[Some("x"), Some("x")]));
+    assert!(matches!(
+        sharding.to_proto(),
+        Err(Error::DuplicateMeshAxis { .. })
+    ));
+    assert!(Mesh::with_devices(&[("x", 2)], vec![0, 1, 2]).is_err());
+
+    // The spec must have one entry per dimension of the sharded value.
+    let sharding = Sharding::tiled(
+        Mesh::new(&[("x", 2)]),
+        PartitionSpec::new(&[Some("x"), None]),
+    );
+    let builder = XlaBuilder::new("test");
+    let shape = Shape::array::<f32>(vec![4]);
+    assert!(matches!(
+        builder.parameter_with_sharding(0, shape.clone(), "x", &sharding),
+        Err(Error::PartitionSpecRankMismatch {
+            spec_len: 2,
+            rank: 1
+        })
+    ));
+    let x = builder.parameter(0, shape, "x")?;
+    assert!(matches!(
+        x.with_sharding(&sharding),
+        Err(Error::PartitionSpecRankMismatch { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn spmd_partitioning() -> Result<()> {
+    let client = PjRtClient::cpu_with_devices(2)?;
+    let mesh = Mesh::new(&[("x", 2)]);
+    let sharding = Sharding::tiled(mesh, PartitionSpec::new(&[Some("x")]));
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter_with_sharding(0, Shape::array::<f32>(vec![4]), "x", &sharding)?;
+    let y = (&x + &x).with_sharding(&sharding)?;
+    let comp = builder.build(&y)?;
+    let mut options = CompileOptions::default();
+    options.set_num_partitions(2);
+    options.set_use_spmd_partitioning(true);
+    let exec = client.compile_with_options(&comp, options)?;
+    let devices = client.addressable_devices();
+    let a = client.copy_host_buffer_to(&[1.0f32, 2.0], &[2], &devices[0])?;
+    let b = client.copy_host_buffer_to(&[3.0f32, 4.0], &[2], &devices[1])?;
+    let out = exec.execute_sharded(&[BufferArgsRef::from([&a]), BufferArgsRef::from([&b])])?;
+    assert_eq!(
+        out[0][0].to_literal_sync()?.typed_buf::<f32>()?,
+        &[2.0, 4.0]
+    );
+    assert_eq!(
+        out[1][0].to_literal_sync()?.typed_buf::<f32>()?,
+        &[6.0, 8.0]
+    );
+    Ok(())
+}
+
+#[test]
+fn partition_id() -> Result<()> {
+    let client = PjRtClient::cpu_with_devices(2)?;
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![1]), "x")?;
+    let id = builder
+        .partition_id()
+        .convert_element_type(PrimitiveType::F32)
+        .reshape(&[1]);
+    let comp = builder.build(&(x + id))?;
+    // The SPMD partitioner rejects a partition id without a manual sharding, so every partition
+    // runs the computation as written.
+    let mut options = CompileOptions::default();
+    options.set_num_partitions(2);
+    let exec = client.compile_with_options(&comp, options)?;
+    let devices = client.addressable_devices();
+    let a = client.copy_host_buffer_to(&[10.0f32], &[1], &devices[0])?;
+    let b = client.copy_host_buffer_to(&[10.0f32], &[1], &devices[1])?;
+    let out = exec.execute_sharded(&[BufferArgsRef::from([&a]), BufferArgsRef::from([&b])])?;
+    assert_eq!(out[0][0].to_literal_sync()?.typed_buf::<f32>()?, &[10.0]);
+    assert_eq!(out[1][0].to_literal_sync()?.typed_buf::<f32>()?, &[11.0]);
+    Ok(())
+}
+
+fn aliased_increment(client: &PjRtClient) -> Result<PjRtLoadedExecutable> {
+    let builder = XlaBuilder::new("test");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let one = builder.constant(1f32);
+    let out = builder.tuple(&[(x + one).as_ref()]);
+    builder.setup_alias_with_index(0, &[], &[0])?;
+    client.compile_with_default_options(&builder.build(&out)?)
+}
+
+#[test]
+fn donate_owned_buffer() -> Result<()> {
+    let client = PjRtClient::cpu()?;
+    let exec = aliased_increment(&client)?;
+    let mut args = BufferArgsOwned::default().untuple_result(true);
+    args.push(client.copy_host_buffer(&[1.0f32, 2.0], &[2])?);
+    let out = exec.execute_buffers(args)?;
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<f32>()?, &[2.0, 3.0]);
+    Ok(())
+}
+
+#[test]
+fn donated_buffer_cannot_be_reused() -> Result<()> {
+    let client = PjRtClient::cpu()?;
+    let exec = aliased_increment(&client)?;
+    let mut args = BufferArgsOwned::default().untuple_result(true);
+    args.push(client.copy_host_buffer(&[1.0f32, 2.0], &[2])?);
+    let out = exec.execute_buffers(&args)?;
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<f32>()?, &[2.0, 3.0]);
+    assert!(matches!(
+        exec.execute_buffers(&args),
+        Err(Error::DonatedArgument { index: 0 })
+    ));
+    Ok(())
+}
+
+#[test]
+fn borrowed_buffer_is_not_donated() -> Result<()> {
+    let client = PjRtClient::cpu()?;
+    let exec = aliased_increment(&client)?;
+    let x = client.copy_host_buffer(&[1.0f32, 2.0], &[2])?;
+    let out = exec.execute_buffers(BufferArgsRef::from([&x]).untuple_result(true))?;
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<f32>()?, &[2.0, 3.0]);
+    assert!(!x.is_deleted());
+    assert_eq!(x.to_literal_sync()?.typed_buf::<f32>()?, &[1.0, 2.0]);
+    assert_ne!(
+        client.unsafe_buffer_pointer(&out[0])?,
+        client.unsafe_buffer_pointer(&x)?
+    );
+    Ok(())
+}
+
+#[test]
+fn donated_buffer_is_aliased_by_output() -> Result<()> {
+    let client = PjRtClient::cpu()?;
+    let exec = aliased_increment(&client)?;
+    let x = client.copy_host_buffer(&[1.0f32, 2.0], &[2])?;
+    let ptr = client.unsafe_buffer_pointer(&x)?;
+    let mut args = BufferArgsOwned::default().untuple_result(true);
+    args.push(x);
+    let out = exec.execute_buffers(args)?;
+    // The output reuses the memory of the donated input rather than a copy of it.
+    assert_eq!(client.unsafe_buffer_pointer(&out[0])?, ptr);
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<f32>()?, &[2.0, 3.0]);
+    Ok(())
+}
+
+fn round_trip<T: NativeType + PartialEq + std::fmt::Debug>(
+    client: &PjRtClient,
+    values: &[T],
+) -> Result<()> {
+    let lit = Literal::vector(values);
+    assert_eq!(lit.primitive_type()?.element_type()?, T::TY);
+    assert_eq!(lit.typed_buf::<T>()?, values);
+    assert_eq!(Literal::scalar(values[0]).typed_buf::<T>()?, &values[..1]);
+
+    let buf = client.copy_host_buffer(values, &[values.len() as i64])?;
+    assert_eq!(buf.to_literal_sync()?.typed_buf::<T>()?, values);
+
+    let builder = XlaBuilder::new("round_trip");
+    let cst = builder.constant_vector(values).build()?;
+    let exec = client.compile_with_default_options(&cst)?;
+    let out = exec.execute_buffers(BufferArgsRef::default())?;
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<T>()?, values);
+    Ok(())
+}
+
+#[test]
+fn native_types_round_trip() -> Result<()> {
+    use half::{bf16, f16};
+    use num_complex::Complex;
+    let client = PjRtClient::cpu()?;
+    round_trip(&client, &[true, false, true])?;
+    round_trip(&client, &[-1i8, 0, 127])?;
+    round_trip(&client, &[0u8, 1, 255])?;
+    round_trip(&client, &[-1i16, 0, 1])?;
+    round_trip(&client, &[0u16, 1, 65535])?;
+    round_trip(&client, &[-1i32, 0, 1])?;
+    round_trip(&client, &[0u32, 1, 2])?;
+    round_trip(&client, &[-1i64, 0, 1])?;
+    round_trip(&client, &[0u64, 1, 2])?;
+    round_trip(&client, &[f16::from_f32(0.5), f16::from_f32(-2.0)])?;
+    round_trip(&client, &[bf16::from_f32(0.5), bf16::from_f32(-2.0)])?;
+    round_trip(&client, &[0.5f32, -2.0])?;
+    round_trip(&client, &[0.5f64, -2.0])?;
+    round_trip(&client, &[Complex::new(1f32, -1.), Complex::new(0.5, 2.)])?;
+    round_trip(&client, &[Complex::new(1f64, -1.), Complex::new(0.5, 2.)])?;
+    Ok(())
+}
+
+#[test]
+fn literal_nd() -> Result<()> {
+    let lit = Literal::from_slice_with_shape(&[1i32, 2, 3, 4, 5, 6], &[2, 3])?;
+    assert_eq!(lit.array_shape()?.dims(), [2, 3]);
+    assert_eq!(lit.get::<i32>(&[1, 0])?, 4);
+    assert!(matches!(
+        lit.get::<i32>(&[2, 0]),
+        Err(Error::MultiIndexOutOfBounds { .. })
+    ));
+    assert!(matches!(
+        Literal::from_slice_with_shape(&[1i32, 2, 3], &[2, 2]),
+        Err(Error::WrongElementCount { .. })
+    ));
+
+    // Setting an element does not affect clones.
+    let mut other = lit.clone();
+    other.set(&[0, 2], 42i32)?;
+    assert_eq!(other.typed_buf::<i32>()?, [1, 2, 42, 4, 5, 6]);
+    assert_eq!(lit.typed_buf::<i32>()?, [1, 2, 3, 4, 5, 6]);
+
+    let slice = lit.slice(&[0, 1], &[2, 3])?;
+    assert_eq!(slice.array_shape()?.dims(), [2, 2]);
+    assert_eq!(slice.typed_buf::<i32>()?, [2, 3, 5, 6]);
+    assert!(matches!(
+        lit.slice(&[0, 2], &[2, 1]),
+        Err(Error::InvalidSlice { .. })
+    ));
+
+    let row = Literal::vector(&[1f32, 2.]);
+    let b = row.broadcast(&[2])?;
+    assert_eq!(b.typed_buf::<f32>()?, [1., 2., 1., 2.]);
+    let b = row.broadcast_in_dim(&[2, 2], &[0])?;
+    assert_eq!(b.typed_buf::<f32>()?, [1., 1., 2., 2.]);
+
+    let f = lit.convert(ElementType::F32)?;
+    assert_eq!(f.get::<f32>(&[1, 2])?, 6.);
+
+    let zeros = Literal::create_from_shape(&Shape::array::<f64>(vec![2, 2]));
+    assert_eq!(zeros.typed_buf::<f64>()?, [0.; 4]);
+    Ok(())
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn ndarray_round_trip() -> Result<()> {
+    use ndarray::{ArrayD, IxDyn, array};
+    let a = array![[1f32, 2., 3.], [4., 5., 6.]];
+    let lit = Literal::from(a.view());
+    assert_eq!(lit.array_shape()?.dims(), [2, 3]);
+    let back = ArrayD::<f32>::try_from(&lit)?;
+    assert_eq!(back, a.clone().into_dyn());
+
+    // Non-contiguous views are copied in logical order.
+    let client = PjRtClient::cpu()?;
+    let t = a.t();
+    let buf = client.copy_ndarray(&t)?;
+    let out = buf.to_ndarray::<f32>()?;
+    assert_eq!(out.shape(), [3, 2]);
+    assert_eq!(out, t.to_owned().into_dyn());
+    assert_eq!(out[IxDyn(&[2, 1])], 6.);
+
+    // Literals with a column-major layout are read in logical order too.
+    let lit = lit.relayout(&[0, 1])?;
+    assert_eq!(lit.typed_buf::<f32>()?, [1., 4., 2., 5., 3., 6.]);
+    assert_eq!(ArrayD::<f32>::try_from(&lit)?, a.into_dyn());
+    Ok(())
+}
+
+#[test]
+fn literal_display() -> Result<()> {
+    let lit = Literal::from_slice_with_shape(&[1f32, 2., 3., 4., 5., 6.], &[2, 3])?;
+    assert_eq!(
+        lit.to_string(),
+        "f32[2,3]\n[[1.0000, 2.0000, 3.0000],\n [4.0000, 5.0000, 6.0000]]"
+    );
+    assert_eq!(
+        format!("{lit:.1}"),
+        "f32[2,3]\n[[1.0, 2.0, 3.0],\n [4.0, 5.0, 6.0]]"
+    );
+    // The elements are printed at their indices whatever the layout.
+    assert_eq!(lit.relayout(&[0, 1])?.to_string(), lit.to_string());
+    assert_eq!(Literal::scalar(-7i32).to_string(), "s32[]\n-7");
+    assert_eq!(
+        Literal::vector(&[true, false]).to_string(),
+        "pred[2]\n[ true, false]"
+    );
+
+    let options = PrintOptions {
+        threshold: 4,
+        edge_items: 1,
+        ..Default::default()
+    };
+    let long = Literal::vector(&[1u8, 2, 3, 4, 5]);
+    assert_eq!(long.display(options).to_string(), "u8[5]\n[1, ..., 5]");
+
+    let tuple = Literal::tuple(vec![Literal::scalar(1i64), Literal::vector(&[2i64, 3])]);
+    assert_eq!(
+        tuple.to_string(),
+        "(s64[], s64[2])\n(\n  s64[]\n  1,\n  s64[2]\n  [2, 3],\n)"
+    );
+
+    let client = PjRtClient::cpu()?;
+    let buf = client.copy_host_buffer(&[half::f16::from_f32(0.5)], &[1])?;
+    assert_eq!(format!("{buf:.2}"), "f16[1]\n[0.50]");
+    Ok(())
+}
+
+#[test]
+fn literal_allclose() -> Result<()> {
+    let expected = Literal::vector(&[1f32, f32::NAN, f32::INFINITY, 100.]);
+    let actual = Literal::vector(&[1.0001f32, f32::NAN, f32::INFINITY, 100.01]);
+    assert_allclose(&actual, &expected, 1e-3, 1e-3);
+    allclose(&actual, &expected, 1e-3, 1e-3)?;
+
+    let err = allclose(&actual, &expected, 0., 1e-3).unwrap_err();
+    match err {
+        Error::NotClose {
+            mismatches,
+            element_count,
+            index,
+            ..
+        } => {
+            assert_eq!((mismatches, element_count), (1, 4));
+            assert_eq!(index, [3]);
+        }
+        err => panic!("unexpected error {err}"),
+    }
+
+    let nan = Literal::vector(&[1f32, 1., f32::NEG_INFINITY, 100.]);
+    assert!(matches!(
+        allclose(&nan, &expected, 1e-3, 1e-3),
+        Err(Error::NotClose { mismatches: 2, .. })
+    ));
+    assert!(matches!(
+        allclose(&expected.convert(ElementType::F64)?, &expected, 1e-3, 1e-3),
+        Err(Error::ShapeMismatch { .. })
+    ));
+    assert!(matches!(
+        allclose(&expected.reshape(&[2, 2])?, &expected, 1e-3, 1e-3),
+        Err(Error::ShapeMismatch { .. })
+    ));
+
+    let m = Literal::from_slice_with_shape(&[1i32, 2, 3, 4], &[2, 2])?;
+    let mut n = m.clone();
+    n.set(&[1, 0], 7i32)?;
+    assert!(matches!(
+        allclose(&n, &m, 0., 0.),
+        Err(Error::NotClose { ref index, .. }) if index == &[1, 0]
+    ));
+
+    // Operands with different layouts are compared index by index.
+    let column_major = m.relayout(&[0, 1])?;
+    allclose(&column_major, &m, 0., 0.)?;
+    assert!(matches!(
+        allclose(&n.relayout(&[0, 1])?, &m, 0., 0.),
+        Err(Error::NotClose { ref index, .. }) if index == &[1, 0]
+    ));
+    Ok(())
+}
+
+#[test]
+#[should_panic(expected = "assert_allclose failed")]
+fn assert_allclose_panics() {
+    let a = Literal::vector(&[1f64, 2.]);
+    let b = Literal::vector(&[1f64, 2.5]);
+    assert_allclose(&a, &b, 1e-5, 1e-8);
+}
+
+unsafe extern "C" fn double_f32(
+    out: *mut std::ffi::c_void,
+    ins: *const *const std::ffi::c_void,
+    opaque: *const std::ffi::c_char,
+    opaque_len: usize,
+    status: *mut std::ffi::c_void,
+) {
+    unsafe {
+        let opaque = std::slice::from_raw_parts(opaque as *const u8, opaque_len);
+        let len = match std::str::from_utf8(opaque)
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(len) => len,
+            None => return custom_call_set_failure(status, "invalid length"),
+        };
+        let x = std::slice::from_raw_parts(*ins as *const f32, len);
+        let out = std::slice::from_raw_parts_mut(out as *mut f32, len);
+        for (out, x) in out.iter_mut().zip(x.iter()) {
+            *out = 2. * x
+        }
+    }
+}
+
+fn run_custom_call(target: &str, opaque: &str, x: &[f32]) -> Result<Vec<f32>> {
+    let client = PjRtClient::cpu()?;
+    let builder = XlaBuilder::new("custom_call");
+    let shape = Shape::array::<f32>(vec![x.len() as i64]);
+    let p = builder.parameter(0, shape.clone(), "x")?;
+    let comp = builder
+        .custom_call(target, &[p.as_ref()], &shape, opaque)
+        .build()?;
+    let exec = client.compile_with_default_options(&comp)?;
+    let x = client.copy_host_buffer(x, &[x.len() as i64])?;
+    let out = exec.execute_buffers(BufferArgsRef::from([&x]))?;
+    Ok(out[0].to_literal_sync()?.typed_buf::<f32>()?.to_vec())
+}
+
+#[test]
+fn custom_call_extern_fn() -> Result<()> {
+    unsafe { register_custom_call_target("test_double_f32", double_f32)? };
+    assert!(matches!(
+        unsafe { register_custom_call_target("test_double_f32", double_f32) },
+        Err(Error::CustomCallAlreadyRegistered { .. })
+    ));
+    assert_eq!(
+        run_custom_call("test_double_f32", "3", &[1., 2., 3.])?,
+        [2., 4., 6.]
+    );
+    assert!(run_custom_call("test_double_f32", "?", &[1., 2., 3.]).is_err());
+    Ok(())
+}
+
+#[test]
+fn custom_call_closure() -> Result<()> {
+    register_custom_call("test_add_offset", |args, rets, opaque| {
+        let offset: f32 = opaque.parse().map_err(|_| Error::CastError)?;
+        assert_eq!(args[0].dims(), rets[0].dims());
+        let x = args[0].data::<f32>()?.to_vec();
+        for (out, x) in rets[0].data_mut::<f32>()?.iter_mut().zip(x) {
+            *out = x + offset
+        }
+        Ok(())
+    })?;
+    assert_eq!(
+        run_custom_call("test_add_offset", "0.5", &[1., 2.])?,
+        [1.5, 2.5]
+    );
+    assert!(run_custom_call("test_add_offset", "nan?", &[1., 2.]).is_err());
+    Ok(())
+}
+
+#[test]
+fn host_callback() -> Result<()> {
+    use std::sync::{Arc, Mutex};
+    let client = PjRtClient::cpu()?;
+    let builder = XlaBuilder::new("host_callback");
+    let shape = Shape::array::<f32>(vec![2]);
+    let x = builder.parameter(0, shape.clone(), "x")?;
+    let logged = Arc::new(Mutex::new(vec![]));
+    let log = logged.clone();
+    // The result of the logging callback is unused, it must not be removed.
+    builder.host_callback(&[x.as_ref()], &Shape::tuple(vec![]), move |args| {
+        log.lock()
+            .unwrap()
+            .push(args[0].typed_buf::<f32>().unwrap().to_vec());
+        vec![]
+    })?;
+    let y = builder.host_callback(&[x.as_ref()], &shape, |args| {
+        let x = args[0].typed_buf::<f32>().unwrap();
+        vec![Literal::vector(&[x[0] * 10., x[1] * 10.])]
+    })?;
+    let comp = (y + builder.constant(1f32)).build()?;
+    let exec = client.compile_with_default_options(&comp)?;
+    let x = client.copy_host_buffer(&[1f32, 2.], &[2])?;
+    let out = exec.execute_buffers(BufferArgsRef::from([&x]))?;
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<f32>()?, [11., 21.]);
+    assert_eq!(*logged.lock().unwrap(), [vec![1f32, 2.]]);
+    // The closures are dropped along with the last computation or executable calling them.
+    drop(comp);
+    assert_eq!(Arc::strong_count(&logged), 2);
+    drop(exec);
+    assert_eq!(Arc::strong_count(&logged), 1);
+
+    // Computations embedded in another one are kept alive by it.
+    let inner = XlaBuilder::new("inner");
+    let p = inner.parameter(0, shape.clone(), "p")?;
+    let log = logged.clone();
+    let inner = inner
+        .host_callback(&[p.as_ref()], &shape, move |args| {
+            let _ = &log;
+            args.to_vec()
+        })?
+        .build()?;
+    let outer = XlaBuilder::new("outer");
+    let q = outer.parameter(0, shape.clone(), "q")?;
+    let call = outer.call(&[q.as_ref()], &inner);
+    drop((p, inner));
+    let exec = client.compile_with_default_options(&call.build()?)?;
+    let out = exec.execute_buffers(BufferArgsRef::from([&x]))?;
+    assert_eq!(out[0].to_literal_sync()?.typed_buf::<f32>()?, [1., 2.]);
+    assert_eq!(Arc::strong_count(&logged), 2);
+    drop(exec);
+    assert_eq!(Arc::strong_count(&logged), 1);
+    Ok(())
+}
+
+fn run_prng<T: ArrayElement>(f: impl Fn(&PrngKey) -> XlaOp) -> Result<Vec<T>> {
+    let client = PjRtClient::cpu()?;
+    let builder = XlaBuilder::new("prng");
+    let key = PrngKey::new(&builder, 42);
+    let comp = f(&key).build()?;
+    let exec = client.compile_with_default_options(&comp)?;
+    let out = exec.execute_buffers(BufferArgsRef::default())?;
+    Ok(out[0].to_literal_sync()?.typed_buf::<T>()?.to_vec())
+}
+
+#[test]
+fn prng_reproducible() -> Result<()> {
+    let shape = ArrayShape::new::<f32>(vec![1000]);
+    let a = run_prng::<f32>(|k| k.uniform(&shape, -1., 1.))?;
+    let b = run_prng::<f32>(|k| k.uniform(&shape, -1., 1.))?;
+    assert_eq!(a, b);
+    assert!(a.iter().all(|v| (-1. ..1.).contains(v)));
+
+    let split = run_prng::<f32>(|k| k.split(2)[1].uniform(&shape, -1., 1.))?;
+    assert_ne!(a, split);
+    let folded = |i: u32| {
+        run_prng::<f32>(move |k| {
+            k.fold_in(&k.state().builder().constant(i))
+                .uniform(&shape, -1., 1.)
+        })
+    };
+    assert_eq!(folded(3)?, folded(3)?);
+    assert_ne!(folded(3)?, folded(4)?);
+    Ok(())
+}
+
+#[test]
+fn prng_samplers() -> Result<()> {
+    let n = 10000;
+    let normal = run_prng::<f32>(|k| k.normal(&ArrayShape::new::<f32>(vec![n])))?;
+    assert!(normal.iter().all(|v| v.is_finite()));
+    let mean = normal.iter().sum::<f32>() / n as f32;
+    let var = normal.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / n as f32;
+    assert!(mean.abs() < 0.05 && (var - 1.).abs() < 0.05, "{mean} {var}");
+
+    let truncated =
+        run_prng::<f64>(|k| k.truncated_normal(&ArrayShape::new::<f64>(vec![n]), -0.5, 1.))?;
+    assert!(truncated.iter().all(|v| (-0.5..=1.).contains(v)));
+
+    let coins = run_prng::<bool>(|k| k.bernoulli(0.25, &ArrayShape::new::<f32>(vec![n])))?;
+    let heads = coins.iter().filter(|c| **c).count() as f64 / n as f64;
+    assert!((heads - 0.25).abs() < 0.02, "{heads}");
+
+    let mut perm = run_prng::<i32>(|k| k.permutation(100))?;
+    assert_ne!(perm, (0..100).collect::<Vec<_>>());
+    perm.sort();
+    assert_eq!(perm, (0..100).collect::<Vec<_>>());
+
+    let picks = run_prng::<i32>(|k| {
+        let logits = k
+            .state()
+            .builder()
+            .constant_vector(&[0f32, 10., 0.])
+            .broadcast(&[50]);
+        k.categorical(&logits, 1)
+    })?;
+    assert_eq!(picks.len(), 50);
+    assert!(picks.iter().filter(|p| **p == 1).count() > 45);
+    Ok(())
+}
+
+fn run_op<T: ArrayElement>(op: XlaOp) -> Result<Vec<T>> {
+    let client = PjRtClient::cpu()?;
+    let exec = client.compile_with_default_options(&op.build()?)?;
+    let out = exec.execute_buffers(BufferArgsRef::default())?;
+    Ok(out[0].to_literal_sync()?.typed_buf::<T>()?.to_vec())
+}
+
+#[test]
+fn fft() -> Result<()> {
+    use num_complex::Complex;
+    // Building a computation clears its builder, so each one gets its own.
+    let x = || XlaBuilder::new("fft").constant_vector(&[1f32, 2., 3., 4.]);
+    let spectrum = || x().fft(FftType::Rfft, &[4]);
+    let close = |actual: &[Complex<f32>], expected: &[Complex<f32>]| {
+        assert_allclose(
+            &Literal::vector(actual),
+            &Literal::vector(expected),
+            1e-6,
+            1e-6,
+        )
+    };
+    let out = run_op::<Complex<f32>>(spectrum())?;
+    close(
+        &out,
+        &[
+            Complex::new(10., 0.),
+            Complex::new(-2., 2.),
+            Complex::new(-2., 0.),
+        ],
+    );
+    let back = run_op::<f32>(spectrum().fftn(FftType::Irfft, 1))?;
+    assert_allclose(
+        &Literal::vector(&back),
+        &Literal::vector(&[1f32, 2., 3., 4.]),
+        1e-6,
+        1e-6,
+    );
+
+    let c = || {
+        x().convert_element_type(PrimitiveType::C64)
+            .reshape(&[2, 2])
+    };
+    let out = run_op::<Complex<f32>>(c().fft2(FftType::Fft))?;
+    close(&out, &[10., -2., -4., 0.].map(|re| Complex::new(re, 0.)));
+    let back = run_op::<Complex<f32>>(c().fft2(FftType::Fft).fft2(FftType::Ifft))?;
+    close(&back, &[1., 2., 3., 4.].map(|re| Complex::new(re, 0.)));
+
+    let builder = XlaBuilder::new("fftfreq");
+    let freqs = run_op::<f32>(builder.fftfreq(5, 0.1, ElementType::F32))?;
+    assert_eq!(freqs, [0., 2., 4., -4., -2.]);
+    let builder = XlaBuilder::new("rfftfreq");
+    let freqs = run_op::<f64>(builder.rfftfreq(4, 1., ElementType::F64))?;
+    assert_eq!(freqs, [0., 0.25, 0.5]);
+    Ok(())
+}
+
+#[test]
+fn einsum() -> Result<()> {
+    // Building a computation clears its builder, so each case gets its own.
+    let range =
+        |b: &XlaBuilder, n: i64, dims: &[i64]| b.iota(&[n], ElementType::F32, 0).reshape(dims);
+    let ab = || {
+        let builder = XlaBuilder::new("einsum");
+        (range(&builder, 6, &[2, 3]), range(&builder, 6, &[3, 2]))
+    };
+    let (a, b) = ab();
+    assert_eq!(
+        run_op::<f32>(a.einsum2(&b, "ij,jk->ik")?)?,
+        [10., 13., 28., 40.]
+    );
+    // Implicit output, the remaining subscripts in alphabetical order.
+    let (a, b) = ab();
+    assert_eq!(
+        run_op::<f32>(a.einsum2(&b, "ij,jk")?)?,
+        [10., 13., 28., 40.]
+    );
+    let (a, _) = ab();
+    assert_eq!(
+        run_op::<f32>(a.einsum1("ij->ji")?)?,
+        [0., 3., 1., 4., 2., 5.]
+    );
+    let (a, _) = ab();
+    assert_eq!(run_op::<f32>(a.einsum1("ij->")?)?, [15.]);
+
+    let m = || range(&XlaBuilder::new("einsum"), 9, &[3, 3]);
+    assert_eq!(run_op::<f32>(m().einsum1("ii->")?)?, [12.]);
+    assert_eq!(run_op::<f32>(m().einsum1("ii->i")?)?, [0., 4., 8.]);
+    assert_eq!(run_op::<f32>(m().einsum1("ii")?)?, [12.]);
+
+    let builder = XlaBuilder::new("einsum");
+    let x = range(&builder, 12, &[2, 2, 3]);
+    let y = range(&builder, 6, &[3, 2]);
+    let bmm = x.einsum2(&y, "...ij,jk->...ik")?;
+    assert_eq!(bmm.array_shape()?.dims(), [2, 2, 2]);
+    assert_eq!(
+        run_op::<f32>(bmm)?,
+        [10., 13., 28., 40., 46., 67., 64., 94.]
+    );
+    // Size 1 ellipsis dimensions broadcast.
+    let builder = XlaBuilder::new("einsum");
+    let x = range(&builder, 12, &[4, 3]);
+    let z = range(&builder, 3, &[1, 3]);
+    let sum = einsum("...j,...j->...", &[&x, &z])?;
+    assert_eq!(run_op::<f32>(sum)?, [5., 14., 23., 32.]);
+
+    // N-ary contractions are evaluated pairwise.
+    let (a, b) = ab();
+    let v = range(a.builder(), 2, &[2]);
+    let chain = einsum("ij,jk,k->i", &[&a, &b, &v])?;
+    assert_eq!(run_op::<f32>(chain)?, [13., 40.]);
+
+    let (a, _) = ab();
+    let invalid = |spec: &str, operands: &[&XlaOp]| {
+        matches!(einsum(spec, operands), Err(Error::InvalidEinsum { .. }))
+    };
+    assert!(invalid("ij,jk->ik", &[&a, &a]));
+    assert!(invalid("ijk->i", &[&a]));
+    assert!(invalid("ij->ik", &[&a]));
+    assert!(invalid("ij->ii", &[&a]));
+    assert!(invalid("i1->i", &[&a]));
+    assert!(invalid("......->", &[&a]));
+    assert!(invalid("ij,jk->ik", &[&a]));
+    Ok(())
+}
+
+#[test]
+fn build_error_call_site() {
+    let builder = XlaBuilder::new("call_site");
+    let x = builder.constant_vector(&[1f32, 2.]);
+    let y = builder.constant_vector(&[1f32, 2., 3.]);
+    let expected_line = line!() + 1;
+    let sum = (&x + &y).sqrt();
+    match sum.build() {
+        Err(Error::BuildError { file, line, .. }) => {
+            assert!(file.ends_with("tests.rs"), "{file}");
+            assert_eq!(line, expected_line);
+        }
+        Err(err) => panic!("unexpected error {err}"),
+        Ok(_) => panic!("expected a shape error"),
+    }
+}
+
+#[test]
+fn structured_xla_errors() {
+    let builder = XlaBuilder::new("errors");
+    let x = builder.constant_vector(&[1f32, 2.]);
+    let y = builder.constant_vector(&[1f32, 2., 3.]);
+    let err = (x + y).build().unwrap_err();
+    assert_eq!(err.status_code(), Some(StatusCode::InvalidArgument));
+    let source = match err {
+        Error::BuildError { source, .. } => source,
+        err => panic!("unexpected error {err}"),
+    };
+    match *source {
+        Error::XlaShapeMismatch { expected, got, .. } => {
+            assert_eq!(expected, Shape::array::<f32>(vec![2]));
+            assert_eq!(got, Shape::array::<f32>(vec![3]));
+        }
+        err => panic!("unexpected error {err}"),
+    }
+
+    let (shape, rest) = crate::display::parse_shape("(f32[2,3]{1,0}, s32[]) and more").unwrap();
+    assert_eq!(
+        shape,
+        Shape::tuple(vec![
+            Shape::array::<f32>(vec![2, 3]),
+            Shape::array::<i32>(vec![])
+        ])
+    );
+    assert_eq!(rest, " and more");
+}
+
+#[test]
+fn computation_to_dot() -> Result<()> {
+    let builder = XlaBuilder::new("dot");
+    let x = builder.parameter(0, Shape::array::<f32>(vec![2]), "x")?;
+    let comp = (&x + &x).exp().build()?;
+    let dot = comp.to_dot()?;
+    assert!(dot.starts_with("digraph"));
+    assert!(dot.contains("exponential"));
+    Ok(())
+}
+
+#[test]
+fn device_array() -> Result<()> {
+    let client = PjRtClient::cpu_with_devices(2)?;
+    let x = DeviceArray::from_host(&client, &[1f32, 2., 3., 4., 5., 6.], &[2, 3])?;
+    assert_eq!(x.dims(), [2, 3]);
+    assert_eq!(x.element_type(), ElementType::F32);
+    assert!(matches!(
+        x.to_vec::<f64>(),
+        Err(Error::ElementTypeMismatch { .. })
+    ));
+
+    // Outputs are fed back to the executable without leaving the device.
+    let builder = XlaBuilder::new("test");
+    let p = builder.parameter(0, Shape::array::<f32>(vec![2, 3]), "p")?;
+    let exec = client.compile_with_default_options(&(&p + &p).build()?)?;
+    let mut y = x;
+    for _ in 0..3 {
+        let out = exec.execute_buffers(BufferArgsRef::from([&y]))?;
+        y = DeviceArray::new(&client, out.into_iter().next().unwrap())?;
+    }
+    y.block_until_ready()?;
+    assert_eq!(y.to_vec::<f32>()?, [8., 16., 24., 32., 40., 48.]);
+    assert_eq!(y.to_literal()?.get::<f32>(&[1, 0])?, 32.);
+
+    let device = &client.addressable_devices()[1];
+    let z = y.copy_to_device(device)?;
+    assert_eq!(z.buffer().to_literal_sync()?.typed_buf::<f32>()?[5], 48.);
+    assert_eq!(z.shape(), y.shape());
+    Ok(())
+}
+
+#[test]
+fn conv() -> Result<()> {
+    // Building a computation clears its builder, so each case gets its own.
+    let range =
+        |b: &XlaBuilder, n: i64, dims: &[i64]| b.iota(&[n], ElementType::F32, 0).reshape(dims);
+    let xk = || {
+        let builder = XlaBuilder::new("conv");
+        let k = builder.constant_vector(&[1f32; 3]).reshape(&[1, 1, 3]);
+        (range(&builder, 5, &[1, 1, 5]), k)
+    };
+    let (x, k) = xk();
+    assert_eq!(
+        run_op::<f32>(x.conv_with_general_padding(&k, &[1], &[(1, 1)], 1, 1))?,
+        [1., 3., 6., 9., 7.]
+    );
+    let (x, k) = xk();
+    assert_eq!(
+        run_op::<f32>(x.conv_with_general_padding(&k, &[2], &[(0, 0)], 1, 1))?,
+        [3., 9.]
+    );
+    // Depthwise, each of the two channels is convolved with its own kernel.
+    let builder = XlaBuilder::new("conv");
+    let x = range(&builder, 6, &[1, 2, 3]);
+    let k = builder.constant_vector(&[1f32; 4]).reshape(&[2, 1, 2]);
+    assert_eq!(
+        run_op::<f32>(x.conv_with_general_padding(&k, &[1], &[(0, 0)], 2, 1))?,
+        [1., 3., 7., 9.]
+    );
+    Ok(())
+}
+
+#[test]
+fn scalar_like() -> Result<()> {
+    let builder = XlaBuilder::new("scalar_like");
+    let x = builder.iota(&[3], ElementType::F32, 0);
+    assert_eq!(run_op::<f32>(x.add(&x.scalar_like(0.5)))?, [0.5, 1.5, 2.5]);
+    let n = XlaBuilder::new("scalar_like").constant(7i32);
+    assert_eq!(run_op::<i32>(n.scalar_like(2.9))?, [2]);
+    Ok(())
+}